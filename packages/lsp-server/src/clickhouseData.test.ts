@@ -77,6 +77,7 @@ describe('clickhouseData', () => {
       assert.ok(Array.isArray(data.formats));
       assert.ok(Array.isArray(data.tableFunctions));
       assert.ok(Array.isArray(data.aggregateCombinators));
+      assert.ok(Array.isArray(data.timeZones));
       assert.ok(Array.isArray(data.settings));
       assert.ok(Array.isArray(data.mergeTreeSettings));
     });
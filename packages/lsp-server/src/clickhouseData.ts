@@ -60,6 +60,7 @@ export interface ClickHouseData {
   formats: FormatInfo[];
   tableFunctions: TableFunctionInfo[];
   aggregateCombinators: string[];
+  timeZones: string[];
   settings: SettingInfo[];
   mergeTreeSettings: SettingInfo[];
   /** Warning message if loaded version differs from requested */
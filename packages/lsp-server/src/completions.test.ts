@@ -81,6 +81,7 @@ const testData: ClickHouseData = {
     { name: 'url', description: 'Reads from a URL.' },
   ],
   aggregateCombinators: ['If', 'Array'],
+  timeZones: ['UTC', 'Europe/Berlin'],
   settings: [
     {
       name: 'max_threads',
@@ -57,6 +57,51 @@ test('createLocationDiagnostic Tests', async (t) => {
     assert.strictEqual(diagnostic.range.end.character, 5); // 6 - 1
   });
 
+  await t.test('maps error offsets through template segments', () => {
+    // `SELECT ${someColumn} FRM t` on line 3, with the backtick at column 19
+    const location: SqlLocation = {
+      id: 'test.ts:3:19',
+      file: '/project/test.ts',
+      line: 3,
+      column: 19,
+      endLine: 4,
+      endColumn: 5,
+      templateText: 'SELECT ${...} FRM\n  t',
+      tagKind: 'statement',
+      tagLine: 3,
+      tagColumn: 5,
+      tagEndColumn: 18,
+      segments: [
+        { offset: 0, line: 3, column: 20 },
+        { offset: 7, line: 3, column: 27 },
+        { offset: 13, line: 3, column: 40 },
+      ],
+    };
+
+    const frm = { message: 'Expected FROM', startOffset: 14, endOffset: 17 };
+    const { diagnostic } = createLocationDiagnostic(location, frm);
+    assert.deepStrictEqual(diagnostic.range, {
+      start: { line: 2, character: 40 },
+      end: { line: 2, character: 43 },
+    });
+
+    // Offsets past a newline move to the following lines
+    const table = { message: 'Unknown table', startOffset: 20, endOffset: 21 };
+    const { diagnostic: next } = createLocationDiagnostic(location, table);
+    assert.deepStrictEqual(next.range, {
+      start: { line: 3, character: 2 },
+      end: { line: 3, character: 3 },
+    });
+
+    // A placeholder is covered whole, up to where the next literal starts
+    const hole = { message: 'Bad value', startOffset: 7, endOffset: 13 };
+    const { diagnostic: whole } = createLocationDiagnostic(location, hole);
+    assert.deepStrictEqual(whole.range, {
+      start: { line: 2, character: 26 },
+      end: { line: 2, character: 39 },
+    });
+  });
+
   await t.test('includes error message in diagnostic', () => {
     const location: SqlLocation = {
       id: 'test.ts:1:1',
@@ -1,34 +1,77 @@
-import type { ValidationResult } from '@514labs/moose-sql-validator-wasm';
+import type { ValidationError } from '@514labs/moose-sql-validator-wasm';
 import {
   type Connection,
   type Diagnostic,
   DiagnosticSeverity,
   DiagnosticTag,
+  type Position,
   type Range,
 } from 'vscode-languageserver/node';
-import type { SqlLocation } from './sqlLocations';
+import type { SqlLocation, TemplateSegment } from './sqlLocations';
+
+/**
+ * The document position of an offset into a location's template text, or
+ * undefined when the location has no segments to map it through.
+ */
+export function templatePosition(
+  location: SqlLocation,
+  offset: number,
+): Position | undefined {
+  let segment: TemplateSegment | undefined;
+  for (const candidate of location.segments ?? []) {
+    if (candidate.offset > offset) break;
+    segment = candidate;
+  }
+  if (!segment) return undefined;
+
+  // Convert 1-indexed source positions to 0-indexed LSP positions
+  let line = segment.line - 1;
+  let character = segment.column - 1;
+  for (let i = segment.offset; i < offset; i++) {
+    if (location.templateText[i] === '\n') {
+      line++;
+      character = 0;
+    } else {
+      character++;
+    }
+  }
+  return { line, character };
+}
 
 /**
  * Creates an LSP diagnostic from a SQL validation error for a SqlLocation.
- * This is used for inline sql template literals.
+ * This is used for inline sql template literals. The error's offsets into
+ * the template text are mapped to the document; without them, or without
+ * segments to map them through, the diagnostic covers the whole template.
  */
 export function createLocationDiagnostic(
   location: SqlLocation,
-  validationError: NonNullable<ValidationResult['error']>,
+  validationError: ValidationError,
 ): { uri: string; diagnostic: Diagnostic } {
   const uri = `file://${location.file}`;
 
+  const { startOffset, endOffset } = validationError;
+  const start =
+    startOffset === undefined
+      ? undefined
+      : templatePosition(location, startOffset);
+  const end =
+    endOffset === undefined ? undefined : templatePosition(location, endOffset);
+
   // Convert 1-indexed source positions to 0-indexed LSP positions
-  const range: Range = {
-    start: {
-      line: location.line - 1,
-      character: location.column - 1,
-    },
-    end: {
-      line: location.endLine - 1,
-      character: location.endColumn - 1,
-    },
-  };
+  const range: Range =
+    start && end
+      ? { start, end }
+      : {
+          start: {
+            line: location.line - 1,
+            character: location.column - 1,
+          },
+          end: {
+            line: location.endLine - 1,
+            character: location.endColumn - 1,
+          },
+        };
 
   const diagnostic: Diagnostic = {
     range,
@@ -36,6 +36,12 @@ interface LspCompletionItem {
   insertText?: string;
   insertTextFormat?: number;
   sortText?: string;
+  data?: unknown;
+}
+
+interface LspCompletionList {
+  isIncomplete: boolean;
+  items: LspCompletionItem[];
 }
 
 // LSP CompletionItemKind constants
@@ -396,14 +402,21 @@ const stmtValid = sql.statement\`SELECT count() FROM users\`;
 // sql.statement — invalid SQL, should produce error
 const stmtInvalid = sql.statement\`SELCT * FROM users\`;
 
-// sql.fragment — invalid as standalone SQL, should NOT produce error
+// sql.fragment — invalid as standalone SQL, valid as a condition
 const frag = sql.fragment\`status = 'active' AND amount > 0\`;
 
+// sql.fragment — invalid in every fragment mode, should produce error
+const fragInvalid = sql.fragment\`status = AND amount > 0\`;
+
 // sql.fragment — hover target
 const fragHover = sql.fragment\`toUInt32(id) > 0\`;
 
 // sql.fragment — completions target
 const fragComp = sql.fragment\`cou\`;
+
+// Default context with partly typed multi-word keywords
+const groupBy = sql\`GRO\`;
+const orderBy = sql\`ORD\`;
 `;
 
 async function createTsFixture(): Promise<string> {
@@ -647,8 +660,8 @@ describe('TypeScript LSP features', () => {
       textDocument: { uri: tsFileUri() },
       position: pos,
     });
-    const items = response.result as LspCompletionItem[];
-    assert.ok(Array.isArray(items), 'Result should be an array');
+    const items = (response.result as LspCompletionList).items;
+    assert.ok(Array.isArray(items), 'Result should have an items array');
     assert.ok(items.length > 0, 'Should have completion items');
   });
 
@@ -658,7 +671,7 @@ describe('TypeScript LSP features', () => {
       textDocument: { uri: tsFileUri() },
       position: pos,
     });
-    const items = response.result as LspCompletionItem[];
+    const items = (response.result as LspCompletionList).items;
     const kinds = new Set(items.map((i) => i.kind));
     assert.ok(
       kinds.has(CompletionItemKind.Function) ||
@@ -681,8 +694,8 @@ describe('TypeScript LSP features', () => {
       textDocument: { uri: tsFileUri() },
       position: { line: 0, character: 5 },
     });
-    const items = response.result as LspCompletionItem[];
-    assert.ok(Array.isArray(items), 'Result should be an array');
+    const items = (response.result as LspCompletionList).items;
+    assert.ok(Array.isArray(items), 'Result should have an items array');
     assert.strictEqual(
       items.length,
       0,
@@ -690,24 +703,25 @@ describe('TypeScript LSP features', () => {
     );
   });
 
-  test('prefix filtering narrows completions', async () => {
+  test('completions are matched against the typed prefix', async () => {
     const pos = cursorAfter(TS_TEST_FILE_CONTENT, 'prefix = sql`SELECT cou');
     const response = await client.request('textDocument/completion', {
       textDocument: { uri: tsFileUri() },
       position: pos,
     });
-    const items = response.result as LspCompletionItem[];
+    const items = (response.result as LspCompletionList).items;
     assert.ok(items.length > 0, 'Should have some completions');
     const labels = items.map((i) => i.label.toLowerCase());
     assert.ok(
       labels.some((l) => l.startsWith('count')),
       `Should include count*, got: ${labels.slice(0, 10).join(', ')}`,
     );
-    // Every item should start with 'cou'
-    for (const item of items) {
+    // Labels starting with 'cou' rank above fuzzier matches
+    const firstOther = labels.findIndex((l) => !l.startsWith('cou'));
+    if (firstOther !== -1) {
       assert.ok(
-        item.label.toLowerCase().startsWith('cou'),
-        `Item "${item.label}" should start with "cou"`,
+        labels.slice(firstOther).every((l) => !l.startsWith('cou')),
+        `Prefix matches should come first, got: ${labels.join(', ')}`,
       );
     }
   });
@@ -723,7 +737,7 @@ describe('TypeScript LSP features', () => {
       textDocument: { uri: tsFileUri() },
       position: pos,
     });
-    const items = response.result as LspCompletionItem[];
+    const items = (response.result as LspCompletionList).items;
     assert.ok(items.length > 0, 'Should have engine completions');
     const labels = items.map((i) => i.label);
     assert.ok(
@@ -749,7 +763,7 @@ describe('TypeScript LSP features', () => {
       textDocument: { uri: tsFileUri() },
       position: pos,
     });
-    const items = response.result as LspCompletionItem[];
+    const items = (response.result as LspCompletionList).items;
     assert.ok(items.length > 0, 'Should have format completions');
     const labels = items.map((i) => i.label);
     assert.ok(
@@ -774,7 +788,7 @@ describe('TypeScript LSP features', () => {
       textDocument: { uri: tsFileUri() },
       position: pos,
     });
-    const items = response.result as LspCompletionItem[];
+    const items = (response.result as LspCompletionList).items;
     assert.ok(items.length > 0, 'Should have settings completions');
     const labels = items.map((i) => i.label);
     assert.ok(
@@ -799,7 +813,7 @@ describe('TypeScript LSP features', () => {
       textDocument: { uri: tsFileUri() },
       position: pos,
     });
-    const items = response.result as LspCompletionItem[];
+    const items = (response.result as LspCompletionList).items;
     assert.ok(items.length > 0, 'Should have data type completions');
     const labels = items.map((i) => i.label);
     assert.ok(
@@ -821,7 +835,7 @@ describe('TypeScript LSP features', () => {
       textDocument: { uri: tsFileUri() },
       position: pos,
     });
-    const items = response.result as LspCompletionItem[];
+    const items = (response.result as LspCompletionList).items;
     assert.ok(items.length > 0, 'Should have FROM completions');
     const labels = items.map((i) => i.label);
     assert.ok(
@@ -1002,7 +1016,7 @@ describe('TypeScript LSP features', () => {
       textDocument: { uri: tsFileUri() },
       position: pos,
     });
-    const items = response.result as LspCompletionItem[];
+    const items = (response.result as LspCompletionList).items;
     const labels = items.map((i) => i.label);
     assert.ok(
       labels.some((l) => l === 'sumIf'),
@@ -1010,7 +1024,7 @@ describe('TypeScript LSP features', () => {
     );
   });
 
-  test('combinator function has documentation', async () => {
+  test('combinator function has documentation once resolved', async () => {
     const pos = cursorAfter(
       TS_TEST_FILE_CONTENT,
       'combPrefix = sql`SELECT sum',
@@ -1019,9 +1033,13 @@ describe('TypeScript LSP features', () => {
       textDocument: { uri: tsFileUri() },
       position: pos,
     });
-    const items = response.result as LspCompletionItem[];
-    const sumIf = items.find((i) => i.label === 'sumIf');
-    assert.ok(sumIf, 'Should have sumIf item');
+    const items = (response.result as LspCompletionList).items;
+    const listed = items.find((i) => i.label === 'sumIf');
+    assert.ok(listed, 'Should have sumIf item');
+    assert.ok(!listed.documentation, 'Documentation should wait for resolve');
+
+    const resolved = await client.request('completionItem/resolve', listed);
+    const sumIf = resolved.result as LspCompletionItem;
     assert.ok(sumIf.documentation, 'sumIf should have documentation');
     const docValue =
       typeof sumIf.documentation === 'string'
@@ -1058,14 +1076,31 @@ describe('TypeScript LSP features', () => {
 
   // ---- Feature 9: Multi-word Keywords ----
 
-  test('GROUP BY appears in default context completions', async () => {
-    // Use the empty SQL template which triggers Default context (all completions)
+  test('default context returns a limited, incomplete list', async () => {
     const pos = cursorAfter(TS_TEST_FILE_CONTENT, 'defaultCtx = sql`');
     const response = await client.request('textDocument/completion', {
       textDocument: { uri: tsFileUri() },
       position: pos,
     });
-    const items = response.result as LspCompletionItem[];
+    const list = response.result as LspCompletionList;
+    assert.ok(list.items.length > 0, 'Should have completion items');
+    assert.ok(list.items.length <= 100, 'Should return at most 100 items');
+    assert.strictEqual(
+      list.isIncomplete,
+      true,
+      'Should ask to be requested again as typing continues',
+    );
+  });
+
+  test('GROUP BY appears in default context completions', async () => {
+    // The empty template gets more completions than are returned, so the
+    // keyword is partly typed
+    const pos = cursorAfter(TS_TEST_FILE_CONTENT, 'groupBy = sql`GRO');
+    const response = await client.request('textDocument/completion', {
+      textDocument: { uri: tsFileUri() },
+      position: pos,
+    });
+    const items = (response.result as LspCompletionList).items;
     const labels = items.map((i) => i.label);
     assert.ok(
       labels.some((l) => l === 'GROUP BY'),
@@ -1074,12 +1109,12 @@ describe('TypeScript LSP features', () => {
   });
 
   test('ORDER BY appears in default context completions', async () => {
-    const pos = cursorAfter(TS_TEST_FILE_CONTENT, 'defaultCtx = sql`');
+    const pos = cursorAfter(TS_TEST_FILE_CONTENT, 'orderBy = sql`ORD');
     const response = await client.request('textDocument/completion', {
       textDocument: { uri: tsFileUri() },
       position: pos,
     });
-    const items = response.result as LspCompletionItem[];
+    const items = (response.result as LspCompletionList).items;
     const labels = items.map((i) => i.label);
     assert.ok(
       labels.some((l) => l === 'ORDER BY'),
@@ -1117,6 +1152,13 @@ describe('TypeScript LSP features', () => {
       `Expected error diagnostic on invalid sql.statement line (${stmtInvalidLine})`,
     );
     assert.strictEqual(errors[0].source, 'moose-sql');
+    // The diagnostic covers the misspelled keyword, not the whole template
+    const lineText = TS_TEST_FILE_CONTENT.split('\n')[stmtInvalidLine];
+    const selct = lineText.indexOf('SELCT');
+    assert.deepStrictEqual(errors[0].range, {
+      start: { line: stmtInvalidLine, character: selct },
+      end: { line: stmtInvalidLine, character: selct + 'SELCT'.length },
+    });
   });
 
   test('sql.fragment does NOT produce validation errors', async () => {
@@ -1134,6 +1176,20 @@ describe('TypeScript LSP features', () => {
     );
   });
 
+  test('sql.fragment with invalid SQL produces error diagnostic', async () => {
+    const diagnostics = await client.waitForDiagnostics(tsFileUri());
+    const fragInvalidLine = TS_TEST_FILE_CONTENT.split('\n').findIndex((l) =>
+      l.includes('const fragInvalid'),
+    );
+    const errors = diagnostics.filter(
+      (d) => d.range.start.line === fragInvalidLine && d.severity === 1,
+    );
+    assert.ok(
+      errors.length > 0,
+      `Expected error diagnostic on invalid sql.fragment line (${fragInvalidLine})`,
+    );
+  });
+
   test('hover works inside sql.fragment', async () => {
     const fragHoverLine = TS_TEST_FILE_CONTENT.split('\n').findIndex((l) =>
       l.includes('const fragHover'),
@@ -1168,8 +1224,8 @@ describe('TypeScript LSP features', () => {
       textDocument: { uri: tsFileUri() },
       position: pos,
     });
-    const items = response.result as LspCompletionItem[];
-    assert.ok(Array.isArray(items), 'Result should be an array');
+    const items = (response.result as LspCompletionList).items;
+    assert.ok(Array.isArray(items), 'Result should have an items array');
     assert.ok(items.length > 0, 'Should have completion items inside fragment');
     const labels = items.map((i) => i.label.toLowerCase());
     assert.ok(
@@ -1303,7 +1359,7 @@ describe('Snippet support toggle', () => {
         textDocument: { uri },
         position: pos,
       });
-      const items = response.result as LspCompletionItem[];
+      const items = (response.result as LspCompletionList).items;
       const countItem = items.find((i) => i.label === 'count');
       assert.ok(
         countItem,
@@ -1345,7 +1401,7 @@ describe('Snippet support toggle', () => {
         textDocument: { uri },
         position: pos,
       });
-      const items = response.result as LspCompletionItem[];
+      const items = (response.result as LspCompletionList).items;
       const countItem = items.find((i) => i.label === 'count');
       assert.ok(
         countItem,
@@ -1,10 +1,11 @@
 import * as path from 'node:path';
 import {
-  getCompletions,
-  initCompletionData,
+  ClickHouseEngine,
+  type Hole,
   initValidator,
+  type ParseMode,
   type CompletionItem as RustCompletionItem,
-  validateSql,
+  type ValidationResult,
 } from '@514labs/moose-sql-validator-wasm';
 import {
   type CodeAction,
@@ -12,6 +13,7 @@ import {
   type CodeActionParams,
   type CompletionItem,
   CompletionItemKind,
+  type CompletionList,
   type CompletionParams,
   createConnection,
   type Hover,
@@ -19,7 +21,10 @@ import {
   type InitializeParams,
   type InitializeResult,
   InsertTextFormat,
+  MarkupKind,
+  type Position,
   ProposedFeatures,
+  type Range,
   TextDocumentSyncKind,
   TextDocuments,
 } from 'vscode-languageserver/node';
@@ -63,6 +68,25 @@ let pyService: PythonService | null = null;
 let clickhouseData: ClickHouseData | null = null;
 let clientSupportsSnippets = false;
 
+// Completion and validation data, keyed by ClickHouse version
+const engine = new ClickHouseEngine();
+
+/** The version the engine is asked about; before any data is loaded it has
+ * none, and validation is syntax-only */
+function loadedVersion(): string {
+  return clickhouseData?.version ?? '';
+}
+
+/** Validates template SQL, with its placeholders as holes, against the
+ * loaded ClickHouse version */
+function validateTemplate(
+  sql: string,
+  holes: Hole[],
+  mode: ParseMode,
+): ValidationResult {
+  return engine.validateTemplate(loadedVersion(), sql, holes, mode);
+}
+
 // Debounce timers for as-you-type validation
 const validationTimers = new Map<string, ReturnType<typeof setTimeout>>();
 const DEBOUNCE_MS = 300;
@@ -99,7 +123,7 @@ function validateTypeScriptDocument(
     // Validate and collect diagnostics
     const diagnosticsMap = validateSqlLocations(
       sqlLocations,
-      validateSql,
+      validateTemplate,
       createLocationDiagnostic,
     );
 
@@ -148,7 +172,7 @@ function validatePythonDocument(
     // Validate and collect diagnostics
     const diagnosticsMap = validateSqlLocations(
       sqlLocations,
-      validateSql,
+      validateTemplate,
       createLocationDiagnostic,
     );
 
@@ -211,7 +235,7 @@ function performTypeScriptInitialScan(): void {
     // Validate all locations
     const diagnosticsMap = validateSqlLocations(
       allLocations,
-      validateSql,
+      validateTemplate,
       createLocationDiagnostic,
     );
 
@@ -264,7 +288,7 @@ function performPythonInitialScan(): void {
     // Validate all locations
     const diagnosticsMap = validateSqlLocations(
       allLocations,
-      validateSql,
+      validateTemplate,
       createLocationDiagnostic,
     );
 
@@ -313,7 +337,7 @@ function performInitialScan(): void {
 }
 
 /**
- * Loads ClickHouse completion data and initializes Rust completion engine.
+ * Loads ClickHouse completion data into the Rust engine under its version.
  * Falls back to latest available version if detection fails.
  */
 async function loadClickHouseCompletionData(
@@ -339,22 +363,23 @@ async function loadClickHouseCompletionData(
       }
     }
 
-    clickhouseData = await loadClickHouseData(version);
+    const data = await loadClickHouseData(version);
 
-    if (clickhouseData.warning) {
-      connection.console.warn(clickhouseData.warning);
+    if (data.warning) {
+      connection.console.warn(data.warning);
     }
 
-    // Initialize Rust completion engine with the data
-    const jsonData = JSON.stringify(clickhouseData);
-    const initResult = initCompletionData(jsonData);
+    // Load the data into the Rust engine, replacing what was loaded for the
+    // same version before
+    const loadResult = engine.load(data.version, JSON.stringify(data));
 
-    if (!initResult.success) {
+    if (!loadResult.success) {
       connection.console.error(
-        `Failed to init completion data: ${initResult.error}`,
+        `Failed to load completion data: ${loadResult.error}`,
       );
       return;
     }
+    clickhouseData = data;
 
     connection.console.log(
       `Loaded ClickHouse data: ${clickhouseData.functions.length} functions, ${clickhouseData.keywords.length} keywords`,
@@ -458,7 +483,7 @@ connection.onInitialize(
         },
         completionProvider: {
           triggerCharacters: ['.', '(', ' '],
-          resolveProvider: false,
+          resolveProvider: true,
         },
         hoverProvider: true,
       },
@@ -610,6 +635,12 @@ function mapCompletionItemKind(
       return CompletionItemKind.Constant;
     case 'setting':
       return CompletionItemKind.Property;
+    case 'time_zone':
+      return CompletionItemKind.Value;
+    case 'table':
+      return CompletionItemKind.Struct;
+    case 'column':
+      return CompletionItemKind.Field;
     default:
       return CompletionItemKind.Text;
   }
@@ -643,12 +674,29 @@ function toRustCompletionItem(
     label: rustItem.label,
     kind,
     detail: rustItem.detail,
-    documentation: rustItem.documentation
-      ? { kind: 'markdown' as const, value: rustItem.documentation.value }
-      : undefined,
     insertText,
     insertTextFormat,
     sortText: rustItem.sortText,
+    // Documentation is looked up when the item is resolved
+    data: { kind: rustItem.kind },
+  };
+}
+
+/**
+ * Converts an offset in a SQL template's text to a document position.
+ */
+function templateOffsetToPosition(
+  location: import('./sqlLocations').SqlLocation,
+  offset: number,
+): Position {
+  const lines = location.templateText.slice(0, offset).split('\n');
+  const lastLine = lines[lines.length - 1];
+  return {
+    line: location.line - 1 + lines.length - 1,
+    character:
+      lines.length === 1
+        ? location.column - 1 + lastLine.length
+        : lastLine.length,
   };
 }
 
@@ -671,21 +719,23 @@ function getSqlLocationsForFile(
   return [];
 }
 
+const NO_COMPLETIONS: CompletionList = { isIncomplete: false, items: [] };
+
 // Completion handler - provides context-aware SQL completions inside sql template literals
-connection.onCompletion((params: CompletionParams): CompletionItem[] => {
+connection.onCompletion((params: CompletionParams): CompletionList => {
   if (!mooseProjectRoot || !clickhouseData) {
-    return [];
+    return NO_COMPLETIONS;
   }
 
   const document = documents.get(params.textDocument.uri);
-  if (!document) return [];
+  if (!document) return NO_COMPLETIONS;
 
   const filePath = new URL(params.textDocument.uri).pathname;
-  if (!shouldValidateFile(filePath, mooseProjectRoot)) return [];
+  if (!shouldValidateFile(filePath, mooseProjectRoot)) return NO_COMPLETIONS;
 
   try {
     const sqlLocations = getSqlLocationsForFile(filePath);
-    if (sqlLocations.length === 0) return [];
+    if (sqlLocations.length === 0) return NO_COMPLETIONS;
 
     // Check if cursor is inside any SQL template
     const location = findSqlTemplateAtPosition(
@@ -694,7 +744,7 @@ connection.onCompletion((params: CompletionParams): CompletionItem[] => {
       params.position.character,
     );
 
-    if (!location) return [];
+    if (!location) return NO_COMPLETIONS;
 
     // Calculate cursor offset within the SQL template
     const cursorLine = params.position.line;
@@ -723,31 +773,65 @@ connection.onCompletion((params: CompletionParams): CompletionItem[] => {
     // Clamp to valid range
     cursorOffset = Math.max(0, Math.min(cursorOffset, sqlText.length));
 
-    // Get context-aware completions from Rust
-    const rustCompletions = getCompletions(sqlText, cursorOffset);
+    // Get context-aware completions from Rust, already matched against the
+    // word before the cursor and ranked best first
+    const rustCompletions = engine.getCompletions(
+      loadedVersion(),
+      sqlText,
+      cursorOffset,
+    );
 
-    // Get prefix for filtering
-    const lineText = document.getText({
-      start: { line: params.position.line, character: 0 },
-      end: params.position,
-    });
-    const wordMatch = lineText.match(/[\w]+$/);
-    const prefix = wordMatch ? wordMatch[0].toLowerCase() : '';
-
-    // Convert to LSP CompletionItem format and filter by prefix
-    const completions: CompletionItem[] = rustCompletions
-      .filter(
-        (c: RustCompletionItem) =>
-          !prefix || c.label.toLowerCase().startsWith(prefix),
-      )
-      .map((c: RustCompletionItem) =>
-        toRustCompletionItem(c, clientSupportsSnippets),
-      );
+    // Replace the whole word at the cursor rather than what the client takes
+    // to be a word, which goes wrong around quotes and dots
+    const { replaceRange } = rustCompletions;
+    const range: Range = {
+      start: templateOffsetToPosition(location, replaceRange.start),
+      end: templateOffsetToPosition(location, replaceRange.end),
+    };
+    // Clients filter on the replaced text, so a quoted identifier keeps its
+    // opening quote in the filter text
+    const quote = /^[`"]/.exec(sqlText.slice(replaceRange.start))?.[0] ?? '';
+
+    // Keep Rust's ranking; clients sort by sortText
+    const items: CompletionItem[] = rustCompletions.items.map(
+      (c: RustCompletionItem, rank: number) => {
+        const item = toRustCompletionItem(c, clientSupportsSnippets);
+        return {
+          ...item,
+          filterText: quote ? `${quote}${item.label}` : undefined,
+          textEdit: { range, newText: item.insertText ?? item.label },
+          sortText: rank.toString().padStart(4, '0'),
+        };
+      },
+    );
+
+    return { isIncomplete: rustCompletions.isIncomplete, items };
+  } catch {
+    return NO_COMPLETIONS;
+  }
+});
+
+// Completion resolve handler - adds documentation to the selected completion
+connection.onCompletionResolve((item: CompletionItem): CompletionItem => {
+  const data = item.data as { kind?: RustCompletionItem['kind'] } | undefined;
+  if (!data?.kind) return item;
 
-    return completions;
+  try {
+    const documentation = engine.resolveCompletion(
+      loadedVersion(),
+      item.label,
+      data.kind,
+    );
+    if (documentation) {
+      item.documentation = {
+        kind: MarkupKind.Markdown,
+        value: documentation.value,
+      };
+    }
   } catch {
-    return [];
+    // Leave the item undocumented
   }
+  return item;
 });
 
 // Hover handler - provides documentation on hover inside sql template literals
@@ -784,6 +868,18 @@ connection.onHover((params: HoverParams): Hover | null => {
     const word = getWordAtPosition(lineText, params.position.character);
     if (!word) return null;
 
+    // Aggregate combinator forms (countIf, avgMergeOrNull) are explained
+    // in terms of their base function by the validator
+    const combinatorDoc = engine.getCombinatorDocumentation(
+      loadedVersion(),
+      word,
+    );
+    if (combinatorDoc) {
+      return {
+        contents: { kind: MarkupKind.Markdown, value: combinatorDoc.value },
+      };
+    }
+
     // Look up hover info
     const hoverInfo = findHoverInfo(word, clickhouseData);
     if (!hoverInfo) return null;
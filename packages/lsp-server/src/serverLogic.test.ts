@@ -1,10 +1,14 @@
 import assert from 'node:assert';
 import { test } from 'node:test';
+import type {
+  Hole,
+  ValidationError,
+} from '@514labs/moose-sql-validator-wasm';
 import type { Diagnostic, Range } from 'vscode-languageserver/node';
 import {
   type CreateLocationDiagnosticFn,
   shouldValidateFile,
-  type ValidateSqlFn,
+  type ValidateTemplateFn,
   validateSqlLocations,
 } from './serverLogic';
 import type { SqlLocation } from './sqlLocations';
@@ -23,6 +27,11 @@ function createMockDiagnostic(message: string): Diagnostic {
   };
 }
 
+// Helper to create a validation error without a position
+function syntaxError(message: string): ValidationError {
+  return { code: 'syntax/unexpected-token', category: 'syntax', message };
+}
+
 test('shouldValidateFile Tests', async (t) => {
   await t.test('returns false when mooseProjectRoot is null', () => {
     assert.strictEqual(shouldValidateFile('/some/path/file.ts', null), false);
@@ -81,7 +90,10 @@ test('shouldValidateFile Tests', async (t) => {
 
 test('validateSqlLocations Tests', async (t) => {
   await t.test('returns empty map for empty locations', () => {
-    const mockValidateSql: ValidateSqlFn = () => ({ valid: true });
+    const mockValidateSql: ValidateTemplateFn = () => ({
+      valid: true,
+      errors: [],
+    });
     const mockCreateDiagnostic: CreateLocationDiagnosticFn = () => ({
       uri: '',
       diagnostic: createMockDiagnostic(''),
@@ -113,9 +125,9 @@ test('validateSqlLocations Tests', async (t) => {
       },
     ];
 
-    const mockValidateSql: ValidateSqlFn = () => ({
+    const mockValidateSql: ValidateTemplateFn = () => ({
       valid: false,
-      error: { message: 'Expected SELECT, found SLECT' },
+      errors: [syntaxError('Expected SELECT, found SLECT')],
     });
 
     const mockCreateDiagnostic: CreateLocationDiagnosticFn = (
@@ -159,7 +171,10 @@ test('validateSqlLocations Tests', async (t) => {
       },
     ];
 
-    const mockValidateSql: ValidateSqlFn = () => ({ valid: true });
+    const mockValidateSql: ValidateTemplateFn = () => ({
+      valid: true,
+      errors: [],
+    });
     const mockCreateDiagnostic: CreateLocationDiagnosticFn = () => ({
       uri: 'file:///project/app/apis/bar.ts',
       diagnostic: createMockDiagnostic('Should not be called'),
@@ -204,9 +219,9 @@ test('validateSqlLocations Tests', async (t) => {
       },
     ];
 
-    const mockValidateSql: ValidateSqlFn = () => ({
+    const mockValidateSql: ValidateTemplateFn = () => ({
       valid: false,
-      error: { message: 'Syntax error' },
+      errors: [syntaxError('Syntax error')],
     });
 
     const mockCreateDiagnostic: CreateLocationDiagnosticFn = (
@@ -260,9 +275,9 @@ test('validateSqlLocations Tests', async (t) => {
       },
     ];
 
-    const mockValidateSql: ValidateSqlFn = () => ({
+    const mockValidateSql: ValidateTemplateFn = () => ({
       valid: false,
-      error: { message: 'Syntax error' },
+      errors: [syntaxError('Syntax error')],
     });
 
     const mockCreateDiagnostic: CreateLocationDiagnosticFn = (
@@ -284,8 +299,8 @@ test('validateSqlLocations Tests', async (t) => {
     assert.ok(result.has('file:///project/app/apis/bar.ts'));
   });
 
-  await t.test('skips validation for fragment tagKind', () => {
-    const validatedSqls: string[] = [];
+  await t.test('validates fragments in the first parse mode that fits', () => {
+    const validated: Array<[string, string]> = [];
     const locations: SqlLocation[] = [
       {
         id: 'test.ts:1:1',
@@ -307,7 +322,7 @@ test('validateSqlLocations Tests', async (t) => {
         column: 1,
         endLine: 5,
         endColumn: 30,
-        templateText: "status = 'active'",
+        templateText: "status, 'active'",
         tagKind: 'fragment',
         tagLine: 5,
         tagColumn: 1,
@@ -328,9 +343,60 @@ test('validateSqlLocations Tests', async (t) => {
       },
     ];
 
-    const mockValidateSql: ValidateSqlFn = (sql) => {
-      validatedSqls.push(sql);
-      return { valid: true };
+    const mockValidateSql: ValidateTemplateFn = (sql, _holes, mode) => {
+      validated.push([sql, mode]);
+      // Pretend the fragment only parses as a select list
+      const parses = sql !== "status, 'active'" || mode === 'select_items';
+      return parses
+        ? { valid: true, errors: [] }
+        : { valid: false, errors: [syntaxError('Unexpected ,')] };
+    };
+
+    const mockCreateDiagnostic: CreateLocationDiagnosticFn = () => ({
+      uri: '',
+      diagnostic: createMockDiagnostic(''),
+    });
+
+    const result = validateSqlLocations(
+      locations,
+      mockValidateSql,
+      mockCreateDiagnostic,
+    );
+
+    // Statements are validated once; the fragment until a mode fits
+    assert.deepStrictEqual(validated, [
+      ['SELECT * FROM users', 'statement'],
+      ["status, 'active'", 'condition'],
+      ["status, 'active'", 'select_items'],
+      ['SELECT 1', 'statement'],
+    ]);
+    // The fragment parses as a select list, so none of its errors are reported
+    assert.strictEqual(result.get('')?.length ?? 0, 0);
+  });
+
+  await t.test('passes ${...} placeholders as holes', () => {
+    const locations: SqlLocation[] = [
+      {
+        id: 'test.ts:1:1',
+        file: '/project/test.ts',
+        line: 1,
+        column: 1,
+        endLine: 1,
+        endColumn: 50,
+        templateText: 'SELECT ${...} FROM ${...}',
+        tagKind: 'bare',
+        tagLine: 1,
+        tagColumn: 1,
+        tagEndColumn: 4,
+      },
+    ];
+
+    let validatedSql = '';
+    let validatedHoles: Hole[] = [];
+    const mockValidateSql: ValidateTemplateFn = (sql, holes) => {
+      validatedSql = sql;
+      validatedHoles = holes;
+      return { valid: true, errors: [] };
     };
 
     const mockCreateDiagnostic: CreateLocationDiagnosticFn = () => ({
@@ -340,50 +406,59 @@ test('validateSqlLocations Tests', async (t) => {
 
     validateSqlLocations(locations, mockValidateSql, mockCreateDiagnostic);
 
-    // Fragment should be skipped, statement and bare should be validated
-    assert.strictEqual(validatedSqls.length, 2);
-    assert.ok(validatedSqls[0].includes('SELECT'));
-    assert.ok(validatedSqls[1].includes('SELECT'));
+    // The template text is validated as written, with its placeholder spans
+    assert.strictEqual(validatedSql, 'SELECT ${...} FROM ${...}');
+    assert.deepStrictEqual(validatedHoles, [
+      { start: 7, end: 13 },
+      { start: 19, end: 25 },
+    ]);
   });
 
-  await t.test(
-    'prepares SQL by replacing ${...} placeholders before validation',
-    () => {
-      const locations: SqlLocation[] = [
-        {
-          id: 'test.ts:1:1',
-          file: '/project/test.ts',
-          line: 1,
-          column: 1,
-          endLine: 1,
-          endColumn: 50,
-          templateText: 'SELECT ${...} FROM ${...}',
-          tagKind: 'bare',
-          tagLine: 1,
-          tagColumn: 1,
-          tagEndColumn: 4,
-        },
-      ];
-
-      let validatedSql = '';
-      const mockValidateSql: ValidateSqlFn = (sql) => {
-        validatedSql = sql;
-        return { valid: true };
-      };
-
-      const mockCreateDiagnostic: CreateLocationDiagnosticFn = () => ({
-        uri: '',
-        diagnostic: createMockDiagnostic(''),
-      });
-
-      validateSqlLocations(locations, mockValidateSql, mockCreateDiagnostic);
-
-      // Should have replaced ${...} with placeholders
-      assert.ok(!validatedSql.includes('${...}'));
-      assert.ok(validatedSql.includes('SELECT'));
-      assert.ok(validatedSql.includes('FROM'));
-    },
-  );
+  await t.test('creates one diagnostic per error', () => {
+    const locations: SqlLocation[] = [
+      {
+        id: 'test.ts:1:1',
+        file: '/project/test.ts',
+        line: 1,
+        column: 1,
+        endLine: 1,
+        endColumn: 50,
+        templateText: 'SLECT a FRM t',
+        tagKind: 'statement',
+        tagLine: 1,
+        tagColumn: 1,
+        tagEndColumn: 14,
+      },
+    ];
+
+    const mockValidateSql: ValidateTemplateFn = () => ({
+      valid: false,
+      errors: [
+        syntaxError('Expected SELECT, found SLECT'),
+        syntaxError('Expected FROM, found FRM'),
+      ],
+    });
+
+    const mockCreateDiagnostic: CreateLocationDiagnosticFn = (
+      location,
+      error,
+    ) => ({
+      uri: `file://${location.file}`,
+      diagnostic: createMockDiagnostic(error.message),
+    });
+
+    const result = validateSqlLocations(
+      locations,
+      mockValidateSql,
+      mockCreateDiagnostic,
+    );
+
+    const diagnostics = result.get('file:///project/test.ts');
+    assert.deepStrictEqual(
+      diagnostics?.map((d) => d.message),
+      ['Expected SELECT, found SLECT', 'Expected FROM, found FRM'],
+    );
+  });
 
   await t.test('emits deprecation diagnostic for bare tagKind', () => {
     const locations: SqlLocation[] = [
@@ -402,7 +477,10 @@ test('validateSqlLocations Tests', async (t) => {
       },
     ];
 
-    const mockValidateSql: ValidateSqlFn = () => ({ valid: true });
+    const mockValidateSql: ValidateTemplateFn = () => ({
+      valid: true,
+      errors: [],
+    });
     const mockCreateDiagnostic: CreateLocationDiagnosticFn = () => ({
       uri: '',
       diagnostic: createMockDiagnostic(''),
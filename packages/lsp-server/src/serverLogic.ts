@@ -1,21 +1,40 @@
-import type { ValidationResult } from '@514labs/moose-sql-validator-wasm';
+import type {
+  Hole,
+  ParseMode,
+  ValidationError,
+  ValidationResult,
+} from '@514labs/moose-sql-validator-wasm';
 import type { Diagnostic } from 'vscode-languageserver/node';
 import { createDeprecationDiagnostic } from './diagnostics';
-import { prepareSqlForValidation, type SqlLocation } from './sqlLocations';
+import { type SqlLocation, templateHoles } from './sqlLocations';
 
 /**
- * Function type for SQL validation
+ * Function type for validating template SQL whose placeholders are holes
  */
-export type ValidateSqlFn = (sql: string) => ValidationResult;
+export type ValidateTemplateFn = (
+  sql: string,
+  holes: Hole[],
+  mode: ParseMode,
+) => ValidationResult;
 
 /**
  * Function type for creating diagnostics from SqlLocation
  */
 export type CreateLocationDiagnosticFn = (
   location: SqlLocation,
-  error: NonNullable<ValidationResult['error']>,
+  error: ValidationError,
 ) => { uri: string; diagnostic: Diagnostic };
 
+/**
+ * Parse modes a `sql.fragment` is tried in after `condition`. The tag does
+ * not say what the fragment is, so the first mode it parses in is taken.
+ */
+const OTHER_FRAGMENT_MODES: ParseMode[] = [
+  'select_items',
+  'order_by',
+  'column_definitions',
+];
+
 /**
  * Determines if a file should be validated based on path and project root
  * @param filePath - The absolute path to the file
@@ -45,16 +64,42 @@ export function isPythonFile(filePath: string): boolean {
   return filePath.endsWith('.py');
 }
 
+/**
+ * Validates a location's template text with its placeholders as holes.
+ * Fragments are validated in the first parse mode they parse in, and report
+ * the errors of the `condition` mode when none fits.
+ */
+function validateLocation(
+  location: SqlLocation,
+  validateTemplate: ValidateTemplateFn,
+): ValidationResult {
+  const sql = location.templateText;
+  const holes = templateHoles(sql);
+  if (location.tagKind !== 'fragment') {
+    return validateTemplate(sql, holes, 'statement');
+  }
+
+  const parses = (result: ValidationResult) =>
+    !result.errors.some((error) => error.category === 'syntax');
+  const condition = validateTemplate(sql, holes, 'condition');
+  if (parses(condition)) return condition;
+  for (const mode of OTHER_FRAGMENT_MODES) {
+    const result = validateTemplate(sql, holes, mode);
+    if (parses(result)) return result;
+  }
+  return condition;
+}
+
 /**
  * Validates SQL from template locations and returns a map of URI -> diagnostics
  * @param sqlLocations - Array of SQL template locations
- * @param validateSql - Function to validate SQL strings
+ * @param validateTemplate - Function to validate template SQL
  * @param createDiagnostic - Function to create LSP diagnostics from validation errors
  * @returns Map of file URIs to their diagnostics
  */
 export function validateSqlLocations(
   sqlLocations: SqlLocation[],
-  validateSql: ValidateSqlFn,
+  validateTemplate: ValidateTemplateFn,
   createDiagnostic: CreateLocationDiagnosticFn,
 ): Map<string, Diagnostic[]> {
   const diagnosticsMap = new Map<string, Diagnostic[]>();
@@ -69,15 +114,10 @@ export function validateSqlLocations(
       diagnosticsMap.get(uri)?.push(diagnostic);
     }
 
-    // Skip validation for fragments — they're intentionally partial SQL
-    if (location.tagKind === 'fragment') continue;
-
-    // Replace ${...} placeholders with valid SQL identifiers before validation
-    const preparedSql = prepareSqlForValidation(location.templateText);
-    const result = validateSql(preparedSql);
-
-    if (!result.valid && result.error) {
-      const { uri, diagnostic } = createDiagnostic(location, result.error);
+    // One diagnostic per error, placed through the template's segments
+    const result = validateLocation(location, validateTemplate);
+    for (const error of result.errors) {
+      const { uri, diagnostic } = createDiagnostic(location, error);
 
       if (!diagnosticsMap.has(uri)) {
         diagnosticsMap.set(uri, []);
@@ -183,6 +183,12 @@ const query = sql\`SELECT \${column} FROM \${tableName} WHERE active = \${true}\
           locations[0].templateText,
           'SELECT ${...} FROM ${...} WHERE active = ${...}',
         );
+        // Each literal part and placeholder is placed in the file
+        assert.deepStrictEqual(locations[0].segments?.slice(0, 3), [
+          { offset: 0, line: 6, column: 19 },
+          { offset: 7, line: 6, column: 26 },
+          { offset: 13, line: 6, column: 35 },
+        ]);
       } finally {
         cleanupTestProject(tmpDir);
       }
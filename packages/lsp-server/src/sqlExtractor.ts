@@ -1,5 +1,9 @@
 import ts from 'typescript';
-import type { SqlLocation, SqlTagKind } from './sqlLocations';
+import type {
+  SqlLocation,
+  SqlTagKind,
+  TemplateSegment,
+} from './sqlLocations';
 
 /**
  * Check if the tag is a moose-lib sql tag and determine its kind.
@@ -80,19 +84,40 @@ function getMooseSqlTagKind(
 /**
  * Extract template text with ${...} placeholders.
  * Converts template literals like `SELECT ${col} FROM ${table}`
- * into "SELECT ${...} FROM ${...}" for SQL validation.
+ * into "SELECT ${...} FROM ${...}" for SQL validation, along with where each
+ * literal part and placeholder starts in the source file.
  */
-function extractTemplateText(template: ts.TemplateLiteral): string {
+function extractTemplateText(
+  template: ts.TemplateLiteral,
+  sourceFile: ts.SourceFile,
+): Pick<SqlLocation, 'templateText' | 'segments'> {
+  const segment = (offset: number, position: number): TemplateSegment => {
+    const { line, character } =
+      sourceFile.getLineAndCharacterOfPosition(position);
+    return { offset, line: line + 1, column: character + 1 };
+  };
+
+  // Literal text starts just past the backtick or the `}` before it
   if (ts.isNoSubstitutionTemplateLiteral(template)) {
-    return template.text;
+    return {
+      templateText: template.text,
+      segments: [segment(0, template.getStart() + 1)],
+    };
   }
 
   // Template with substitutions: `head ${expr} middle ${expr} tail`
   let text = template.head.text;
+  const segments = [segment(0, template.head.getStart() + 1)];
+  let previous: ts.Node = template.head;
   for (const span of template.templateSpans) {
-    text += `\${...}${span.literal.text}`;
+    // The placeholder starts at the `${` that ends the previous literal
+    segments.push(segment(text.length, previous.getEnd() - 2));
+    text += '${...}';
+    segments.push(segment(text.length, span.literal.getStart() + 1));
+    text += span.literal.text;
+    previous = span.literal;
   }
-  return text;
+  return { templateText: text, segments };
 }
 
 /**
@@ -121,7 +146,7 @@ function extractSqlLocation(
     column: start.character + 1, // 1-based
     endLine: end.line + 1,
     endColumn: end.character + 1,
-    templateText: extractTemplateText(node.template),
+    ...extractTemplateText(node.template, sourceFile),
     tagKind,
     tagLine: tagStart.line + 1,
     tagColumn: tagStart.character + 1,
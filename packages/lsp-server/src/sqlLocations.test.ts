@@ -2,8 +2,8 @@ import assert from 'node:assert';
 import { test } from 'node:test';
 import {
   loadSqlLocations,
-  prepareSqlForValidation,
   type SqlLocationManifest,
+  templateHoles,
 } from './sqlLocations';
 
 test('loadSqlLocations Tests', async (t) => {
@@ -98,48 +98,33 @@ test('loadSqlLocations Tests', async (t) => {
   });
 });
 
-test('prepareSqlForValidation Tests', async (t) => {
-  await t.test('replaces ${...} placeholders with valid identifiers', () => {
-    const templateText = 'SELECT ${...} FROM ${...}';
-    const result = prepareSqlForValidation(templateText);
+test('templateHoles Tests', async (t) => {
+  await t.test('returns the span of each ${...} placeholder', () => {
+    const holes = templateHoles('SELECT ${...} FROM ${...}');
 
-    // Should replace ${...} with something the SQL parser can handle
-    assert.ok(!result.includes('${...}'));
-    // The result should be valid SQL structure
-    assert.ok(result.includes('SELECT'));
-    assert.ok(result.includes('FROM'));
+    assert.deepStrictEqual(holes, [
+      { start: 7, end: 13 },
+      { start: 19, end: 25 },
+    ]);
   });
 
-  await t.test('preserves SQL keywords and structure', () => {
-    const templateText = 'SELECT ${...} as col FROM ${...} WHERE x > 1';
-    const result = prepareSqlForValidation(templateText);
-
-    assert.ok(result.includes('SELECT'));
-    assert.ok(result.includes('as col'));
-    assert.ok(result.includes('FROM'));
-    assert.ok(result.includes('WHERE x > 1'));
-  });
-
-  await t.test('handles template with SLECT typo', () => {
-    const templateText = 'SLECT ${...} FROM ${...}';
-    const result = prepareSqlForValidation(templateText);
-
-    // The typo should be preserved
-    assert.ok(result.includes('SLECT'));
+  await t.test('returns no holes for plain SQL', () => {
+    assert.deepStrictEqual(templateHoles('SELECT * FROM users'), []);
   });
 
   await t.test('handles multi-line templates', () => {
     const templateText = `
-      SELECT 
+      SELECT
         \${...},
         \${...}
       FROM \${...}
       ORDER BY \${...} DESC
     `;
-    const result = prepareSqlForValidation(templateText);
+    const holes = templateHoles(templateText);
 
-    assert.ok(result.includes('SELECT'));
-    assert.ok(result.includes('FROM'));
-    assert.ok(result.includes('ORDER BY'));
+    assert.strictEqual(holes.length, 4);
+    for (const hole of holes) {
+      assert.strictEqual(templateText.slice(hole.start, hole.end), '${...}');
+    }
   });
 });
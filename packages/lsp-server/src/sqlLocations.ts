@@ -1,5 +1,20 @@
+import type { Hole } from '@514labs/moose-sql-validator-wasm';
+
 export type SqlTagKind = 'statement' | 'fragment' | 'bare';
 
+/**
+ * Where a piece of `templateText` starts in the document. A template has one
+ * segment per literal part and one per `${...}` placeholder.
+ */
+export interface TemplateSegment {
+  /** Offset of the piece in `templateText` */
+  offset: number;
+  /** 1-indexed line of the piece's first character */
+  line: number;
+  /** 1-indexed column of the piece's first character */
+  column: number;
+}
+
 /**
  * Represents a SQL template literal location from .moose/sql-locations.json
  */
@@ -18,6 +33,9 @@ export interface SqlLocation {
   tagColumn: number;
   /** 1-indexed column of the tag identifier end */
   tagEndColumn: number;
+  /** Document positions of the pieces of `templateText`, in order. Without
+   * them, diagnostics cover the whole template. */
+  segments?: TemplateSegment[];
 }
 
 /**
@@ -68,15 +86,18 @@ export function loadSqlLocations(jsonContent: string): SqlLocationManifest {
 }
 
 /**
- * Prepares template text for SQL validation by replacing ${...} placeholders
- * with valid SQL identifiers that won't cause parse errors.
+ * The spans of the `${...}` placeholders in template text, to validate it
+ * with each placeholder as a hole.
  */
-export function prepareSqlForValidation(templateText: string): string {
-  // Replace ${...} with a placeholder identifier that's valid in SQL
-  // Using '_ph_N' pattern to create unique, valid identifiers
-  let counter = 0;
-  return templateText.replace(/\$\{\.\.\.\}/g, () => {
-    counter++;
-    return `_ph_${counter}`;
-  });
+export function templateHoles(templateText: string): Hole[] {
+  const holes: Hole[] = [];
+  const placeholder = /\$\{\.\.\.\}/g;
+  for (
+    let match = placeholder.exec(templateText);
+    match;
+    match = placeholder.exec(templateText)
+  ) {
+    holes.push({ start: match.index, end: match.index + match[0].length });
+  }
+  return holes;
 }
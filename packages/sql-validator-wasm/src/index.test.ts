@@ -1,11 +1,14 @@
 import assert from 'node:assert';
 import { describe, test } from 'node:test';
 import {
+  buildSchemaFromDdl,
   formatSql,
-  getCompletions,
-  initCompletionData,
+  ClickHouseEngine,
+  getErrorCodes,
   initValidator,
   validateSql,
+  validateSqlFragment,
+  validateTemplate,
 } from './index.js';
 
 test('SQL Validator Tests', async (t) => {
@@ -26,6 +29,75 @@ test('SQL Validator Tests', async (t) => {
     assert.ok(result.error.message.includes('SELCT'));
   });
 
+  await t.test('reports every syntax error in one pass', () => {
+    const result = validateSql('SELECT a,, b FROM t WHERE x = = 1');
+    assert.strictEqual(result.valid, false);
+    assert.strictEqual(result.errors.length, 2);
+    assert.deepStrictEqual(result.error, result.errors[0]);
+  });
+
+  await t.test('tags errors with a stable code and category', () => {
+    const result = validateSql('SELECT count(x FROM t');
+    assert.strictEqual(result.errors[0].code, 'syntax/unclosed-paren');
+    assert.strictEqual(result.errors[0].category, 'syntax');
+    assert.ok(
+      getErrorCodes().some((c) => c.code === 'syntax/unclosed-paren'),
+    );
+  });
+
+  await t.test('validates fragments in the requested parse mode', () => {
+    const condition = "status = 'active' AND ts > now()";
+    assert.strictEqual(
+      validateSqlFragment(condition, 'condition').valid,
+      true,
+    );
+    assert.strictEqual(validateSql(condition).valid, false);
+    assert.strictEqual(
+      validateSqlFragment('ts DESC, id', 'order_by').valid,
+      true,
+    );
+
+    const result = validateSqlFragment('price *', 'expression');
+    assert.strictEqual(result.errors[0].code, 'syntax/unexpected-end');
+  });
+
+  await t.test('treats placeholders as holes', () => {
+    const sql = 'SELECT * FROM _ph_1 WHERE x IN _ph_2';
+    assert.strictEqual(validateSql(sql).valid, false);
+
+    const result = validateTemplate(sql, [
+      { start: 14, end: 19 },
+      { start: 31, end: 36 },
+    ]);
+    assert.strictEqual(result.valid, true);
+    assert.deepStrictEqual(
+      result.holes.map((h) => h.role),
+      ['table', 'expression'],
+    );
+  });
+
+  await t.test('counts offsets in UTF-16 code units by default', () => {
+    const sql = "SELECT '日本🦀', count(x FROM t";
+    const from = sql.indexOf('FROM');
+    const result = validateSql(sql);
+    assert.strictEqual(result.errors[0].startOffset, from);
+    assert.strictEqual(result.errors[0].column, from + 1);
+
+    const bytes = validateSql(sql, 'byte');
+    assert.strictEqual(
+      bytes.errors[0].startOffset,
+      Buffer.byteLength(sql.slice(0, from)),
+    );
+  });
+
+  await t.test('positions template errors in the original text', () => {
+    const sql = 'SELECT ${someLongExpression} FROM t WHERE = 1';
+    const result = validateTemplate(sql, [{ start: 7, end: 28 }]);
+    assert.strictEqual(result.valid, false);
+    assert.strictEqual(result.errors[0].startOffset, sql.indexOf('= 1'));
+    assert.strictEqual(result.errors[0].column, sql.indexOf('= 1') + 1);
+  });
+
   await t.test('validates ClickHouse-specific syntax', () => {
     const result = validateSql(
       'CREATE MATERIALIZED VIEW mv AS SELECT * FROM source',
@@ -128,7 +200,8 @@ const testData = JSON.stringify({
     { name: 'file', description: 'Reads from file' },
     { name: 'url', description: 'Reads from URL' },
   ],
-  aggregateCombinators: [],
+  aggregateCombinators: ['If', 'OrNull'],
+  timeZones: ['UTC', 'Europe/Berlin'],
   settings: [
     { name: 'max_threads', type: 'UInt64', description: 'Max threads' },
   ],
@@ -142,15 +215,19 @@ const testData = JSON.stringify({
 });
 
 describe('completions', () => {
-  test('initializes completion data', () => {
-    const result = initCompletionData(testData);
+  const VERSION = '25.8';
+  const engine = new ClickHouseEngine();
+
+  test('loads completion data', () => {
+    const result = engine.load(VERSION, testData);
     assert.strictEqual(result.success, true);
     // error can be null or undefined when success
     assert.ok(!result.error);
+    assert.deepStrictEqual(engine.versions(), [VERSION]);
   });
 
   test('returns all completions for default context', () => {
-    const completions = getCompletions('', 0);
+    const completions = engine.getCompletions(VERSION, '', 0).items;
     assert.ok(completions.length > 0);
     // Should have functions, keywords, etc.
     assert.ok(completions.some((c) => c.label === 'count'));
@@ -158,37 +235,247 @@ describe('completions', () => {
   });
 
   test('returns only engines after ENGINE =', () => {
-    const completions = getCompletions('CREATE TABLE t ENGINE = ', 24);
+    const completions = engine.getCompletions(
+      VERSION,
+      'CREATE TABLE t ENGINE = ',
+      24,
+    ).items;
     assert.ok(completions.length > 0);
     assert.ok(completions.every((c) => c.detail === '(table engine)'));
     assert.ok(completions.some((c) => c.label === 'MergeTree'));
   });
 
   test('returns only formats after FORMAT', () => {
-    const completions = getCompletions('SELECT * FORMAT ', 16);
+    const completions = engine.getCompletions(
+      VERSION,
+      'SELECT * FORMAT ',
+      16,
+    ).items;
     assert.ok(completions.length > 0);
     assert.ok(completions.every((c) => c.detail?.includes('format')));
     assert.ok(completions.some((c) => c.label === 'JSON'));
   });
 
   test('returns functions in WHERE clause', () => {
-    const completions = getCompletions('SELECT * FROM t WHERE ', 22);
+    const completions = engine.getCompletions(
+      VERSION,
+      'SELECT * FROM t WHERE ',
+      22,
+    ).items;
     assert.ok(completions.some((c) => c.label === 'count'));
     assert.ok(completions.some((c) => c.label === 'AND'));
   });
 
+  test('reads the cursor as a UTF-16 offset', () => {
+    const sql = "-- 日本語 🦀\nSELECT '🦀' FROM t WHERE ";
+    const completions = engine.getCompletions(VERSION, sql, sql.length).items;
+    assert.ok(completions.some((c) => c.label === 'AND'));
+  });
+
+  test('completes values in strings and nothing in comments', () => {
+    const zones = engine.getCompletions(
+      VERSION,
+      "SELECT toDateTime(ts, '",
+      23,
+    ).items;
+    assert.deepStrictEqual(
+      zones.map((c) => c.label),
+      ['Europe/Berlin', 'UTC'],
+    );
+    assert.ok(zones.every((c) => c.kind === 'time_zone'));
+
+    assert.deepStrictEqual(
+      engine.getCompletions(VERSION, 'SELECT 1 -- co', 14).items,
+      [],
+    );
+  });
+
   test('returns table functions after FROM', () => {
-    const completions = getCompletions('SELECT * FROM ', 14);
+    const completions = engine.getCompletions(
+      VERSION,
+      'SELECT * FROM ',
+      14,
+    ).items;
     assert.ok(completions.some((c) => c.label === 'file'));
   });
 
   test('returns data types in column definition', () => {
-    const completions = getCompletions('CREATE TABLE t (id ', 19);
+    const completions = engine.getCompletions(
+      VERSION,
+      'CREATE TABLE t (id ',
+      19,
+    ).items;
     assert.ok(completions.some((c) => c.label === 'UInt64'));
   });
 
   test('returns settings after SETTINGS', () => {
-    const completions = getCompletions('SELECT * SETTINGS ', 18);
+    const completions = engine.getCompletions(
+      VERSION,
+      'SELECT * SETTINGS ',
+      18,
+    ).items;
     assert.ok(completions.some((c) => c.label === 'max_threads'));
   });
+
+  test('ranks fuzzy matches of the typed word', () => {
+    const list = engine.getCompletions(
+      VERSION,
+      'SELECT * FROM t WHERE cnt',
+      25,
+    );
+    assert.strictEqual(list.items[0].label, 'count');
+    assert.ok(list.items.every((c) => c.score > 0));
+    assert.deepStrictEqual(list.replaceRange, { start: 22, end: 25 });
+
+    const inWord = engine.getCompletions(VERSION, 'SELECT cou + 1', 9);
+    assert.strictEqual(inWord.items[0].label, 'count');
+    assert.deepStrictEqual(inWord.replaceRange, { start: 7, end: 10 });
+
+    const limited = engine.getCompletions(VERSION, 'SELECT ', 7, 'utf16', 2);
+    assert.strictEqual(limited.items.length, 2);
+    assert.strictEqual(limited.isIncomplete, true);
+  });
+
+  test('resolves documentation on demand', () => {
+    const { items } = engine.getCompletions(VERSION, 'SELECT su', 9);
+    const item = items.find((c) => c.label === 'sum');
+    assert.ok(item);
+    assert.strictEqual('documentation' in item, false);
+
+    const doc = engine.resolveCompletion(VERSION, item.label, item.kind);
+    assert.ok(doc?.value.includes('Sums values'));
+    assert.strictEqual(
+      engine.resolveCompletion(VERSION, 'SELECT', 'keyword'),
+      null,
+    );
+  });
+
+  test('offers and documents combinator chains', () => {
+    const completions = engine.getCompletions(
+      VERSION,
+      'SELECT sumIfOr',
+      14,
+    ).items;
+    assert.ok(completions.some((c) => c.label === 'sumIfOrNull'));
+
+    const doc = engine.getCombinatorDocumentation(VERSION, 'sumIfOrNull');
+    assert.ok(doc?.value.includes('`If` and `OrNull` combinators'));
+    assert.ok(doc?.value.includes('Sums values'));
+    assert.strictEqual(engine.getCombinatorDocumentation(VERSION, 'sum'), null);
+  });
+
+  test('keeps versions apart and replaces reloaded data', () => {
+    const other = new ClickHouseEngine();
+    other.load('25.6', testData);
+    other.load('25.8', testData.replace('"sum"', '"sumOnlyIn258"'));
+    const labels = (version: string) =>
+      other.getCompletions(version, 'SELECT su', 9).items.map((c) => c.label);
+    assert.ok(labels('25.6').includes('sum'));
+    assert.ok(!labels('25.8').includes('sum'));
+    assert.ok(labels('25.8').includes('sumOnlyIn258'));
+    assert.deepStrictEqual(labels('25.7'), []);
+
+    other.load('25.6', testData.replace('"sum"', '"sumOnlyIn258"'));
+    assert.ok(!labels('25.6').includes('sum'));
+    assert.strictEqual(other.unload('25.8'), true);
+    assert.deepStrictEqual(other.versions(), ['25.6']);
+  });
+
+  test('checks names only against loaded data', () => {
+    const sql = 'SELECT nosuchFunction(1)';
+    assert.strictEqual(engine.validateSql(VERSION, sql).valid, false);
+    assert.strictEqual(engine.validateSql('25.7', sql).valid, true);
+  });
+
+  test('completes registered tables and columns', () => {
+    const result = engine.registerSchema({
+      databases: [
+        {
+          name: 'analytics',
+          tables: [
+            {
+              name: 'events',
+              comment: 'Page views',
+              columns: [
+                { name: 'user_id', type: 'UInt64', comment: 'Who acted' },
+                { name: 'ts', type: 'DateTime' },
+              ],
+            },
+          ],
+        },
+      ],
+    });
+    assert.strictEqual(result.success, true);
+
+    const tables = engine.getCompletions(VERSION, 'SELECT * FROM ev', 16);
+    assert.strictEqual(tables.items[0].label, 'events');
+    assert.strictEqual(tables.items[0].kind, 'table');
+
+    const sql = 'SELECT e. FROM analytics.events AS e';
+    const columns = engine.getCompletions(VERSION, sql, 9).items;
+    assert.deepStrictEqual(
+      columns.map((c) => c.label),
+      ['ts', 'user_id'],
+    );
+    const doc = engine.resolveCompletion(VERSION, 'user_id', 'column');
+    assert.ok(doc?.value.includes('Who acted'));
+  });
+
+  test('registers a schema built from DDL', () => {
+    const built = buildSchemaFromDdl([
+      'CREATE TABLE events (id UInt64, ts DateTime) ENGINE = MergeTree ORDER BY id',
+      'CREATE TABLE events (id String) ENGINE = MergeTree ORDER BY id',
+    ]);
+    assert.strictEqual(built.errors.length, 1);
+    assert.strictEqual(built.errors[0].source, 1);
+    assert.strictEqual(
+      built.errors[0].code,
+      'semantic/conflicting-definition',
+    );
+    const events = built.schema.databases[0].tables[0];
+    assert.strictEqual(events.engine, 'MergeTree');
+    assert.deepStrictEqual(events.orderBy, ['id']);
+
+    engine.registerSchema(built.schema);
+    const sql = 'SELECT  FROM events';
+    const columns = engine.getCompletions(VERSION, sql, 7).items;
+    assert.ok(columns.some((c) => c.label === 'ts' && c.kind === 'column'));
+  });
+
+  test('checks tables and columns against the registered schema', () => {
+    engine.registerSchema({
+      databases: [
+        {
+          name: 'default',
+          tables: [{ name: 'events', columns: [{ name: 'user_id' }] }],
+        },
+      ],
+    });
+    const result = engine.validateSql(VERSION, 'SELECT usre_id FROM events');
+    assert.strictEqual(result.errors[0].code, 'semantic/unknown-column');
+    assert.deepStrictEqual(result.errors[0].suggestions, ['user_id']);
+  });
+
+  test('registers the tables of an infrastructure map', () => {
+    const result = engine.registerInfrastructureMap({
+      tables: {
+        PageView_0_1: {
+          name: 'PageView',
+          version: '0.1',
+          columns: [
+            { name: 'path', data_type: 'String', required: true },
+            { name: 'referrer', data_type: 'String', required: false },
+          ],
+          order_by: ['path'],
+        },
+      },
+    });
+    assert.strictEqual(result.success, true);
+
+    const sql = 'SELECT  FROM local.PageView_0_1';
+    const columns = engine.getCompletions(VERSION, sql, 7).items;
+    assert.ok(columns.some((c) => c.label === 'referrer'));
+    const checked = engine.validateSql(VERSION, 'SELECT pth FROM PageView_0_1');
+    assert.strictEqual(checked.errors[0].code, 'semantic/unknown-column');
+  });
 });
@@ -1,10 +1,71 @@
+/** Broad family of a validation error; `usage` errors come from calls
+ * given an unknown encoding or mode rather than from the SQL */
+export type ErrorCategory = 'syntax' | 'semantic' | 'usage';
+
+/**
+ * A validation failure and the source range it applies to.
+ * Lines and columns are 1-based and the end is exclusive. Columns and offsets
+ * count units of the requested `OffsetEncoding`, UTF-16 code units by default.
+ */
+export interface ValidationError {
+  /** Stable `<category>/<rule>` identifier, e.g. `syntax/unclosed-paren` */
+  code: string;
+  category: ErrorCategory;
+  message: string;
+  line?: number;
+  column?: number;
+  endLine?: number;
+  endColumn?: number;
+  /** Offset of the first offending character */
+  startOffset?: number;
+  /** Offset just past the last offending character */
+  endOffset?: number;
+  /** Likely intended names, best first, for "did you mean" quick fixes */
+  suggestions?: string[];
+}
+
 export interface ValidationResult {
   valid: boolean;
-  error?: {
-    message: string;
-    line?: number;
-    column?: number;
-  };
+  /** First error, kept for callers that only show a single diagnostic */
+  error?: ValidationError;
+  /** Every error found, in source order */
+  errors: ValidationError[];
+}
+
+/** What `validateSqlFragment` parses its input as */
+export type ParseMode =
+  | 'statement'
+  | 'expression'
+  | 'condition'
+  | 'select_items'
+  | 'order_by'
+  | 'column_definitions';
+
+/**
+ * Unit that offsets and columns count, both given and returned. JavaScript
+ * strings index UTF-16 code units, so that is the default everywhere.
+ */
+export type OffsetEncoding = 'byte' | 'char' | 'utf16';
+
+/** Range of a template placeholder in the SQL */
+export interface Hole {
+  start: number;
+  end: number;
+}
+
+/** What a placeholder turned out to stand for */
+export type HoleRole = 'value' | 'identifier' | 'table' | 'expression';
+
+export interface TemplateValidationResult extends ValidationResult {
+  /** Each placeholder with its role; null when no reading of it parses */
+  holes: Array<Hole & { role: HoleRole | null }>;
+}
+
+/** Catalog entry describing one validation error code */
+export interface ErrorCodeInfo {
+  code: string;
+  category: ErrorCategory;
+  description: string;
 }
 
 export interface FormatResult {
@@ -27,22 +88,82 @@ export type CompletionItemKind =
   | 'format'
   | 'setting'
   | 'aggregate_function'
-  | 'table_function';
+  | 'table_function'
+  | 'time_zone'
+  | 'table'
+  | 'column';
 
-/** Completion item from Rust - domain data only, no LSP-specific types */
+/** Completion item from Rust - domain data only, no LSP-specific types.
+ * Documentation is fetched with `resolveCompletion` when an item is selected. */
 export interface CompletionItem {
   label: string;
   kind: CompletionItemKind;
   detail?: string;
-  documentation?: {
-    kind: string;
-    value: string;
-  };
   /** Whether this completion accepts parameters (for functions) */
   hasParams: boolean;
   sortText?: string;
 }
 
+/** A completion item with how well it matches the word being typed */
+export interface RankedCompletionItem extends CompletionItem {
+  /** Fuzzy match score of the label; higher is better */
+  score: number;
+}
+
+/** Range of the input SQL, in the offset encoding of the request */
+export interface TextRange {
+  start: number;
+  end: number;
+}
+
+/** Completions at a cursor, best first */
+export interface CompletionList {
+  items: RankedCompletionItem[];
+  /** The word at the cursor, which the chosen completion replaces: all of it,
+   * with the quotes of a quoted identifier, or the contents of a string */
+  replaceRange: TextRange;
+  /** Whether matches beyond the requested number were left out, so the list
+   * should be requested again as typing continues */
+  isIncomplete: boolean;
+  /** Set when the call's arguments are invalid, such as an unknown encoding */
+  error?: ValidationError;
+}
+
+/** A column of a registered table */
+export interface ColumnSchema {
+  name: string;
+  /** ClickHouse type, such as `LowCardinality(String)`; empty when unknown */
+  type?: string;
+  comment?: string;
+}
+
+/** What a name in `FROM` refers to */
+export type TableKind = 'table' | 'view' | 'materialized_view';
+
+export interface TableSchema {
+  name: string;
+  /** Defaults to `table` */
+  kind?: TableKind;
+  columns: ColumnSchema[];
+  comment?: string;
+  /** Table engine with its arguments, such as `ReplacingMergeTree(ts)` */
+  engine?: string;
+  /** Expressions of the sorting key */
+  orderBy?: string[];
+  /** Expression of the partition key */
+  partitionBy?: string;
+}
+
+export interface DatabaseSchema {
+  name: string;
+  tables: TableSchema[];
+}
+
+/** The databases, tables and columns queries are written against */
+export interface Schema {
+  databases: DatabaseSchema[];
+}
+
 // The nodejs target auto-initializes WASM synchronously
 // eslint-disable-next-line @typescript-eslint/no-require-imports
 const wasmModule = require('../pkg/sql_validator.js');
@@ -52,25 +173,215 @@ export async function initValidator(): Promise<void> {
   return Promise.resolve();
 }
 
-export function validateSql(sql: string): ValidationResult {
-  const resultJson = wasmModule.validate_sql(sql);
+export function validateSql(
+  sql: string,
+  encoding: OffsetEncoding = 'utf16',
+): ValidationResult {
+  const resultJson = wasmModule.validate_sql_with_encoding(sql, encoding);
   return JSON.parse(resultJson);
 }
 
-export function formatSql(sql: string): FormatResult {
-  const resultJson = wasmModule.format_sql(sql);
+/** Validates a `sql.fragment` template body parsed as `mode` */
+export function validateSqlFragment(
+  sql: string,
+  mode: ParseMode,
+  encoding: OffsetEncoding = 'utf16',
+): ValidationResult {
+  const resultJson = wasmModule.validate_sql_fragment(sql, mode, encoding);
   return JSON.parse(resultJson);
 }
 
-export function initCompletionData(json: string): InitCompletionResult {
-  const resultJson = wasmModule.init_completion_data(json);
+/**
+ * Validates SQL containing placeholders. Each hole is tried as a value, a
+ * name, a table and an expression, and the role it played is reported.
+ * Errors are positioned in `sql` as given, not in the SQL with the holes
+ * filled in.
+ */
+export function validateTemplate(
+  sql: string,
+  holes: Hole[],
+  mode: ParseMode = 'statement',
+  encoding: OffsetEncoding = 'utf16',
+): TemplateValidationResult {
+  const resultJson = wasmModule.validate_template(
+    sql,
+    JSON.stringify(holes),
+    mode,
+    encoding,
+  );
   return JSON.parse(resultJson);
 }
 
-export function getCompletions(
-  sql: string,
-  cursorOffset: number,
-): CompletionItem[] {
-  const resultJson = wasmModule.get_completions(sql, cursorOffset);
+/** An error in one of the sources given to `buildSchemaFromDdl` */
+export interface SourceError extends ValidationError {
+  /** Index of the source the error is in */
+  source: number;
+}
+
+export interface DdlSchemaResult {
+  schema: Schema;
+  /** Syntax errors and conflicting definitions, by source */
+  errors: SourceError[];
+  /** Set when the arguments themselves are invalid, with the
+   * `usage/invalid-argument` code */
+  error?: ValidationError;
+}
+
+/**
+ * Builds a schema from the `CREATE TABLE` and `CREATE VIEW` statements of
+ * `sources`, such as migration files, read in order. The schema can be given
+ * to `ClickHouseEngine.registerSchema`.
+ */
+export function buildSchemaFromDdl(
+  sources: string[],
+  encoding: OffsetEncoding = 'utf16',
+): DdlSchemaResult {
+  const resultJson = wasmModule.build_schema_from_ddl(
+    JSON.stringify(sources),
+    encoding,
+  );
   return JSON.parse(resultJson);
 }
+
+export function getErrorCodes(): ErrorCodeInfo[] {
+  const resultJson = wasmModule.get_error_codes();
+  return JSON.parse(resultJson);
+}
+
+export function formatSql(sql: string): FormatResult {
+  const resultJson = wasmModule.format_sql(sql);
+  return JSON.parse(resultJson);
+}
+
+/**
+ * Completion and validation against `ClickHouse` data loaded per version.
+ * Versions are held side by side under keys the caller chooses, and loading
+ * a key again replaces its data. A version that is not loaded gets no
+ * completions and syntax-only validation.
+ */
+export class ClickHouseEngine {
+  private readonly engine = new wasmModule.Engine();
+
+  /** Loads `ClickHouse` data JSON under `version`. Data that does not parse
+   * leaves what was loaded under it before. */
+  load(version: string, json: string): InitCompletionResult {
+    const resultJson = this.engine.load(version, json);
+    return JSON.parse(resultJson);
+  }
+
+  /** Registers the tables and columns of the project for all versions,
+   * replacing those registered before. They are completed after `FROM`, in
+   * expressions over the tables in scope, and after `table.` or `alias.`. */
+  registerSchema(schema: Schema): InitCompletionResult {
+    const resultJson = this.engine.register_schema(JSON.stringify(schema));
+    return JSON.parse(resultJson);
+  }
+
+  /** Registers the tables and views of a Moose infrastructure map, as the
+   * `moose` CLI dumps it, in place of a schema. The map is given as parsed
+   * JSON or as its text; tables without a database go in `database`. */
+  registerInfrastructureMap(
+    map: string | object,
+    database = 'local',
+  ): InitCompletionResult {
+    const json = typeof map === 'string' ? map : JSON.stringify(map);
+    const resultJson = this.engine.register_infrastructure_map(json, database);
+    return JSON.parse(resultJson);
+  }
+
+  /** Drops the data loaded under `version`, returning whether there was any */
+  unload(version: string): boolean {
+    return this.engine.unload(version);
+  }
+
+  /** The loaded versions, sorted */
+  versions(): string[] {
+    return JSON.parse(this.engine.versions());
+  }
+
+  /** `validateSql`, also checking names against the data of `version` and
+   * tables and columns against the registered schema */
+  validateSql(
+    version: string,
+    sql: string,
+    encoding: OffsetEncoding = 'utf16',
+  ): ValidationResult {
+    const resultJson = this.engine.validate_sql(version, sql, encoding);
+    return JSON.parse(resultJson);
+  }
+
+  /** `validateSqlFragment`, also checking names against the data of
+   * `version` */
+  validateSqlFragment(
+    version: string,
+    sql: string,
+    mode: ParseMode,
+    encoding: OffsetEncoding = 'utf16',
+  ): ValidationResult {
+    const resultJson = this.engine.validate_sql_fragment(
+      version,
+      sql,
+      mode,
+      encoding,
+    );
+    return JSON.parse(resultJson);
+  }
+
+  /** `validateTemplate`, also checking names against the data of `version` */
+  validateTemplate(
+    version: string,
+    sql: string,
+    holes: Hole[],
+    mode: ParseMode = 'statement',
+    encoding: OffsetEncoding = 'utf16',
+  ): TemplateValidationResult {
+    const resultJson = this.engine.validate_template(
+      version,
+      sql,
+      JSON.stringify(holes),
+      mode,
+      encoding,
+    );
+    return JSON.parse(resultJson);
+  }
+
+  /** The `limit` completions that best match the word typed before the
+   * cursor, matched fuzzily so that `tSOH` finds `toStartOfHour` */
+  getCompletions(
+    version: string,
+    sql: string,
+    cursorOffset: number,
+    encoding: OffsetEncoding = 'utf16',
+    limit = 100,
+  ): CompletionList {
+    const resultJson = this.engine.get_completions(
+      version,
+      sql,
+      cursorOffset,
+      encoding,
+      limit,
+    );
+    return JSON.parse(resultJson);
+  }
+
+  /** Markdown documentation for a completion item, looked up by the label
+   * and kind it was returned with, or null when it has none */
+  resolveCompletion(
+    version: string,
+    label: string,
+    kind: CompletionItemKind,
+  ): { kind: string; value: string } | null {
+    const resultJson = this.engine.resolve_completion(version, label, kind);
+    return JSON.parse(resultJson);
+  }
+
+  /** Markdown documentation for an aggregate combinator form such as
+   * `countIf` or `avgMergeOrNull`, or null when the name is not one */
+  getCombinatorDocumentation(
+    version: string,
+    name: string,
+  ): { kind: string; value: string } | null {
+    const resultJson = this.engine.get_combinator_documentation(version, name);
+    return JSON.parse(resultJson);
+  }
+}
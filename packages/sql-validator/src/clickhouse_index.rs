@@ -0,0 +1,255 @@
+//! Name lookups over the loaded `ClickHouseData`, used by semantic validation.
+
+use crate::combinators::{self, CombinatorForm};
+use crate::signature::{parse_signatures, undocumented_syntax, Signature};
+use crate::{ClickHouseData, DataTypeInfo, FormatInfo, FunctionInfo, SettingInfo};
+use std::collections::{HashMap, HashSet};
+
+/// `ClickHouseData` plus the indexes needed to resolve names quickly
+#[derive(Debug)]
+pub(crate) struct ClickHouseIndex {
+    pub data: ClickHouseData,
+    /// Exact function name -> index into `data.functions`
+    functions: HashMap<String, usize>,
+    /// Lowercased name -> index, only for case-insensitive functions
+    functions_ci: HashMap<String, usize>,
+    /// Exact data type name -> index into `data.data_types`
+    data_types: HashMap<String, usize>,
+    /// Lowercased name -> index, only for case-insensitive data types
+    data_types_ci: HashMap<String, usize>,
+    /// Lowercased leading words of multi-word types such as
+    /// `DOUBLE PRECISION`, including the full names
+    data_type_phrases: HashSet<String>,
+}
+
+impl ClickHouseIndex {
+    pub fn new(data: ClickHouseData) -> Self {
+        let mut functions = HashMap::new();
+        let mut functions_ci = HashMap::new();
+        for (i, func) in data.functions.iter().enumerate() {
+            functions.insert(func.name.clone(), i);
+            if func.case_insensitive {
+                functions_ci.insert(func.name.to_lowercase(), i);
+            }
+        }
+
+        let mut data_types = HashMap::new();
+        let mut data_types_ci = HashMap::new();
+        let mut data_type_phrases = HashSet::new();
+        for (i, data_type) in data.data_types.iter().enumerate() {
+            data_types.insert(data_type.name.clone(), i);
+            if data_type.case_insensitive {
+                data_types_ci.insert(data_type.name.to_lowercase(), i);
+            }
+            let words: Vec<String> = data_type
+                .name
+                .split_whitespace()
+                .map(str::to_lowercase)
+                .collect();
+            for n in 2..=words.len() {
+                data_type_phrases.insert(words[..n].join(" "));
+            }
+        }
+
+        Self {
+            data,
+            functions,
+            functions_ci,
+            data_types,
+            data_types_ci,
+            data_type_phrases,
+        }
+    }
+
+    /// Looks up a function by name, honouring `ClickHouse` case sensitivity
+    pub fn function(&self, name: &str) -> Option<&FunctionInfo> {
+        self.functions
+            .get(name)
+            .or_else(|| self.functions_ci.get(&name.to_lowercase()))
+            .map(|&i| &self.data.functions[i])
+    }
+
+    /// Resolves a function name, falling back to splitting off aggregate
+    /// combinator suffixes when the full name is not listed. For combinator
+    /// forms such as `avgMergeOrNull` the base aggregate function is returned.
+    pub fn resolve_function(&self, name: &str) -> Option<&FunctionInfo> {
+        if let Some(func) = self.function(name) {
+            return Some(func);
+        }
+        self.combinator_form(name)
+            .map(|form| form.base)
+            .filter(|base| base.is_aggregate)
+    }
+
+    /// Splits `name` into a base function and the combinators appended to
+    /// it. `None` for documented functions, even those whose names end in a
+    /// combinator (`groupArray`), and for names that do not split. The base
+    /// is not required to be an aggregate so callers can explain misuse.
+    pub fn combinator_form(&self, name: &str) -> Option<CombinatorForm<'_>> {
+        if self
+            .function(name)
+            .is_some_and(|f| !combinators::is_generated(f))
+        {
+            return None;
+        }
+        self.split_combinators(name)
+    }
+
+    fn split_combinators(&self, name: &str) -> Option<CombinatorForm<'_>> {
+        let mut suffixes: Vec<&String> = self
+            .data
+            .aggregate_combinators
+            .iter()
+            .filter(|c| name.len() > c.len() && name.ends_with(c.as_str()))
+            .collect();
+        suffixes.sort_by_key(|c| std::cmp::Reverse(c.len()));
+
+        for combinator in suffixes {
+            let rest = &name[..name.len() - combinator.len()];
+            let inner = match self.function(rest) {
+                Some(base) if !combinators::is_generated(base) => Some(CombinatorForm {
+                    base,
+                    combinators: Vec::new(),
+                }),
+                _ => self.split_combinators(rest),
+            };
+            if let Some(mut form) = inner {
+                form.combinators.push(combinator);
+                return Some(form);
+            }
+        }
+        None
+    }
+
+    /// Overloads of `func` parsed from its documented syntax, falling back to
+    /// the function it aliases and then to the syntax of common undocumented
+    /// functions. Empty for other functions without documented syntax, whose
+    /// argument counts are then not checked.
+    pub fn signatures(&self, func: &FunctionInfo) -> Vec<Signature> {
+        let documented = if func.syntax.is_empty() {
+            func.alias_to
+                .as_deref()
+                .and_then(|target| self.function(target))
+        } else {
+            Some(func)
+        };
+        let func = documented.unwrap_or(func);
+        if func.syntax.is_empty() {
+            undocumented_syntax(&func.name)
+                .map(|syntax| parse_signatures(&func.name, &syntax))
+                .unwrap_or_default()
+        } else {
+            parse_signatures(&func.name, &func.syntax)
+        }
+    }
+
+    pub fn function_names(&self) -> impl Iterator<Item = &str> {
+        self.data.functions.iter().map(|f| f.name.as_str())
+    }
+
+    /// Looks up a data type by name, honouring `ClickHouse` case sensitivity.
+    /// Multi-word names must be joined by single spaces.
+    pub fn data_type(&self, name: &str) -> Option<&DataTypeInfo> {
+        self.data_types
+            .get(name)
+            .or_else(|| self.data_types_ci.get(&name.to_lowercase()))
+            .map(|&i| &self.data.data_types[i])
+    }
+
+    /// The name a data type resolves to after following its alias
+    pub fn canonical_data_type(&self, name: &str) -> Option<&str> {
+        let data_type = self.data_type(name)?;
+        Some(data_type.alias_to.as_deref().unwrap_or(&data_type.name))
+    }
+
+    /// Whether `words` (space separated) is a multi-word data type or the
+    /// start of one
+    pub fn is_data_type_phrase(&self, words: &str) -> bool {
+        self.data_type_phrases.contains(&words.to_lowercase())
+    }
+
+    /// Whether `name` is a table engine; engine names are case-sensitive
+    pub fn is_table_engine(&self, name: &str) -> bool {
+        self.data.table_engines.iter().any(|e| e.name == name)
+    }
+
+    pub fn table_engine_names(&self) -> impl Iterator<Item = &str> {
+        self.data.table_engines.iter().map(|e| e.name.as_str())
+    }
+
+    /// Looks up a format by name. The data does not say which formats are
+    /// case-insensitive, so an exact match is preferred but case is ignored.
+    pub fn format(&self, name: &str) -> Option<&FormatInfo> {
+        let formats = &self.data.formats;
+        formats
+            .iter()
+            .find(|f| f.name == name)
+            .or_else(|| formats.iter().find(|f| f.name.eq_ignore_ascii_case(name)))
+    }
+
+    /// Looks up a query-level setting
+    pub fn setting(&self, name: &str) -> Option<&SettingInfo> {
+        self.data.settings.iter().find(|s| s.name == name)
+    }
+
+    /// Looks up a `MergeTree` table setting
+    pub fn merge_tree_setting(&self, name: &str) -> Option<&SettingInfo> {
+        self.data
+            .merge_tree_settings
+            .iter()
+            .find(|s| s.name == name)
+    }
+
+    pub fn data_type_names(&self) -> impl Iterator<Item = &str> {
+        self.data.data_types.iter().map(|t| t.name.as_str())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test_data {
+    use super::ClickHouseIndex;
+    use std::sync::OnceLock;
+
+    /// The bundled `ClickHouse` 25.8 data, parsed once per test run
+    pub(crate) fn clickhouse_25_8() -> &'static ClickHouseIndex {
+        static INDEX: OnceLock<ClickHouseIndex> = OnceLock::new();
+        INDEX.get_or_init(|| {
+            let json = include_str!("../../lsp-server/src/data/clickhouse-25.8.json");
+            ClickHouseIndex::new(serde_json::from_str(json).expect("bundled data parses"))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::test_data::clickhouse_25_8;
+
+    #[test]
+    fn test_function_case_sensitivity() {
+        let index = clickhouse_25_8();
+        assert!(index.function("toStartOfHour").is_some());
+        // toStartOfHour is case-sensitive, count is not
+        assert!(index.function("tostartofhour").is_none());
+        assert!(index.function("COUNT").is_some());
+    }
+
+    #[test]
+    fn test_resolve_combinators() {
+        let index = clickhouse_25_8();
+        let base = index.resolve_function("avgMergeOrNull").expect("resolves");
+        assert!(base.name.starts_with("avg") && base.is_aggregate);
+        assert!(index.resolve_function("toStartOfHourIf").is_none());
+    }
+
+    #[test]
+    fn test_data_type_lookup() {
+        let index = clickhouse_25_8();
+        assert!(index.data_type("UInt64").is_some());
+        assert!(index.data_type("uint64").is_none());
+        assert_eq!(index.canonical_data_type("datetime"), Some("DateTime"));
+        assert_eq!(index.canonical_data_type("bigint unsigned"), Some("UInt64"));
+        assert!(index.is_data_type_phrase("DOUBLE PRECISION"));
+        assert!(index.is_data_type_phrase("national character"));
+        assert!(!index.is_data_type_phrase("String DEFAULT"));
+    }
+}
@@ -0,0 +1,187 @@
+//! Stable, machine-readable codes attached to every `ValidationError`.
+//!
+//! A code is a `<category>/<rule>` string. Codes are part of the public
+//! contract: once published they are never renamed or reused, so editor
+//! settings can key severities, filters and quick fixes on them.
+//!
+//! | Code | Category | Reported when |
+//! |------|----------|---------------|
+//! | `syntax/unexpected-token` | syntax | The parser found a token that cannot appear at that point |
+//! | `syntax/unexpected-end` | syntax | The statement ends before it is complete |
+//! | `syntax/unclosed-paren` | syntax | A `(` is never closed |
+//! | `syntax/unmatched-paren` | syntax | A `)` has no matching `(` |
+//! | `syntax/unterminated-string` | syntax | A `'...'` string literal is never closed |
+//! | `syntax/unterminated-identifier` | syntax | A `"..."` or `` `...` `` quoted identifier is never closed |
+//! | `syntax/unterminated-comment` | syntax | A `/* ... */` comment is never closed |
+//! | `syntax/invalid-token` | syntax | Any other text the tokenizer cannot read |
+//! | `syntax/too-deeply-nested` | syntax | Expressions or subqueries exceed the parser's nesting limit |
+//! | `semantic/unknown-function` | semantic | A called function is not a known function, alias or combinator form |
+//! | `semantic/wrong-argument-count` | semantic | A function is called with fewer or more arguments than its documented signature allows |
+//! | `semantic/aggregate-in-where` | semantic | An aggregate function is used in `WHERE` or `PREWHERE` |
+//! | `semantic/nested-aggregate` | semantic | An aggregate function is used inside another aggregate function |
+//! | `semantic/not-in-group-by` | semantic | A selected column is neither aggregated nor listed in `GROUP BY` |
+//! | `semantic/unknown-data-type` | semantic | A column is declared with a data type that does not exist |
+//! | `semantic/unknown-table-engine` | semantic | `ENGINE =` names a table engine that does not exist |
+//! | `semantic/invalid-engine-arguments` | semantic | A table engine is given the wrong number or kind of arguments |
+//! | `semantic/unknown-format` | semantic | `FORMAT` names a format that does not exist |
+//! | `semantic/wrong-format-direction` | semantic | `SELECT ... FORMAT` uses an input-only format, or `INSERT ... FORMAT` an output-only one |
+//! | `semantic/unknown-setting` | semantic | A `SETTINGS` clause names a setting that does not exist |
+//! | `semantic/misplaced-setting` | semantic | A `MergeTree` setting in a query `SETTINGS`, or a query setting in `CREATE TABLE ... SETTINGS` |
+//! | `semantic/invalid-setting-value` | semantic | A setting value cannot be converted to the setting's type |
+//! | `semantic/conflicting-definition` | semantic | A table or view is created again with a different definition |
+//! | `semantic/unknown-table` | semantic | A query reads a table that is neither registered nor a CTE in scope |
+//! | `semantic/unknown-column` | semantic | A column is in none of the tables in scope |
+//! | `semantic/ambiguous-column` | semantic | An unqualified column is in more than one of the joined tables |
+//! | `usage/invalid-argument` | usage | An export is given an unknown offset encoding or parse mode, or placeholder spans that do not parse |
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Broad family of a diagnostic
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCategory {
+    /// The text is not valid `ClickHouse` SQL
+    Syntax,
+    /// The text parses but refers to something `ClickHouse` will reject
+    Semantic,
+    /// The call itself is invalid, whatever the text
+    Usage,
+}
+
+macro_rules! error_codes {
+    ($($variant:ident => ($code:literal, $category:ident, $description:literal),)+) => {
+        /// Stable identifier for each kind of validation error.
+        /// See the module docs for the full catalog.
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum ErrorCode {
+            $(#[doc = $description] $variant,)+
+        }
+
+        impl ErrorCode {
+            /// Every code, in catalog order
+            pub const ALL: &'static [ErrorCode] = &[$(ErrorCode::$variant,)+];
+
+            /// The `<category>/<rule>` string used in JSON output
+            #[must_use]
+            pub fn as_str(self) -> &'static str {
+                match self {
+                    $(ErrorCode::$variant => $code,)+
+                }
+            }
+
+            #[must_use]
+            pub fn category(self) -> ErrorCategory {
+                match self {
+                    $(ErrorCode::$variant => ErrorCategory::$category,)+
+                }
+            }
+
+            #[must_use]
+            pub fn description(self) -> &'static str {
+                match self {
+                    $(ErrorCode::$variant => $description,)+
+                }
+            }
+
+            #[must_use]
+            pub fn from_code(code: &str) -> Option<Self> {
+                match code {
+                    $($code => Some(ErrorCode::$variant),)+
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+error_codes! {
+    UnexpectedToken => ("syntax/unexpected-token", Syntax, "The parser found a token that cannot appear at that point"),
+    UnexpectedEnd => ("syntax/unexpected-end", Syntax, "The statement ends before it is complete"),
+    UnclosedParen => ("syntax/unclosed-paren", Syntax, "A `(` is never closed"),
+    UnmatchedParen => ("syntax/unmatched-paren", Syntax, "A `)` has no matching `(`"),
+    UnterminatedString => ("syntax/unterminated-string", Syntax, "A string literal is never closed"),
+    UnterminatedIdentifier => ("syntax/unterminated-identifier", Syntax, "A quoted identifier is never closed"),
+    UnterminatedComment => ("syntax/unterminated-comment", Syntax, "A multi-line comment is never closed"),
+    InvalidToken => ("syntax/invalid-token", Syntax, "The tokenizer cannot read this text"),
+    TooDeeplyNested => ("syntax/too-deeply-nested", Syntax, "Expressions or subqueries are nested too deeply"),
+    UnknownFunction => ("semantic/unknown-function", Semantic, "A called function is not a known function, alias or combinator form"),
+    WrongArgumentCount => ("semantic/wrong-argument-count", Semantic, "A function is called with fewer or more arguments than its documented signature allows"),
+    AggregateInWhere => ("semantic/aggregate-in-where", Semantic, "An aggregate function is used in WHERE or PREWHERE"),
+    NestedAggregate => ("semantic/nested-aggregate", Semantic, "An aggregate function is used inside another aggregate function"),
+    NotInGroupBy => ("semantic/not-in-group-by", Semantic, "A selected column is neither aggregated nor listed in GROUP BY"),
+    UnknownDataType => ("semantic/unknown-data-type", Semantic, "A column is declared with a data type that does not exist"),
+    UnknownTableEngine => ("semantic/unknown-table-engine", Semantic, "The ENGINE clause names a table engine that does not exist"),
+    InvalidEngineArguments => ("semantic/invalid-engine-arguments", Semantic, "A table engine is given the wrong number or kind of arguments"),
+    UnknownFormat => ("semantic/unknown-format", Semantic, "The FORMAT clause names a format that does not exist"),
+    WrongFormatDirection => ("semantic/wrong-format-direction", Semantic, "A query outputs in an input-only format, or an insert reads an output-only one"),
+    UnknownSetting => ("semantic/unknown-setting", Semantic, "A SETTINGS clause names a setting that does not exist"),
+    MisplacedSetting => ("semantic/misplaced-setting", Semantic, "A table setting is used in a query SETTINGS clause, or a query setting in a table one"),
+    InvalidSettingValue => ("semantic/invalid-setting-value", Semantic, "A setting value cannot be converted to the setting's type"),
+    ConflictingDefinition => ("semantic/conflicting-definition", Semantic, "A table or view is created again with a different definition"),
+    UnknownTable => ("semantic/unknown-table", Semantic, "A query reads a table that is neither registered nor a CTE in scope"),
+    UnknownColumn => ("semantic/unknown-column", Semantic, "A column is in none of the tables in scope"),
+    AmbiguousColumn => ("semantic/ambiguous-column", Semantic, "An unqualified column is in more than one of the joined tables"),
+    InvalidArgument => ("usage/invalid-argument", Usage, "An export is given an unknown offset encoding or parse mode, or placeholder spans that do not parse"),
+}
+
+impl Serialize for ErrorCode {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for ErrorCode {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let code = String::deserialize(deserializer)?;
+        ErrorCode::from_code(&code)
+            .ok_or_else(|| serde::de::Error::custom(format!("unknown error code: {code}")))
+    }
+}
+
+/// Catalog entry returned by `get_error_codes`
+#[derive(Serialize, Debug)]
+pub struct ErrorCodeInfo {
+    pub code: ErrorCode,
+    pub category: ErrorCategory,
+    pub description: &'static str,
+}
+
+#[must_use]
+pub fn catalog() -> Vec<ErrorCodeInfo> {
+    ErrorCode::ALL
+        .iter()
+        .map(|&code| ErrorCodeInfo {
+            code,
+            category: code.category(),
+            description: code.description(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_codes_are_unique_and_prefixed_by_category() {
+        let mut seen = std::collections::HashSet::new();
+        for &code in ErrorCode::ALL {
+            assert!(seen.insert(code.as_str()), "duplicate {}", code.as_str());
+            let prefix = match code.category() {
+                ErrorCategory::Syntax => "syntax/",
+                ErrorCategory::Semantic => "semantic/",
+                ErrorCategory::Usage => "usage/",
+            };
+            assert!(code.as_str().starts_with(prefix), "{}", code.as_str());
+            assert_eq!(ErrorCode::from_code(code.as_str()), Some(code));
+        }
+    }
+
+    #[test]
+    fn test_code_serializes_as_string() {
+        let json = serde_json::to_string(&ErrorCode::UnclosedParen).expect("serializes");
+        assert_eq!(json, r#""syntax/unclosed-paren""#);
+        let code: ErrorCode = serde_json::from_str(&json).expect("deserializes");
+        assert_eq!(code, ErrorCode::UnclosedParen);
+    }
+}
@@ -0,0 +1,129 @@
+//! Aggregate function combinators.
+//!
+//! A combinator is a suffix that turns an aggregate function into another one:
+//! `sumIf` is `sum` with `If`, and `avgMergeOrNull` is `avg` with `Merge`
+//! and then `OrNull`. The data lists single-combinator forms as generated
+//! functions but not chains, so names are split here instead.
+
+use crate::FunctionInfo;
+
+/// A function name split into the function it applies to and its combinators
+#[derive(Debug, Clone)]
+pub(crate) struct CombinatorForm<'a> {
+    pub base: &'a FunctionInfo,
+    /// In the order written, so the innermost combinator comes first:
+    /// `avgMergeOrNull` has `["Merge", "OrNull"]`
+    pub combinators: Vec<&'a str>,
+}
+
+impl CombinatorForm<'_> {
+    /// Arguments the combinators add to those of the base function, and the
+    /// exact count when a `Merge` replaces them with a single state
+    pub fn extra_arguments(&self) -> (usize, Option<usize>) {
+        let mut extra = 0;
+        // Arguments are consumed from the outermost combinator inwards
+        for combinator in self.combinators.iter().rev() {
+            match *combinator {
+                "If" | "ArgMin" | "ArgMax" | "Resample" => extra += 1,
+                "Merge" => return (extra, Some(extra + 1)),
+                _ => {}
+            }
+        }
+        (extra, None)
+    }
+}
+
+/// Whether `func` is an entry the data generator derived by appending a
+/// combinator to an aggregate, rather than a documented function
+pub(crate) fn is_generated(func: &FunctionInfo) -> bool {
+    func.description.contains("Generated by applying the `")
+}
+
+/// One-line explanation of what a combinator does
+pub(crate) fn describe(combinator: &str) -> &'static str {
+    match combinator {
+        "If" => "Takes a condition as an extra last argument and aggregates only the rows where it is true.",
+        "Array" => "Takes arrays as arguments and aggregates all of their elements.",
+        "Map" => "Takes a map as its argument and aggregates the values of each key separately.",
+        "ForEach" => "Takes arrays as arguments and aggregates the elements at each position separately, returning an array.",
+        "Distinct" => "Aggregates each distinct set of argument values only once.",
+        "State" => "Returns the intermediate aggregation state (an `AggregateFunction` value) instead of the result.",
+        "SimpleState" => "Returns the result as a `SimpleAggregateFunction` value, for storing in such columns.",
+        "Merge" => "Takes an intermediate aggregation state as its only argument and returns the final result.",
+        "OrDefault" => "Returns the default value of the result type when there is nothing to aggregate.",
+        "OrNull" => "Returns `NULL` when there is nothing to aggregate.",
+        "Resample" => "Takes a key as an extra last argument and aggregates separately for each `(start, end, step)` interval of it, returning an array.",
+        "ArgMin" => "Takes an extra last argument and aggregates only the rows where it has its minimum value.",
+        "ArgMax" => "Takes an extra last argument and aggregates only the rows where it has its maximum value.",
+        _ => "Modifies how the aggregate function is applied.",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::clickhouse_index::test_data::clickhouse_25_8;
+
+    fn split(name: &str) -> Option<(String, Vec<String>)> {
+        let form = clickhouse_25_8().combinator_form(name)?;
+        Some((
+            form.base.name.clone(),
+            form.combinators.iter().map(ToString::to_string).collect(),
+        ))
+    }
+
+    #[test]
+    fn test_splits_combinator_chains() {
+        assert_eq!(split("sumIf"), Some(("sum".into(), vec!["If".into()])));
+        assert_eq!(
+            split("avgMergeOrNull"),
+            Some(("avg".into(), vec!["Merge".into(), "OrNull".into()]))
+        );
+        assert_eq!(
+            split("uniqExactState"),
+            Some(("uniqExact".into(), vec!["State".into()]))
+        );
+        assert_eq!(
+            split("sumSimpleState"),
+            Some(("sum".into(), vec!["SimpleState".into()]))
+        );
+        // The base keeps its case-insensitivity, combinators do not
+        assert_eq!(split("COUNTIf"), Some(("count".into(), vec!["If".into()])));
+    }
+
+    #[test]
+    fn test_documented_functions_are_not_split() {
+        // groupArray ends in a combinator name but is a function of its own
+        assert_eq!(split("groupArray"), None);
+        assert_eq!(
+            split("groupArrayIf"),
+            Some(("groupArray".into(), vec!["If".into()]))
+        );
+        assert_eq!(split("toStartOfHour"), None);
+        assert_eq!(split("unknownIf"), None);
+    }
+
+    #[test]
+    fn test_non_aggregate_bases_are_reported_as_such() {
+        let form = clickhouse_25_8()
+            .combinator_form("toStartOfHourIf")
+            .expect("splits");
+        assert_eq!(form.base.name, "toStartOfHour");
+        assert!(!form.base.is_aggregate);
+    }
+
+    #[test]
+    fn test_extra_arguments() {
+        let index = clickhouse_25_8();
+        let extra = |name: &str| {
+            index
+                .combinator_form(name)
+                .expect("splits")
+                .extra_arguments()
+        };
+        assert_eq!(extra("sumIf"), (1, None));
+        assert_eq!(extra("avgMergeOrNull"), (0, Some(1)));
+        assert_eq!(extra("sumMergeIf"), (1, Some(2)));
+        assert_eq!(extra("sumIfMerge"), (0, Some(1)));
+        assert_eq!(extra("sumArrayIfDistinct"), (1, None));
+    }
+}
@@ -0,0 +1,629 @@
+//! Which clause the cursor is in, for choosing completions.
+//!
+//! The tokens before the cursor are read forwards, keeping a stack of open
+//! parentheses. A `(` opens a frame that stays in the clause around it, as
+//! for call arguments and `IN (...)` lists, unless a `SELECT` or `WITH`
+//! directly inside turns it into a subquery with clauses of its own; the `(`
+//! after `CREATE TABLE name` opens the column list. Only bare words are
+//! keywords: quoted identifiers, strings and names after a `.` are not.
+//!
+//! A cursor inside a comment or a string literal is reported as such, with
+//! the call or setting a string belongs to so that its value can be
+//! completed. Inside a quoted identifier the identifier is still being typed,
+//! and the clause around it decides.
+
+use crate::position::{self, LineIndex};
+use sqlparser::dialect::ClickHouseDialect;
+use sqlparser::keywords::Keyword;
+use sqlparser::tokenizer::{Token, TokenWithSpan, Tokenizer, Whitespace};
+
+/// SQL context detected from tokens before cursor
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum SqlContext {
+    /// After ENGINE = (show table engines)
+    Engine,
+    /// After FORMAT keyword (show formats)
+    Format,
+    /// In WHERE or HAVING clause (show functions + logical operators)
+    WhereClause,
+    /// After ORDER BY or GROUP BY (show functions + ASC/DESC/NULLS)
+    OrderByClause,
+    /// In SELECT before FROM (show functions, DISTINCT, AS)
+    SelectClause,
+    /// After FROM or JOIN (show table functions)
+    FromClause,
+    /// In column definition after CREATE TABLE ( (show data types)
+    ColumnDefinition,
+    /// After SETTINGS keyword (show settings)
+    Settings,
+    /// Unknown/default context (show all)
+    Default,
+    /// Inside a comment (show nothing)
+    Comment,
+    /// Inside a string literal passed as argument `argument` (0-based) of a
+    /// call of `function` (show values the argument takes)
+    StringArgument { function: String, argument: usize },
+    /// Inside a string literal given as the value of `setting`
+    SettingValue { setting: String },
+    /// Inside any other string literal (show nothing)
+    StringLiteral,
+}
+
+/// The clause of a statement or subquery that the last keyword started
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Clause {
+    /// Not yet in a clause, or in one that gets no special completions
+    Other,
+    /// After `CREATE`, before what is created
+    Create,
+    /// Between `CREATE TABLE` and the column list
+    CreateTable,
+    With,
+    Select,
+    /// `FROM` and `JOIN`
+    From,
+    /// `WHERE`, `PREWHERE`, `HAVING`, `QUALIFY` and `JOIN ... ON`
+    Filter,
+    /// `GROUP BY`, `ORDER BY`, `PARTITION BY` and `SAMPLE BY`
+    OrderBy,
+    Settings,
+    Format,
+    Engine,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Frame<'a> {
+    /// A statement or a parenthesized subquery
+    Query(Clause),
+    /// A parenthesized expression or argument list: the arguments of a call
+    /// of `call` when a name precedes it, now at argument `argument`
+    Paren {
+        call: Option<&'a str>,
+        argument: usize,
+    },
+    /// The column list of `CREATE TABLE`
+    Columns,
+}
+
+/// The tokens before the cursor and what the cursor is inside of
+enum Lexical {
+    Code(Vec<Token>),
+    /// In a string literal; the tokens are those before it opened
+    String(Vec<Token>),
+    /// In a quoted identifier; the tokens are those before it opened
+    Identifier(Vec<Token>),
+    Comment,
+    /// The text does not tokenize
+    Unknown,
+}
+
+/// Detects SQL context from tokens before cursor position
+pub(crate) fn detect_context(sql: &str, cursor_offset: usize) -> SqlContext {
+    let cursor_offset = position::floor_char_boundary(sql, cursor_offset);
+    let sql_before_cursor = &sql[..cursor_offset];
+
+    let lexical = lex(sql_before_cursor);
+    let (tokens, in_string) = match &lexical {
+        Lexical::Code(tokens) | Lexical::Identifier(tokens) => (tokens, false),
+        Lexical::String(tokens) => (tokens, true),
+        Lexical::Comment => return SqlContext::Comment,
+        Lexical::Unknown => return SqlContext::Default,
+    };
+    let mut tokens: Vec<&Token> = tokens
+        .iter()
+        .filter(|t| !matches!(t, Token::Whitespace(_)))
+        .collect();
+    if in_string {
+        return string_context(&tokens);
+    }
+    // A word the cursor touches is still being typed, and says nothing yet
+    // about the clause: `FR` may become `FROM` or `FRAME`
+    if matches!(lexical, Lexical::Code(_))
+        && sql_before_cursor.ends_with(|c: char| c.is_alphanumeric() || c == '_')
+        && matches!(tokens.last(), Some(Token::Word(_)))
+    {
+        tokens.pop();
+    }
+
+    let stack = frames(&tokens);
+    let nested = !matches!(stack.last(), Some(Frame::Query(_)));
+    // Parentheses take the clause of the nearest query around them
+    let mut clause = Clause::Other;
+    for frame in stack.iter().rev() {
+        match frame {
+            Frame::Query(query_clause) => {
+                clause = *query_clause;
+                break;
+            }
+            Frame::Columns => return SqlContext::ColumnDefinition,
+            Frame::Paren { .. } => {}
+        }
+    }
+
+    let last = tokens.last().copied();
+    match clause {
+        Clause::Select => SqlContext::SelectClause,
+        Clause::From => SqlContext::FromClause,
+        Clause::Filter => SqlContext::WhereClause,
+        Clause::OrderBy => SqlContext::OrderByClause,
+        // The remaining contexts complete a single name right after the keyword
+        _ if nested => SqlContext::Default,
+        Clause::Engine => {
+            let before_equals = is_keyword(last, Keyword::ENGINE)
+                && sql[cursor_offset..].trim_start().starts_with('=');
+            if matches!(last, Some(Token::Eq)) || before_equals {
+                SqlContext::Engine
+            } else {
+                SqlContext::Default
+            }
+        }
+        Clause::Format if is_keyword(last, Keyword::FORMAT) => SqlContext::Format,
+        Clause::Settings
+            if is_keyword(last, Keyword::SETTINGS) || matches!(last, Some(Token::Comma)) =>
+        {
+            SqlContext::Settings
+        }
+        _ => SqlContext::Default,
+    }
+}
+
+/// Tokenizes the text before the cursor. When that fails on an unterminated
+/// string or comment, the cursor is inside it: closing it shows which.
+fn lex(sql_before_cursor: &str) -> Lexical {
+    let dialect = ClickHouseDialect {};
+    let tokenize = |text: &str| Tokenizer::new(&dialect, text).tokenize().ok();
+
+    if let Some(tokens) = tokenize(sql_before_cursor) {
+        // A single-line comment runs until its newline
+        let in_comment = matches!(
+            tokens.last(),
+            Some(Token::Whitespace(Whitespace::SingleLineComment { comment, .. }))
+                if !comment.ends_with('\n')
+        );
+        return if in_comment {
+            Lexical::Comment
+        } else {
+            Lexical::Code(tokens)
+        };
+    }
+    if let Some(mut tokens) = tokenize(&format!("{sql_before_cursor}'")) {
+        if matches!(tokens.last(), Some(Token::SingleQuotedString(_))) {
+            tokens.pop();
+            return Lexical::String(tokens);
+        }
+    }
+    if let Some(mut tokens) = tokenize_in_identifier(sql_before_cursor) {
+        tokens.pop();
+        return Lexical::Identifier(tokens.into_iter().map(|t| t.token).collect());
+    }
+    if tokenize(&format!("{sql_before_cursor}*/")).is_some() {
+        return Lexical::Comment;
+    }
+    Lexical::Unknown
+}
+
+/// Where the quoted identifier the cursor is inside of starts, at its opening
+/// quote, and the quote; `None` outside quoted identifiers
+pub(crate) fn open_identifier(sql_before_cursor: &str) -> Option<(usize, char)> {
+    let tokens = tokenize_in_identifier(sql_before_cursor)?;
+    let last = tokens.last()?;
+    let Token::Word(word) = &last.token else {
+        return None;
+    };
+    let start = LineIndex::new(sql_before_cursor).offset(last.span.start);
+    Some((start, word.quote_style?))
+}
+
+/// Tokens of text that ends inside a quoted identifier, up to and including
+/// that identifier, which closing it with its quote shows
+fn tokenize_in_identifier(sql_before_cursor: &str) -> Option<Vec<TokenWithSpan>> {
+    let dialect = ClickHouseDialect {};
+    ['`', '"'].into_iter().find_map(|quote| {
+        if !sql_before_cursor.contains(quote) {
+            return None;
+        }
+        let tokens = Tokenizer::new(&dialect, &format!("{sql_before_cursor}{quote}"))
+            .tokenize_with_location()
+            .ok()?;
+        let closed = matches!(
+            tokens.last().map(|t| &t.token),
+            Some(Token::Word(word)) if word.quote_style == Some(quote)
+        );
+        closed.then_some(tokens)
+    })
+}
+
+/// The context of a string literal opened after `tokens`
+fn string_context(tokens: &[&Token]) -> SqlContext {
+    let stack = frames(tokens);
+    match stack.last() {
+        Some(Frame::Paren {
+            call: Some(function),
+            argument,
+        }) => SqlContext::StringArgument {
+            function: (*function).to_string(),
+            argument: *argument,
+        },
+        Some(Frame::Query(Clause::Settings)) => match tokens {
+            [.., Token::Word(setting), Token::Eq] => SqlContext::SettingValue {
+                setting: setting.value.clone(),
+            },
+            _ => SqlContext::StringLiteral,
+        },
+        _ => SqlContext::StringLiteral,
+    }
+}
+
+/// The frames still open after `tokens`, outermost first
+fn frames<'a>(tokens: &[&'a Token]) -> Vec<Frame<'a>> {
+    let mut stack = vec![Frame::Query(Clause::Other)];
+    let mut previous: Option<&'a Token> = None;
+
+    for &token in tokens {
+        match token {
+            Token::LParen => {
+                let columns = stack.last() == Some(&Frame::Query(Clause::CreateTable));
+                let call = match previous {
+                    Some(Token::Word(word)) => Some(word.value.as_str()),
+                    _ => None,
+                };
+                stack.push(if columns {
+                    Frame::Columns
+                } else {
+                    Frame::Paren { call, argument: 0 }
+                });
+            }
+            Token::Comma => {
+                if let Some(Frame::Paren { argument, .. }) = stack.last_mut() {
+                    *argument += 1;
+                }
+            }
+            Token::RParen if stack.len() > 1 => {
+                let closed = stack.pop();
+                if let (Some(Frame::Columns), Some(Frame::Query(clause))) =
+                    (closed, stack.last_mut())
+                {
+                    *clause = Clause::Other;
+                }
+            }
+            // `t.from` is a column, not a clause
+            Token::Word(word) if !matches!(previous, Some(Token::Period)) => {
+                match stack.last_mut() {
+                    Some(Frame::Query(clause)) => {
+                        if let Some(next) = clause_after(word.keyword, previous, *clause) {
+                            *clause = next;
+                        }
+                    }
+                    // A subquery: `IN (SELECT`, `FROM (WITH`
+                    Some(frame @ Frame::Paren { .. })
+                        if matches!(previous, Some(Token::LParen))
+                            && matches!(word.keyword, Keyword::SELECT | Keyword::WITH) =>
+                    {
+                        let clause = if word.keyword == Keyword::SELECT {
+                            Clause::Select
+                        } else {
+                            Clause::With
+                        };
+                        *frame = Frame::Query(clause);
+                    }
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+        previous = Some(token);
+    }
+    stack
+}
+
+/// The clause `keyword` starts when it follows `previous` in `current`, or
+/// `None` if it does not start one
+fn clause_after(keyword: Keyword, previous: Option<&Token>, current: Clause) -> Option<Clause> {
+    Some(match keyword {
+        Keyword::SELECT => Clause::Select,
+        Keyword::WITH => Clause::With,
+        Keyword::FROM | Keyword::JOIN => Clause::From,
+        Keyword::WHERE | Keyword::PREWHERE | Keyword::HAVING | Keyword::QUALIFY => Clause::Filter,
+        // Not `ON CLUSTER`
+        Keyword::ON if current == Clause::From => Clause::Filter,
+        Keyword::BY
+            if [
+                Keyword::GROUP,
+                Keyword::ORDER,
+                Keyword::PARTITION,
+                Keyword::SAMPLE,
+            ]
+            .into_iter()
+            .any(|k| is_keyword(previous, k)) =>
+        {
+            Clause::OrderBy
+        }
+        Keyword::SETTINGS => Clause::Settings,
+        Keyword::FORMAT => Clause::Format,
+        Keyword::ENGINE => Clause::Engine,
+        Keyword::CREATE => Clause::Create,
+        Keyword::TABLE if current == Clause::Create => Clause::CreateTable,
+        Keyword::LIMIT
+        | Keyword::OFFSET
+        | Keyword::UNION
+        | Keyword::EXCEPT
+        | Keyword::INTERSECT
+        | Keyword::INSERT
+        | Keyword::VALUES
+        | Keyword::PRIMARY => Clause::Other,
+        _ => return None,
+    })
+}
+
+fn is_keyword(token: Option<&Token>, keyword: Keyword) -> bool {
+    matches!(token, Some(Token::Word(w)) if w.keyword == keyword)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_context_engine() {
+        assert_eq!(
+            detect_context("CREATE TABLE t ENGINE = ", 24),
+            SqlContext::Engine
+        );
+        assert_eq!(
+            detect_context("CREATE TABLE t ENGINE =", 23),
+            SqlContext::Engine
+        );
+        assert_eq!(detect_context("ENGINE = M", 10), SqlContext::Engine);
+    }
+
+    #[test]
+    fn test_detect_context_format() {
+        assert_eq!(
+            detect_context("SELECT * FROM t FORMAT ", 23),
+            SqlContext::Format
+        );
+        assert_eq!(detect_context("SELECT * FORMAT ", 16), SqlContext::Format);
+    }
+
+    #[test]
+    fn test_detect_context_where() {
+        assert_eq!(
+            detect_context("SELECT * FROM t WHERE ", 22),
+            SqlContext::WhereClause
+        );
+        assert_eq!(
+            detect_context("SELECT * FROM t WHERE x = 1 AND ", 32),
+            SqlContext::WhereClause
+        );
+    }
+
+    #[test]
+    fn test_detect_context_having() {
+        assert_eq!(
+            detect_context("SELECT * FROM t GROUP BY x HAVING ", 34),
+            SqlContext::WhereClause
+        );
+    }
+
+    #[test]
+    fn test_detect_context_order_by() {
+        assert_eq!(
+            detect_context("SELECT * FROM t ORDER BY ", 25),
+            SqlContext::OrderByClause
+        );
+        assert_eq!(
+            detect_context("SELECT * FROM t ORDER BY x ", 27),
+            SqlContext::OrderByClause
+        );
+    }
+
+    #[test]
+    fn test_detect_context_group_by() {
+        assert_eq!(
+            detect_context("SELECT * FROM t GROUP BY ", 25),
+            SqlContext::OrderByClause
+        );
+    }
+
+    #[test]
+    fn test_detect_context_select() {
+        assert_eq!(detect_context("SELECT ", 7), SqlContext::SelectClause);
+        assert_eq!(detect_context("SELECT x, ", 10), SqlContext::SelectClause);
+    }
+
+    #[test]
+    fn test_detect_context_from() {
+        assert_eq!(detect_context("SELECT * FROM ", 14), SqlContext::FromClause);
+    }
+
+    #[test]
+    fn test_detect_context_settings() {
+        assert_eq!(
+            detect_context("SELECT * FROM t SETTINGS ", 25),
+            SqlContext::Settings
+        );
+    }
+
+    #[test]
+    fn test_detect_context_default() {
+        assert_eq!(detect_context("", 0), SqlContext::Default);
+        assert_eq!(detect_context("SEL", 3), SqlContext::Default);
+    }
+
+    #[test]
+    fn test_detect_context_column_definition() {
+        assert_eq!(
+            detect_context("CREATE TABLE t (id ", 19),
+            SqlContext::ColumnDefinition
+        );
+        assert_eq!(
+            detect_context("CREATE TABLE t (id UInt64, name ", 32),
+            SqlContext::ColumnDefinition
+        );
+    }
+
+    #[test]
+    fn test_detect_context_in_subqueries() {
+        let at_end = |sql: &str| detect_context(sql, sql.len());
+        assert_eq!(
+            at_end("SELECT * FROM t WHERE id IN (SELECT "),
+            SqlContext::SelectClause
+        );
+        assert_eq!(
+            at_end("SELECT * FROM t WHERE id IN (SELECT id FROM "),
+            SqlContext::FromClause
+        );
+        assert_eq!(
+            at_end("SELECT * FROM (SELECT a FROM b WHERE a > 1) AS s WHERE "),
+            SqlContext::WhereClause
+        );
+        assert_eq!(
+            at_end("SELECT * FROM (SELECT a FROM b WHERE a > 1) "),
+            SqlContext::FromClause
+        );
+        assert_eq!(
+            at_end("SELECT * FROM t WHERE x IN (SELECT y FROM u WHERE z IN (SELECT "),
+            SqlContext::SelectClause
+        );
+        assert_eq!(
+            at_end("SELECT * FROM t WHERE x IN (SELECT y FROM u) AND "),
+            SqlContext::WhereClause
+        );
+    }
+
+    #[test]
+    fn test_detect_context_in_ctes() {
+        let at_end = |sql: &str| detect_context(sql, sql.len());
+        assert_eq!(
+            at_end("WITH recent AS (SELECT * FROM "),
+            SqlContext::FromClause
+        );
+        assert_eq!(
+            at_end("WITH recent AS (SELECT * FROM events WHERE ts > now()) SELECT "),
+            SqlContext::SelectClause
+        );
+        assert_eq!(
+            at_end("WITH a AS (SELECT 1), b AS (SELECT x FROM a ORDER BY "),
+            SqlContext::OrderByClause
+        );
+    }
+
+    #[test]
+    fn test_detect_context_inside_parens() {
+        let at_end = |sql: &str| detect_context(sql, sql.len());
+        assert_eq!(at_end("SELECT count("), SqlContext::SelectClause);
+        assert_eq!(
+            at_end("SELECT * FROM t WHERE x IN (1, "),
+            SqlContext::WhereClause
+        );
+        assert_eq!(
+            at_end("SELECT extract(day FROM ts), "),
+            SqlContext::SelectClause
+        );
+        assert_eq!(
+            at_end("CREATE TABLE t (a Decimal(10, "),
+            SqlContext::ColumnDefinition
+        );
+        assert_eq!(
+            at_end("CREATE TABLE t (a UInt64) ENGINE = MergeTree() ORDER BY "),
+            SqlContext::OrderByClause
+        );
+        assert_eq!(
+            at_end("SELECT a FROM t JOIN u ON "),
+            SqlContext::WhereClause
+        );
+    }
+
+    #[test]
+    fn test_detect_context_ignores_quoted_keywords() {
+        let at_end = |sql: &str| detect_context(sql, sql.len());
+        assert_eq!(
+            at_end(r#"SELECT "FROM", `WHERE`, 'ORDER BY', t.from, "#),
+            SqlContext::SelectClause
+        );
+        assert_eq!(
+            at_end("SELECT * FROM t WHERE x = 'SELECT' AND "),
+            SqlContext::WhereClause
+        );
+        assert_eq!(at_end("SELECT 1 -- FROM\n, "), SqlContext::SelectClause);
+    }
+
+    #[test]
+    fn test_detect_context_in_quoted_identifiers() {
+        let at_end = |sql: &str| detect_context(sql, sql.len());
+        assert_eq!(
+            at_end("SELECT a FROM t WHERE `my co"),
+            SqlContext::WhereClause
+        );
+        assert_eq!(at_end("SELECT x, \"FR"), SqlContext::SelectClause);
+        assert_eq!(at_end("SELECT * FROM `db`.`ev"), SqlContext::FromClause);
+
+        assert_eq!(open_identifier("SELECT `my co"), Some((7, '`')));
+        assert_eq!(open_identifier("SELECT 'é', \"a``b"), Some((13, '"')));
+        assert_eq!(open_identifier("SELECT `a b` + `c``d"), Some((15, '`')));
+        assert_eq!(open_identifier("SELECT `ab`"), None);
+        assert_eq!(open_identifier("SELECT 'a `b"), None);
+        assert_eq!(open_identifier("SELECT 1 -- `b"), None);
+    }
+
+    #[test]
+    fn test_detect_context_ignores_word_being_typed() {
+        assert_eq!(
+            detect_context("SELECT * FROM t WH", 18),
+            SqlContext::FromClause
+        );
+        assert_eq!(
+            detect_context("SELECT * FROM t FORMAT JSO", 26),
+            SqlContext::Format
+        );
+        assert_eq!(
+            detect_context("SELECT * FROM t SETTINGS max_threads = 1, max_", 46),
+            SqlContext::Settings
+        );
+    }
+
+    #[test]
+    fn test_detect_context_in_comments() {
+        let at_end = |sql: &str| detect_context(sql, sql.len());
+        assert_eq!(at_end("SELECT 1 -- FROM t WH"), SqlContext::Comment);
+        assert_eq!(at_end("SELECT /* 日本 "), SqlContext::Comment);
+        assert_eq!(at_end("SELECT /* a */ "), SqlContext::SelectClause);
+        assert_eq!(at_end("SELECT 1 -- note\nFROM "), SqlContext::FromClause);
+    }
+
+    #[test]
+    fn test_detect_context_in_strings() {
+        let at_end = |sql: &str| detect_context(sql, sql.len());
+        let argument = |function: &str, argument| SqlContext::StringArgument {
+            function: function.to_string(),
+            argument,
+        };
+        assert_eq!(
+            at_end("SELECT toDateTime(x, 'Eu"),
+            argument("toDateTime", 1)
+        );
+        assert_eq!(
+            at_end("SELECT * FROM file('data/🦀.csv', '"),
+            argument("file", 1)
+        );
+        assert_eq!(
+            at_end("CREATE TABLE t (ts DateTime64(3, '"),
+            argument("DateTime64", 1)
+        );
+        assert_eq!(
+            at_end("SELECT * FROM t SETTINGS session_timezone = '"),
+            SqlContext::SettingValue {
+                setting: "session_timezone".to_string()
+            }
+        );
+        assert_eq!(
+            at_end("SELECT * FROM t WHERE name = 'FROM "),
+            SqlContext::StringLiteral
+        );
+        // Just after a closed string the cursor is back in code
+        assert_eq!(
+            at_end("SELECT * FROM t WHERE name = 'x' "),
+            SqlContext::WhereClause
+        );
+    }
+}
@@ -0,0 +1,621 @@
+//! A `Schema` built from a project's own DDL, without a live `ClickHouse`.
+//!
+//! Tables and views are read from the `CREATE TABLE`, `CREATE VIEW` and
+//! `CREATE MATERIALIZED VIEW` statements of any number of sources, such as
+//! migration files and SQL templates, taken in order. Names created without a
+//! database go in `default`. A view has the columns it declares, or else the
+//! ones its query selects, typed where they are plain columns of a table
+//! defined before it. A materialized view writing `TO` a table has that
+//! table's columns.
+//!
+//! Creating a name again is reported as a conflict unless the definition is
+//! the same, as when migrations repeat `CREATE TABLE IF NOT EXISTS`. The
+//! first definition is kept, as `ClickHouse` keeps it; only `CREATE OR
+//! REPLACE` replaces it.
+
+use crate::position::LineIndex;
+use crate::recovery;
+use crate::schema::{ColumnInfo, DatabaseInfo, Schema, TableInfo, TableKind};
+use crate::{ErrorCode, ValidationError};
+use serde::Serialize;
+use sqlparser::ast::{
+    ColumnOption, CreateTableOptions, DataType, Expr, Ident, ObjectName, Query, SelectItem,
+    SelectItemQualifiedWildcardKind, SetExpr, Spanned, SqlOption, Statement, TableFactor,
+    ViewColumnDef,
+};
+use sqlparser::tokenizer::{Span, Token, TokenWithSpan};
+use std::collections::HashMap;
+
+/// Database of names created without one
+const DEFAULT_DATABASE: &str = "default";
+
+/// A schema built from DDL, and what kept statements out of it
+#[derive(Serialize, Debug, Default)]
+pub struct DdlSchema {
+    pub schema: Schema,
+    /// Syntax errors and conflicting definitions, by source and then in
+    /// source order
+    pub errors: Vec<SourceError>,
+}
+
+/// An error in one of the sources a schema is built from
+#[derive(Serialize, Debug)]
+pub struct SourceError {
+    /// Index of the source the error is in
+    pub source: usize,
+    #[serde(flatten)]
+    pub error: ValidationError,
+}
+
+/// Builds a schema from the `CREATE` statements of `sources`
+pub(crate) fn build_schema<S: AsRef<str>>(sources: &[S]) -> DdlSchema {
+    extend_schema(Schema::default(), DEFAULT_DATABASE, sources)
+}
+
+/// Adds the tables and views `sources` create to `schema`, putting names
+/// created without a database in `database`. Tables already in `schema`
+/// conflict like ones created before.
+pub(crate) fn extend_schema<S: AsRef<str>>(
+    schema: Schema,
+    database: &str,
+    sources: &[S],
+) -> DdlSchema {
+    let mut builder = Builder {
+        result: DdlSchema {
+            schema,
+            errors: Vec::new(),
+        },
+        origins: HashMap::new(),
+    };
+    for (source, sql) in sources.iter().enumerate() {
+        let sql = sql.as_ref();
+        let parsed = recovery::parse_with_recovery(sql);
+        let lines = LineIndex::new(sql);
+        let text = Text {
+            sql,
+            lines: &lines,
+            tokens: &parsed.tokens,
+        };
+        let mut errors: Vec<ValidationError> = parsed.errors;
+        for statement in &parsed.statements {
+            let Some(created) =
+                Created::from_statement(statement, &text, &builder.result.schema, database)
+            else {
+                continue;
+            };
+            let origin = Origin {
+                source,
+                line: lines.location(lines.range(created.span).0).line,
+            };
+            if let Some(conflict) = builder.define(created, origin) {
+                errors.push(ValidationError::at_span(
+                    ErrorCode::ConflictingDefinition,
+                    conflict.message,
+                    conflict.span,
+                    &lines,
+                ));
+            }
+        }
+        errors.sort_by_key(|e| e.start_offset);
+        builder.result.errors.extend(
+            errors
+                .into_iter()
+                .map(|error| SourceError { source, error }),
+        );
+    }
+    builder.result
+}
+
+/// Where a name was first defined
+#[derive(Debug, Clone, Copy)]
+struct Origin {
+    source: usize,
+    line: u64,
+}
+
+/// A table or view a statement creates
+struct Created {
+    database: String,
+    table: TableInfo,
+    /// The name in the statement
+    span: Span,
+    or_replace: bool,
+}
+
+struct Conflict {
+    span: Span,
+    message: String,
+}
+
+struct Builder {
+    result: DdlSchema,
+    /// Where each `(database, name)` was defined
+    origins: HashMap<(String, String), Origin>,
+}
+
+impl Builder {
+    /// Adds `created` to the schema, or returns the conflict with the
+    /// definition already there
+    fn define(&mut self, created: Created, origin: Origin) -> Option<Conflict> {
+        let key = (created.database.clone(), created.table.name.clone());
+        let database = self.database(&created.database);
+        let existing = database
+            .tables
+            .iter_mut()
+            .find(|t| t.name == created.table.name);
+
+        match existing {
+            None => database.tables.push(created.table),
+            Some(existing) if created.or_replace => *existing = created.table,
+            Some(existing) if *existing == created.table => return None,
+            Some(_) => {
+                let first = self.origins.get(&key).copied().unwrap_or(origin);
+                let place = if first.source == origin.source {
+                    format!("line {}", first.line)
+                } else {
+                    format!("source {}, line {}", first.source, first.line)
+                };
+                return Some(Conflict {
+                    span: created.span,
+                    message: format!(
+                        "`{}.{}` is already created with a different definition ({place}).",
+                        key.0, key.1
+                    ),
+                });
+            }
+        }
+        self.origins.insert(key, origin);
+        None
+    }
+
+    fn database(&mut self, name: &str) -> &mut DatabaseInfo {
+        let databases = &mut self.result.schema.databases;
+        let position = databases.iter().position(|d| d.name == name);
+        let position = position.unwrap_or_else(|| {
+            databases.push(DatabaseInfo {
+                name: name.to_string(),
+                tables: Vec::new(),
+            });
+            databases.len() - 1
+        });
+        &mut databases[position]
+    }
+}
+
+impl Created {
+    /// The table or view `statement` in `text` creates, with columns of
+    /// views looked up in `schema` and names without a database in
+    /// `default_database`
+    fn from_statement(
+        statement: &Statement,
+        text: &Text,
+        schema: &Schema,
+        default_database: &str,
+    ) -> Option<Self> {
+        match statement {
+            Statement::CreateTable(create) => {
+                let (database, name) = split_name(&create.name)?;
+                let database = database.unwrap_or_else(|| default_database.to_string());
+                let mut columns: Vec<ColumnInfo> = create
+                    .columns
+                    .iter()
+                    .map(|column| ColumnInfo {
+                        name: column.name.value.clone(),
+                        column_type: text.column_type(&column.name, &column.data_type),
+                        comment: column.options.iter().find_map(|o| match &o.option {
+                            ColumnOption::Comment(comment) => Some(comment.clone()),
+                            _ => None,
+                        }),
+                    })
+                    .collect();
+                if let (true, Some(query)) = (columns.is_empty(), &create.query) {
+                    columns = query_columns(query, schema);
+                }
+                Some(Self {
+                    database,
+                    table: TableInfo {
+                        name,
+                        kind: TableKind::Table,
+                        columns,
+                        comment: None,
+                        engine: engine(&create.table_options),
+                        order_by: create
+                            .order_by
+                            .iter()
+                            .flatten()
+                            .map(ToString::to_string)
+                            .collect(),
+                        partition_by: create.partition_by.as_ref().map(ToString::to_string),
+                    },
+                    span: create.name.span(),
+                    or_replace: create.or_replace,
+                })
+            }
+            Statement::CreateView {
+                name,
+                columns,
+                query,
+                materialized,
+                or_replace,
+                options,
+                comment,
+                to,
+                ..
+            } => {
+                let (database, view) = split_name(name)?;
+                let database = database.unwrap_or_else(|| default_database.to_string());
+                let target = to.as_ref().and_then(split_name).and_then(|(db, table)| {
+                    schema.table(Some(db.as_deref().unwrap_or(default_database)), &table)
+                });
+                let columns = view_columns(columns, query, target, text, schema);
+                Some(Self {
+                    database,
+                    table: TableInfo {
+                        name: view,
+                        kind: if *materialized {
+                            TableKind::MaterializedView
+                        } else {
+                            TableKind::View
+                        },
+                        columns,
+                        comment: comment.clone(),
+                        engine: engine(options),
+                        order_by: Vec::new(),
+                        partition_by: None,
+                    },
+                    span: name.span(),
+                    or_replace: *or_replace,
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+/// The columns of a view: those of `target`, the table a materialized view
+/// writes `TO`, or else the declared ones, typed from the query where they are not
+/// declared with a type, or else the ones its query selects
+fn view_columns(
+    columns: &[ViewColumnDef],
+    query: &Query,
+    target: Option<&TableInfo>,
+    text: &Text,
+    schema: &Schema,
+) -> Vec<ColumnInfo> {
+    let selected = query_columns(query, schema);
+    match target {
+        Some(target) => target.columns.clone(),
+        None if columns.is_empty() => selected,
+        None => columns
+            .iter()
+            .map(|column| {
+                let name = column.name.value.clone();
+                let column_type = column.data_type.as_ref().map_or_else(
+                    || {
+                        selected
+                            .iter()
+                            .find(|c| c.name == name)
+                            .map(|c| c.column_type.clone())
+                            .unwrap_or_default()
+                    },
+                    |data_type| text.column_type(&column.name, data_type),
+                );
+                ColumnInfo {
+                    name,
+                    column_type,
+                    comment: None,
+                }
+            })
+            .collect(),
+    }
+}
+
+/// A source, for reading types back as they are written:
+/// sqlparser normalizes `String` to `STRING` and does not know most
+/// `ClickHouse` types
+struct Text<'a> {
+    sql: &'a str,
+    lines: &'a LineIndex<'a>,
+    tokens: &'a [TokenWithSpan],
+}
+
+impl Text<'_> {
+    /// The type written after the column `name`, up to the end of the
+    /// definition or its first option
+    fn column_type(&self, name: &Ident, data_type: &DataType) -> String {
+        let start = self
+            .tokens
+            .partition_point(|t| t.span.start < name.span.start);
+        let mut end = None;
+        let mut nesting = 0usize;
+        for token in self.tokens.iter().skip(start + 1) {
+            match &token.token {
+                Token::LParen => nesting += 1,
+                Token::RParen if nesting > 0 => nesting -= 1,
+                Token::RParen | Token::Comma if nesting == 0 => break,
+                Token::Word(word)
+                    if nesting == 0
+                        && word.quote_style.is_none()
+                        && COLUMN_OPTIONS
+                            .iter()
+                            .any(|option| word.value.eq_ignore_ascii_case(option)) =>
+                {
+                    break;
+                }
+                _ => {}
+            }
+            end = Some(token.span.end);
+        }
+        let written = match (self.tokens.get(start + 1), end) {
+            (Some(first), Some(end)) if name.span != Span::empty() => {
+                self.text(Span::new(first.span.start, end))
+            }
+            _ => None,
+        };
+        written.map_or_else(|| data_type.to_string(), ToString::to_string)
+    }
+
+    fn text(&self, span: Span) -> Option<&str> {
+        if span == Span::empty() {
+            return None;
+        }
+        let (start, end) = self.lines.range(span);
+        self.sql.get(start..end)
+    }
+}
+
+/// Words that end the type of a column definition
+const COLUMN_OPTIONS: &[&str] = &[
+    "DEFAULT",
+    "MATERIALIZED",
+    "ALIAS",
+    "EPHEMERAL",
+    "COMMENT",
+    "CODEC",
+    "TTL",
+    "NOT",
+    "NULL",
+    "PRIMARY",
+    "SETTINGS",
+];
+
+/// The database and name of `db.name` or `name`
+fn split_name(name: &ObjectName) -> Option<(Option<String>, String)> {
+    let parts: Vec<&str> = name
+        .0
+        .iter()
+        .map(|part| part.as_ident().map(|ident| ident.value.as_str()))
+        .collect::<Option<_>>()?;
+    match parts.as_slice() {
+        [table] => Some((None, (*table).to_string())),
+        [database, table] => Some((Some((*database).to_string()), (*table).to_string())),
+        _ => None,
+    }
+}
+
+/// The `ENGINE = Name(args)` of a statement's options, as written
+fn engine(options: &CreateTableOptions) -> Option<String> {
+    let options = match options {
+        CreateTableOptions::Plain(options)
+        | CreateTableOptions::With(options)
+        | CreateTableOptions::Options(options)
+        | CreateTableOptions::TableProperties(options) => options,
+        CreateTableOptions::None => return None,
+    };
+    options.iter().find_map(|option| match option {
+        SqlOption::NamedParenthesizedList(list)
+            if list.key.value.eq_ignore_ascii_case("ENGINE") =>
+        {
+            let name = list.name.as_ref()?.value.clone();
+            if list.values.is_empty() {
+                Some(name)
+            } else {
+                let args: Vec<String> = list.values.iter().map(ToString::to_string).collect();
+                Some(format!("{name}({})", args.join(", ")))
+            }
+        }
+        _ => None,
+    })
+}
+
+/// The columns a query selects, typed where they are plain columns of a
+/// table in `schema`. Only the first query of a set operation counts, as in
+/// `ClickHouse`.
+fn query_columns(query: &Query, schema: &Schema) -> Vec<ColumnInfo> {
+    let mut body = query.body.as_ref();
+    let select = loop {
+        match body {
+            SetExpr::Select(select) => break select,
+            SetExpr::Query(query) => body = query.body.as_ref(),
+            SetExpr::SetOperation { left, .. } => body = left.as_ref(),
+            _ => return Vec::new(),
+        }
+    };
+
+    // Each table read from, with the name that qualifies its columns
+    let tables: Vec<(&str, &TableInfo)> = select
+        .from
+        .iter()
+        .flat_map(|from| {
+            std::iter::once(&from.relation).chain(from.joins.iter().map(|j| &j.relation))
+        })
+        .filter_map(|relation| {
+            let TableFactor::Table { name, alias, .. } = relation else {
+                return None;
+            };
+            let (database, table_name) = split_name(name)?;
+            let table = schema.table(database.as_deref(), &table_name)?;
+            let qualifier = alias
+                .as_ref()
+                .map_or(table.name.as_str(), |a| a.name.value.as_str());
+            Some((qualifier, table))
+        })
+        .collect();
+    let lookup = |qualifier: Option<&str>, name: &str| -> String {
+        tables
+            .iter()
+            .filter(|(q, _)| qualifier.is_none_or(|qualifier| qualifier == *q))
+            .find_map(|(_, table)| table.columns.iter().find(|c| c.name == name))
+            .map(|c| c.column_type.clone())
+            .unwrap_or_default()
+    };
+    let typed = |name: String, expr: &Expr| -> ColumnInfo {
+        let column_type = match expr {
+            Expr::Identifier(ident) => lookup(None, &ident.value),
+            Expr::CompoundIdentifier(parts) => match parts.as_slice() {
+                [.., qualifier, column] => lookup(Some(&qualifier.value), &column.value),
+                _ => String::new(),
+            },
+            _ => String::new(),
+        };
+        ColumnInfo {
+            name,
+            column_type,
+            comment: None,
+        }
+    };
+
+    let mut columns = Vec::new();
+    for item in &select.projection {
+        match item {
+            SelectItem::UnnamedExpr(expr) => {
+                let name = match expr {
+                    Expr::Identifier(ident) => ident.value.clone(),
+                    Expr::CompoundIdentifier(parts) => {
+                        parts.last().map(|p| p.value.clone()).unwrap_or_default()
+                    }
+                    _ => expr.to_string(),
+                };
+                columns.push(typed(name, expr));
+            }
+            SelectItem::ExprWithAlias { expr, alias } => {
+                columns.push(typed(alias.value.clone(), expr));
+            }
+            SelectItem::Wildcard(_) => {
+                columns.extend(tables.iter().flat_map(|(_, t)| t.columns.iter().cloned()));
+            }
+            SelectItem::QualifiedWildcard(SelectItemQualifiedWildcardKind::ObjectName(name), _) => {
+                let qualifier = name.0.last().and_then(|p| p.as_ident());
+                columns.extend(
+                    tables
+                        .iter()
+                        .filter(|(q, _)| qualifier.is_some_and(|i| i.value == *q))
+                        .flat_map(|(_, t)| t.columns.iter().cloned()),
+                );
+            }
+            SelectItem::QualifiedWildcard(SelectItemQualifiedWildcardKind::Expr(_), _) => {}
+        }
+    }
+    columns
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table<'a>(built: &'a DdlSchema, database: &str, name: &str) -> &'a TableInfo {
+        built.schema.table(Some(database), name).expect("defined")
+    }
+
+    #[test]
+    fn test_tables_with_their_keys() {
+        let built = build_schema(&["CREATE TABLE IF NOT EXISTS local.events (
+                id UInt64 COMMENT 'Event id',
+                ts DateTime64(3),
+                tags Array(LowCardinality(String))
+            ) ENGINE = ReplacingMergeTree(ts)
+            PARTITION BY toYYYYMM(ts)
+            ORDER BY (id, ts)
+            SETTINGS index_granularity = 8192;
+            CREATE TABLE users (id UInt64) ENGINE = MergeTree ORDER BY tuple()"]);
+        assert!(built.errors.is_empty(), "{:?}", built.errors);
+
+        let events = table(&built, "local", "events");
+        let columns: Vec<(&str, &str)> = events
+            .columns
+            .iter()
+            .map(|c| (c.name.as_str(), c.column_type.as_str()))
+            .collect();
+        assert_eq!(
+            columns,
+            vec![
+                ("id", "UInt64"),
+                ("ts", "DateTime64(3)"),
+                ("tags", "Array(LowCardinality(String))")
+            ]
+        );
+        assert_eq!(events.columns[0].comment.as_deref(), Some("Event id"));
+        assert_eq!(events.engine.as_deref(), Some("ReplacingMergeTree(ts)"));
+        assert_eq!(events.order_by, vec!["id", "ts"]);
+        assert_eq!(events.partition_by.as_deref(), Some("toYYYYMM(ts)"));
+
+        let users = table(&built, DEFAULT_DATABASE, "users");
+        assert_eq!(users.engine.as_deref(), Some("MergeTree"));
+        assert_eq!(users.order_by, vec!["tuple()"]);
+    }
+
+    #[test]
+    fn test_views_take_columns_from_their_sources() {
+        let built = build_schema(&[
+            "CREATE TABLE events (id UInt64, ts DateTime) ENGINE = MergeTree ORDER BY id",
+            "CREATE TABLE daily (day Date, n UInt64) ENGINE = SummingMergeTree ORDER BY day;
+             CREATE MATERIALIZED VIEW daily_mv TO daily AS
+                SELECT toDate(ts) AS day, count() AS n FROM events GROUP BY day;
+             CREATE VIEW recent AS SELECT e.id, ts AS at, id + 1 FROM events AS e;
+             CREATE VIEW everything AS SELECT * FROM events",
+        ]);
+        assert!(built.errors.is_empty(), "{:?}", built.errors);
+
+        let mv = table(&built, DEFAULT_DATABASE, "daily_mv");
+        assert_eq!(mv.kind, TableKind::MaterializedView);
+        assert_eq!(mv.columns, table(&built, DEFAULT_DATABASE, "daily").columns);
+
+        let recent = table(&built, DEFAULT_DATABASE, "recent");
+        assert_eq!(recent.kind, TableKind::View);
+        let columns: Vec<(&str, &str)> = recent
+            .columns
+            .iter()
+            .map(|c| (c.name.as_str(), c.column_type.as_str()))
+            .collect();
+        assert_eq!(
+            columns,
+            vec![("id", "UInt64"), ("at", "DateTime"), ("id + 1", "")]
+        );
+        assert_eq!(
+            table(&built, DEFAULT_DATABASE, "everything").columns.len(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_conflicting_definitions_are_reported() {
+        let built = build_schema(&[
+            "CREATE TABLE events (id UInt64) ENGINE = MergeTree ORDER BY id",
+            "CREATE TABLE IF NOT EXISTS events (id UInt64) ENGINE = MergeTree ORDER BY id;\n\
+             CREATE TABLE events (id String) ENGINE = MergeTree ORDER BY id",
+            "CREATE OR REPLACE TABLE events (id UUID) ENGINE = MergeTree ORDER BY id",
+            "CREATE TABLE broken (",
+        ]);
+
+        let codes: Vec<(usize, &str)> = built
+            .errors
+            .iter()
+            .map(|e| (e.source, e.error.code.as_str()))
+            .collect();
+        assert_eq!(
+            codes,
+            vec![
+                (1, "semantic/conflicting-definition"),
+                (3, "syntax/unclosed-paren")
+            ]
+        );
+        let conflict = &built.errors[0].error;
+        assert!(conflict.message.contains("`default.events`"));
+        assert!(conflict.message.contains("source 0, line 1"));
+        assert_eq!(conflict.line, Some(2));
+
+        // The first definition stands until one replaces it
+        let events = table(&built, DEFAULT_DATABASE, "events");
+        assert_eq!(events.columns[0].column_type, "UUID");
+    }
+}
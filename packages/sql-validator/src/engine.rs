@@ -0,0 +1,523 @@
+//! `ClickHouse` data loaded per version.
+//!
+//! Projects in one workspace can pin different `ClickHouse` versions, and a
+//! project can switch versions while the editor is open. An `Engine` holds
+//! any number of versions side by side under keys the caller chooses, and
+//! loading a key again replaces its data. Every call names the version it
+//! runs against; one that is not loaded gets no completions and syntax-only
+//! validation, as before any data is loaded.
+//!
+//! The tables and columns of the project are registered once for all
+//! versions, since they do not depend on the server version, either as a
+//! schema or as the infrastructure map Moose keeps of the project.
+
+use crate::clickhouse_index::ClickHouseIndex;
+use crate::infra_map;
+use crate::position::LineIndex;
+use crate::{
+    build_combinator_documentation, build_completion_cache, build_completion_documentation,
+    build_schema_documentation, complete, validate_sql_fragment_with, validate_sql_with,
+    validate_template_with, ClickHouseData, CompletionCache, CompletionItemKind, CompletionList,
+    InitResult, OffsetEncoding, Schema, ValidationError,
+};
+use std::collections::HashMap;
+use wasm_bindgen::prelude::*;
+
+/// The data of one version and the completion items built from it
+#[derive(Debug)]
+struct LoadedVersion {
+    index: ClickHouseIndex,
+    cache: CompletionCache,
+}
+
+/// Completion and validation against any number of loaded `ClickHouse`
+/// versions
+#[wasm_bindgen]
+#[derive(Debug, Default)]
+pub struct Engine {
+    versions: HashMap<String, LoadedVersion>,
+    schema: Schema,
+}
+
+#[wasm_bindgen]
+impl Engine {
+    #[wasm_bindgen(constructor)]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads `ClickHouse` data, given as JSON, under `version`, replacing any
+    /// data loaded under it before. Data that does not parse leaves the
+    /// loaded data as it was. Returns a JSON `InitResult`.
+    pub fn load(&mut self, version: &str, json: &str) -> String {
+        let result = serde_json::from_str::<ClickHouseData>(json)
+            .map(|data| {
+                let index = ClickHouseIndex::new(data);
+                let cache = build_completion_cache(&index);
+                self.versions
+                    .insert(version.to_string(), LoadedVersion { index, cache });
+            })
+            .map_err(|e| format!("Failed to parse ClickHouse data: {e}"));
+        init_result(result)
+    }
+
+    /// Registers the databases, tables and columns of the project, given as
+    /// a JSON `Schema`, replacing those registered before. A schema that does
+    /// not parse leaves the registered one as it was. Returns a JSON
+    /// `InitResult`.
+    pub fn register_schema(&mut self, json: &str) -> String {
+        let result = serde_json::from_str::<Schema>(json)
+            .map(|schema| self.schema = schema)
+            .map_err(|e| format!("Failed to parse schema: {e}"));
+        init_result(result)
+    }
+
+    /// Registers the tables and views of a Moose infrastructure map, given
+    /// as JSON, replacing those registered before. Names without a database
+    /// go in `database`. A map that does not parse leaves the registered
+    /// schema as it was. Returns a JSON `InitResult`.
+    pub fn register_infrastructure_map(&mut self, json: &str, database: &str) -> String {
+        let result = infra_map::schema_from_infrastructure_map(json, database)
+            .map(|schema| self.schema = schema)
+            .map_err(|e| format!("Failed to parse infrastructure map: {e}"));
+        init_result(result)
+    }
+
+    /// Drops the data loaded under `version`, returning whether there was any
+    pub fn unload(&mut self, version: &str) -> bool {
+        self.versions.remove(version).is_some()
+    }
+
+    /// The loaded versions as a sorted JSON array of keys
+    #[must_use]
+    pub fn versions(&self) -> String {
+        let mut versions: Vec<&String> = self.versions.keys().collect();
+        versions.sort();
+        serde_json::to_string(&versions).unwrap_or_else(|_| "[]".to_string())
+    }
+
+    /// Get completions for SQL at cursor position, given in `encoding` units
+    /// (`byte`, `char` or `utf16`).
+    /// Returns a JSON `CompletionList` of at most `limit` items matching the
+    /// word at the cursor, with its replacement range in `encoding` units.
+    /// The list is empty when `version` is not loaded, and also carries a
+    /// `usage/invalid-argument` error when `encoding` is unknown.
+    #[must_use]
+    pub fn get_completions(
+        &self,
+        version: &str,
+        sql: &str,
+        cursor_offset: usize,
+        encoding: &str,
+        limit: usize,
+    ) -> String {
+        let (loaded, encoding) = match (
+            self.versions.get(version),
+            OffsetEncoding::from_name(encoding),
+        ) {
+            (Some(loaded), Some(encoding)) => (loaded, encoding),
+            (_, parsed) => {
+                let list = CompletionList {
+                    error: parsed.is_none().then(|| {
+                        ValidationError::invalid_argument(format!(
+                            "Unknown offset encoding `{encoding}`"
+                        ))
+                    }),
+                    ..CompletionList::default()
+                };
+                return serde_json::to_string(&list).unwrap_or_else(|_| "{}".to_string());
+            }
+        };
+
+        let cursor_offset = LineIndex::new(sql).byte_offset(cursor_offset, encoding);
+        let list = complete(
+            &loaded.cache,
+            Some(&loaded.index),
+            &self.schema,
+            sql,
+            cursor_offset,
+            limit,
+        )
+        .encoded(sql, encoding);
+
+        serde_json::to_string(&list).unwrap_or_else(|_| "{}".to_string())
+    }
+
+    /// Documentation for a completion item, looked up by the `label` and
+    /// `kind` it was returned with when an editor resolves it. Returns a JSON
+    /// `{ kind, value }` object, or `null` when the item has none.
+    #[must_use]
+    pub fn resolve_completion(&self, version: &str, label: &str, kind: &str) -> String {
+        let documentation = CompletionItemKind::from_name(kind).and_then(|kind| match kind {
+            CompletionItemKind::Table | CompletionItemKind::Column => {
+                build_schema_documentation(&self.schema, label, kind)
+            }
+            _ => self
+                .index(version)
+                .and_then(|index| build_completion_documentation(index, label, kind)),
+        });
+        serde_json::to_string(&documentation).unwrap_or_else(|_| "null".to_string())
+    }
+
+    /// Documentation for an aggregate combinator form such as `countIf` or
+    /// `avgMergeOrNull`, as a JSON `{ kind, value }` object, or `null` when
+    /// the name is not one
+    #[must_use]
+    pub fn get_combinator_documentation(&self, version: &str, name: &str) -> String {
+        let documentation = self.index(version).and_then(|index| {
+            let form = index.combinator_form(name)?;
+            form.base
+                .is_aggregate
+                .then(|| build_combinator_documentation(name, &form))
+        });
+        serde_json::to_string(&documentation).unwrap_or_else(|_| "null".to_string())
+    }
+
+    /// `validate_sql`, also checking names against the data of `version`
+    /// and, once a schema is registered, tables and columns against it
+    #[must_use]
+    pub fn validate_sql(&self, version: &str, sql: &str, encoding: &str) -> String {
+        validate_sql_with(sql, encoding, self.index(version), &self.schema)
+    }
+
+    /// `validate_sql_fragment`, also checking names against the data of
+    /// `version`
+    #[must_use]
+    pub fn validate_sql_fragment(
+        &self,
+        version: &str,
+        sql: &str,
+        mode: &str,
+        encoding: &str,
+    ) -> String {
+        validate_sql_fragment_with(sql, mode, encoding, self.index(version), &self.schema)
+    }
+
+    /// `validate_template`, also checking names against the data of `version`
+    /// and the registered schema
+    #[must_use]
+    pub fn validate_template(
+        &self,
+        version: &str,
+        sql: &str,
+        holes: &str,
+        mode: &str,
+        encoding: &str,
+    ) -> String {
+        validate_template_with(
+            sql,
+            holes,
+            mode,
+            encoding,
+            self.index(version),
+            &self.schema,
+        )
+    }
+}
+
+impl Engine {
+    fn index(&self, version: &str) -> Option<&ClickHouseIndex> {
+        self.versions.get(version).map(|loaded| &loaded.index)
+    }
+}
+
+/// The JSON `InitResult` of loading or registering data
+fn init_result(result: Result<(), String>) -> String {
+    let result = match result {
+        Ok(()) => InitResult {
+            success: true,
+            error: None,
+        },
+        Err(error) => InitResult {
+            success: false,
+            error: Some(error),
+        },
+    };
+
+    serde_json::to_string(&result).unwrap_or_else(|_| {
+        r#"{"success":false,"error":"Internal serialization error"}"#.to_string()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+
+    fn data(version: &str, functions: &[&str]) -> String {
+        let functions: Vec<Value> = functions
+            .iter()
+            .map(|name| {
+                serde_json::json!({
+                    "name": name,
+                    "isAggregate": false,
+                    "caseInsensitive": false,
+                    "aliasTo": null,
+                    "syntax": format!("{name}(x)"),
+                    "description": format!("Only in {version}."),
+                    "arguments": "",
+                    "returnedValue": "",
+                    "examples": "",
+                    "categories": "",
+                })
+            })
+            .collect();
+        serde_json::json!({
+            "version": version,
+            "extractedAt": "2025-01-01T00:00:00Z",
+            "functions": functions,
+            "keywords": ["SELECT"],
+            "dataTypes": [],
+            "tableEngines": [],
+            "formats": [],
+            "tableFunctions": [],
+            "settings": [],
+            "mergeTreeSettings": [],
+        })
+        .to_string()
+    }
+
+    fn labels(engine: &Engine, version: &str, sql: &str) -> Vec<String> {
+        let list: Value =
+            serde_json::from_str(&engine.get_completions(version, sql, sql.len(), "byte", 100))
+                .expect("valid JSON");
+        list["items"]
+            .as_array()
+            .expect("items")
+            .iter()
+            .map(|item| item["label"].as_str().expect("label").to_string())
+            .collect()
+    }
+
+    fn unknown_functions(engine: &Engine, version: &str, sql: &str) -> usize {
+        let result: Value =
+            serde_json::from_str(&engine.validate_sql(version, sql, "byte")).expect("valid JSON");
+        result["errors"]
+            .as_array()
+            .expect("errors")
+            .iter()
+            .filter(|e| e["code"] == "semantic/unknown-function")
+            .count()
+    }
+
+    #[test]
+    fn test_versions_are_loaded_side_by_side() {
+        let mut engine = Engine::new();
+        let loaded: Value =
+            serde_json::from_str(&engine.load("25.6", &data("25.6", &["oldFunction"])))
+                .expect("valid JSON");
+        assert_eq!(loaded["success"], true);
+        engine.load("25.8", &data("25.8", &["newFunction"]));
+        assert_eq!(engine.versions(), r#"["25.6","25.8"]"#);
+
+        assert_eq!(labels(&engine, "25.6", "SELECT o"), vec!["oldFunction"]);
+        assert_eq!(labels(&engine, "25.8", "SELECT n"), vec!["newFunction"]);
+        assert_eq!(
+            unknown_functions(&engine, "25.6", "SELECT newFunction(1)"),
+            1
+        );
+        assert_eq!(
+            unknown_functions(&engine, "25.8", "SELECT newFunction(1)"),
+            0
+        );
+
+        let doc: Value =
+            serde_json::from_str(&engine.resolve_completion("25.8", "newFunction", "function"))
+                .expect("valid JSON");
+        assert!(doc["value"]
+            .as_str()
+            .expect("documented")
+            .contains("Only in 25.8."));
+    }
+
+    #[test]
+    fn test_loading_again_replaces_data() {
+        let mut engine = Engine::new();
+        engine.load("local", &data("25.6", &["oldFunction"]));
+        engine.load("local", &data("25.8", &["newFunction"]));
+        assert_eq!(labels(&engine, "local", "SELECT "), vec!["newFunction"]);
+
+        // Data that does not parse keeps what was loaded
+        let failed: Value = serde_json::from_str(&engine.load("local", "{")).expect("valid JSON");
+        assert_eq!(failed["success"], false);
+        assert_eq!(labels(&engine, "local", "SELECT "), vec!["newFunction"]);
+
+        assert!(engine.unload("local"));
+        assert!(!engine.unload("local"));
+        assert!(labels(&engine, "local", "SELECT ").is_empty());
+        let list: Value =
+            serde_json::from_str(&engine.get_completions("local", "SELECT ", 7, "byte", 100))
+                .expect("valid JSON");
+        assert!(list.get("error").is_none());
+        let list: Value =
+            serde_json::from_str(&engine.get_completions("local", "SELECT ", 7, "utf8", 100))
+                .expect("valid JSON");
+        assert_eq!(list["error"]["code"], "usage/invalid-argument");
+        // Without data, validation is syntax-only
+        assert_eq!(
+            unknown_functions(&engine, "local", "SELECT newFunction(1)"),
+            0
+        );
+    }
+
+    fn schema() -> String {
+        serde_json::json!({
+            "databases": [
+                {
+                    "name": "analytics",
+                    "tables": [
+                        {
+                            "name": "events",
+                            "comment": "Page views and clicks",
+                            "columns": [
+                                {"name": "user_id", "type": "UInt64", "comment": "Who acted"},
+                                {"name": "ts", "type": "DateTime"},
+                            ],
+                        },
+                        {
+                            "name": "users",
+                            "columns": [{"name": "name", "type": "String"}],
+                        },
+                    ],
+                },
+                {"name": "staging", "tables": [{"name": "imports", "columns": []}]},
+            ],
+        })
+        .to_string()
+    }
+
+    #[test]
+    fn test_registered_tables_and_columns_are_completed() {
+        let mut engine = Engine::new();
+        engine.load("25.8", &data("25.8", &["toDate"]));
+        let registered: Value =
+            serde_json::from_str(&engine.register_schema(&schema())).expect("valid JSON");
+        assert_eq!(registered["success"], true);
+        let labels = |sql: &str| labels(&engine, "25.8", sql);
+
+        let tables = labels("SELECT * FROM ");
+        assert!(["events", "users", "imports"]
+            .iter()
+            .all(|t| tables.iter().any(|l| l == t)));
+        assert_eq!(labels("SELECT * FROM staging."), vec!["imports"]);
+
+        let columns = labels("SELECT * FROM events WHERE ");
+        assert!(columns.contains(&"user_id".to_string()));
+        assert!(columns.contains(&"toDate".to_string()));
+        assert!(!columns.contains(&"name".to_string()));
+        assert_eq!(labels("SELECT * FROM events ORDER BY us")[0], "user_id");
+        assert!(labels("SELECT u.").is_empty());
+        assert_eq!(labels("SELECT users."), vec!["name"]);
+    }
+
+    #[test]
+    fn test_dot_completion_uses_aliases_in_scope() {
+        let mut engine = Engine::new();
+        engine.load("25.8", &data("25.8", &["toDate"]));
+        engine.register_schema(&schema());
+        let at = |marked: &str| {
+            let cursor = marked.find('|').expect("cursor marked");
+            let sql = marked.replacen('|', "", 1);
+            let list: Value =
+                serde_json::from_str(&engine.get_completions("25.8", &sql, cursor, "byte", 100))
+                    .expect("valid JSON");
+            list["items"]
+                .as_array()
+                .expect("items")
+                .iter()
+                .map(|item| item["label"].as_str().expect("label").to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            at("SELECT e.| FROM analytics.events e JOIN users u ON e.user_id = u.id"),
+            vec!["ts", "user_id"]
+        );
+        assert_eq!(
+            at("SELECT u.| FROM events e JOIN users u ON 1"),
+            vec!["name"]
+        );
+        assert!(at("SELECT x.| FROM events e").is_empty());
+    }
+
+    #[test]
+    fn test_registered_names_are_documented() {
+        let mut engine = Engine::new();
+        engine.register_schema(&schema());
+        let doc = |engine: &Engine, label: &str, kind: &str| -> Value {
+            serde_json::from_str(&engine.resolve_completion("25.8", label, kind))
+                .expect("valid JSON")
+        };
+        assert!(doc(&engine, "user_id", "column")["value"]
+            .as_str()
+            .expect("documented")
+            .contains("`UInt64`\n\nWho acted"));
+        assert!(doc(&engine, "events", "table")["value"]
+            .as_str()
+            .expect("documented")
+            .contains("Page views and clicks"));
+        assert!(doc(&engine, "missing", "column").is_null());
+
+        let failed: Value = serde_json::from_str(&engine.register_schema("{\"databases\": 1}"))
+            .expect("valid JSON");
+        assert_eq!(failed["success"], false);
+        // A schema that does not parse keeps the registered one
+        assert!(!doc(&engine, "events", "table").is_null());
+    }
+
+    #[test]
+    fn test_queries_are_checked_against_the_registered_schema() {
+        let mut engine = Engine::new();
+        engine.load("25.8", &data("25.8", &[]));
+        let codes = |engine: &Engine, sql: &str| -> Vec<String> {
+            let result: Value = serde_json::from_str(&engine.validate_sql("25.8", sql, "byte"))
+                .expect("valid JSON");
+            result["errors"]
+                .as_array()
+                .expect("errors")
+                .iter()
+                .map(|e| e["code"].as_str().expect("code").to_string())
+                .collect()
+        };
+        let sql = "SELECT usre_id FROM analytics.events";
+        assert!(codes(&engine, sql).is_empty());
+
+        engine.register_schema(&schema());
+        assert_eq!(codes(&engine, sql), vec!["semantic/unknown-column"]);
+        assert!(codes(&engine, "SELECT user_id FROM analytics.events").is_empty());
+    }
+
+    #[test]
+    fn test_infrastructure_map_is_registered_as_the_schema() {
+        let mut engine = Engine::new();
+        engine.load("25.8", &data("25.8", &[]));
+        let map = r#"{"tables": {"PageView_0_1": {
+            "name": "PageView",
+            "version": "0.1",
+            "columns": [{"name": "path", "data_type": "String", "required": true}],
+            "order_by": ["path"]
+        }}}"#;
+        let registered: Value =
+            serde_json::from_str(&engine.register_infrastructure_map(map, "local"))
+                .expect("valid JSON");
+        assert_eq!(registered["success"], true);
+        assert_eq!(
+            labels(&engine, "25.8", "SELECT * FROM local."),
+            vec!["PageView_0_1"]
+        );
+        let validated: Value = serde_json::from_str(&engine.validate_sql(
+            "25.8",
+            "SELECT pth FROM PageView_0_1",
+            "byte",
+        ))
+        .expect("valid JSON");
+        assert_eq!(validated["errors"][0]["code"], "semantic/unknown-column");
+
+        let failed: Value =
+            serde_json::from_str(&engine.register_infrastructure_map("{\"tables\": 1}", "local"))
+                .expect("valid JSON");
+        assert_eq!(failed["success"], false);
+        assert_eq!(labels(&engine, "25.8", "SELECT * FROM local.").len(), 1);
+    }
+}
@@ -0,0 +1,162 @@
+//! Fuzzy matching of the word being typed against completion labels.
+//!
+//! The characters of the pattern must appear in the label in order, ignoring
+//! case. Characters matched at the start of a word in the label score most:
+//! the start of the label, after `_`, `.` or `/`, and an uppercase letter or
+//! digit after a lowercase letter. That makes `tSOH` and `tsoh` rank
+//! `toStartOfHour` high although neither is a prefix of it. Runs of matched
+//! characters score more than scattered ones, and labels that start with the
+//! pattern score above every other match.
+
+/// Any matched character
+const MATCH: i32 = 1;
+/// A character matched at the start of the label
+const LABEL_START: i32 = 12;
+/// A character matched at the start of a word inside the label
+const WORD_START: i32 = 10;
+/// A character matched directly after the previous one
+const CONSECUTIVE: i32 = 8;
+/// A character matched with the case it was typed in
+const SAME_CASE: i32 = 1;
+/// Each label character skipped between two matches, or before the first
+const GAP: i32 = 1;
+/// The label starts with the pattern
+const PREFIX: i32 = 100;
+/// The label is the pattern, ignoring case
+const EXACT: i32 = 200;
+
+/// How well `pattern` matches `label`, higher being better, or `None` if the
+/// pattern's characters do not all appear in the label in order. An empty
+/// pattern matches every label with a score of 0.
+pub(crate) fn score(pattern: &str, label: &str) -> Option<u32> {
+    if pattern.is_empty() {
+        return Some(0);
+    }
+    let pattern: Vec<char> = pattern.chars().collect();
+    let label: Vec<char> = label.chars().collect();
+    if !is_subsequence(&pattern, &label) {
+        return None;
+    }
+
+    // best[j]: the best score of the pattern so far with its last character
+    // matched at label[j]
+    let mut best: Vec<Option<i32>> = vec![None; label.len()];
+    for (i, &p) in pattern.iter().enumerate() {
+        let previous = std::mem::replace(&mut best, vec![None; label.len()]);
+        // The best previous match at least one character before label[j - 1],
+        // less the gap up to label[j]
+        let mut skipped: Option<i32> = None;
+        for (j, &c) in label.iter().enumerate() {
+            if j >= 2 {
+                let candidate = previous[j - 2].map(|s| s - GAP);
+                skipped = skipped.map(|s| s - GAP).max(candidate);
+            }
+            if !c.to_lowercase().eq(p.to_lowercase()) {
+                continue;
+            }
+            let before = if i == 0 {
+                Some(-GAP * i32::try_from(j).unwrap_or(i32::MAX))
+            } else {
+                let adjacent = j
+                    .checked_sub(1)
+                    .and_then(|k| previous[k])
+                    .map(|s| s + CONSECUTIVE);
+                adjacent.max(skipped)
+            };
+            best[j] = before.map(|s| s + character_score(&label, j, p));
+        }
+    }
+
+    let mut total = best.into_iter().flatten().max()?;
+    let folded =
+        |chars: &[char]| -> String { chars.iter().flat_map(|c| c.to_lowercase()).collect() };
+    let (pattern, label) = (folded(&pattern), folded(&label));
+    if label == pattern {
+        total += EXACT;
+    } else if label.starts_with(&pattern) {
+        total += PREFIX;
+    }
+    Some(u32::try_from(total.max(0)).unwrap_or(0))
+}
+
+/// Score for matching `typed` at `label[j]`
+fn character_score(label: &[char], j: usize, typed: char) -> i32 {
+    let c = label[j];
+    let mut score = MATCH;
+    if j == 0 {
+        score += LABEL_START;
+    } else if is_word_start(label[j - 1], c) {
+        score += WORD_START;
+    }
+    if c == typed {
+        score += SAME_CASE;
+    }
+    score
+}
+
+fn is_word_start(previous: char, c: char) -> bool {
+    if !previous.is_alphanumeric() {
+        return c.is_alphanumeric();
+    }
+    previous.is_lowercase() && (c.is_uppercase() || c.is_numeric())
+}
+
+fn is_subsequence(pattern: &[char], label: &[char]) -> bool {
+    let mut label = label.iter();
+    pattern.iter().all(|p| {
+        label
+            .by_ref()
+            .any(|c| c.to_lowercase().eq(p.to_lowercase()))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ranked<'a>(pattern: &str, labels: &[&'a str]) -> Vec<&'a str> {
+        let mut matches: Vec<(u32, &str)> = labels
+            .iter()
+            .filter_map(|label| Some((score(pattern, label)?, *label)))
+            .collect();
+        matches.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(b.1)));
+        matches.into_iter().map(|(_, label)| label).collect()
+    }
+
+    #[test]
+    fn test_matches_word_starts() {
+        let labels = [
+            "toStartOfHour",
+            "toStartOfDay",
+            "toStartOfFifteenMinutes",
+            "timeSlotsOfHours",
+            "toString",
+        ];
+        assert_eq!(ranked("tSOH", &labels)[0], "toStartOfHour");
+        assert_eq!(ranked("tsoh", &labels)[0], "toStartOfHour");
+        assert_eq!(ranked("tsod", &labels), vec!["toStartOfDay"]);
+        assert_eq!(score("xyz", "toStartOfHour"), None);
+        assert_eq!(score("hts", "toStartOfHour"), None);
+    }
+
+    #[test]
+    fn test_prefixes_rank_first() {
+        let labels = ["sum", "sumIf", "uniqExact", "assumeNotNull", "SUM"];
+        assert_eq!(
+            ranked("sum", &labels),
+            vec!["sum", "SUM", "sumIf", "assumeNotNull"]
+        );
+        assert_eq!(ranked("", &labels).len(), labels.len());
+        assert_eq!(score("", "sum"), Some(0));
+    }
+
+    #[test]
+    fn test_word_separators() {
+        assert!(score("mit", "max_insert_threads") > score("mit", "maximum_time"));
+        assert_eq!(
+            ranked("eb", &["Europe/Berlin", "Asia/Tbilisi"])[0],
+            "Europe/Berlin"
+        );
+        assert!(score("i6", "toInt64").is_some());
+    }
+}
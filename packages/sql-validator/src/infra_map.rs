@@ -0,0 +1,595 @@
+//! A `Schema` read from the infrastructure map Moose keeps of a project.
+//!
+//! The map lists every `OlapTable`, view and stream, keyed by id or as a
+//! list, in the snake case of `moose` or the camel case of the data model
+//! libraries; both are read. Tables keep their `ClickHouse` types, sorting
+//! key, partition key and engine; a versioned table is named with its
+//! version, as `events_1_0`, as Moose creates it. A view aliasing a table
+//! has that table's columns, and the setup statements of SQL resources, such
+//! as materialized views, are read as DDL. Streams are not `ClickHouse`
+//! tables and are left out.
+//!
+//! Column types are written the way Moose creates them: a column that is not
+//! required is `Nullable`, data model names such as `Float` become their
+//! `ClickHouse` types, and a type that is not understood is left empty.
+
+use crate::ddl;
+use crate::schema::{ColumnInfo, DatabaseInfo, Schema, TableInfo, TableKind};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use sqlparser::ast::Expr;
+use sqlparser::dialect::ClickHouseDialect;
+use sqlparser::parser::Parser;
+use std::collections::BTreeMap;
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct InfrastructureMap {
+    tables: Entries<Table>,
+    views: Entries<View>,
+    #[serde(alias = "sqlResources")]
+    sql_resources: Entries<SqlResource>,
+}
+
+/// Entries keyed by id, or listed
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Entries<T> {
+    ById(BTreeMap<String, T>),
+    List(Vec<T>),
+}
+
+impl<T> Default for Entries<T> {
+    fn default() -> Self {
+        Entries::List(Vec::new())
+    }
+}
+
+impl<T> Entries<T> {
+    fn into_vec(self) -> Vec<T> {
+        match self {
+            Entries::ById(entries) => entries.into_values().collect(),
+            Entries::List(entries) => entries,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct Table {
+    name: String,
+    #[serde(default)]
+    columns: Vec<Column>,
+    #[serde(default, alias = "orderBy")]
+    order_by: Value,
+    #[serde(default, alias = "partitionBy")]
+    partition_by: Option<String>,
+    #[serde(default)]
+    engine: Value,
+    #[serde(default, alias = "engineConfig")]
+    engine_config: Value,
+    #[serde(default)]
+    version: Option<String>,
+    #[serde(default)]
+    database: Option<String>,
+    #[serde(default)]
+    metadata: Option<Metadata>,
+}
+
+#[derive(Deserialize)]
+struct Metadata {
+    #[serde(default)]
+    description: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Column {
+    name: String,
+    #[serde(default, alias = "dataType")]
+    data_type: Value,
+    #[serde(default = "required")]
+    required: bool,
+    #[serde(default)]
+    comment: Option<String>,
+}
+
+/// Columns are required unless the map says otherwise
+fn required() -> bool {
+    true
+}
+
+#[derive(Deserialize)]
+struct View {
+    name: String,
+    #[serde(default, rename = "view_type", alias = "viewType")]
+    kind: Value,
+    #[serde(default)]
+    version: Option<String>,
+    #[serde(default)]
+    database: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct SqlResource {
+    #[serde(default)]
+    setup: Vec<String>,
+}
+
+/// Reads the tables and views of an infrastructure map, given as JSON,
+/// putting those without a database in `database`
+pub(crate) fn schema_from_infrastructure_map(
+    json: &str,
+    database: &str,
+) -> Result<Schema, serde_json::Error> {
+    let map: InfrastructureMap = serde_json::from_str(json)?;
+    let mut schema = Schema::default();
+
+    for table in map.tables.into_vec() {
+        let info = TableInfo {
+            name: versioned_name(table.name, table.version.as_deref()),
+            kind: TableKind::Table,
+            columns: table.columns.iter().map(column).collect(),
+            comment: table.metadata.and_then(|m| m.description),
+            engine: engine(&table.engine_config).or_else(|| engine(&table.engine)),
+            order_by: order_by(&table.order_by),
+            partition_by: table.partition_by,
+        };
+        add(
+            &mut schema,
+            table.database.as_deref().unwrap_or(database),
+            info,
+        );
+    }
+
+    for view in map.views.into_vec() {
+        let database = view.database.as_deref().unwrap_or(database);
+        let columns = source_table(&view.kind)
+            .and_then(|source| schema.table(Some(database), source))
+            .map(|source| source.columns.clone())
+            .unwrap_or_default();
+        let info = TableInfo {
+            name: versioned_name(view.name, view.version.as_deref()),
+            kind: TableKind::View,
+            columns,
+            comment: None,
+            engine: None,
+            order_by: Vec::new(),
+            partition_by: None,
+        };
+        add(&mut schema, database, info);
+    }
+
+    // Errors in the setup statements are the project's to report; the
+    // statements that do parse still add their views
+    let setup: Vec<String> = map
+        .sql_resources
+        .into_vec()
+        .into_iter()
+        .flat_map(|resource| resource.setup)
+        .collect();
+    Ok(ddl::extend_schema(schema, database, &setup).schema)
+}
+
+/// Adds `table` to `database`, unless a table of that name is already there
+fn add(schema: &mut Schema, database: &str, table: TableInfo) {
+    let position = schema.databases.iter().position(|d| d.name == database);
+    let position = position.unwrap_or_else(|| {
+        schema.databases.push(DatabaseInfo {
+            name: database.to_string(),
+            tables: Vec::new(),
+        });
+        schema.databases.len() - 1
+    });
+    let tables = &mut schema.databases[position].tables;
+    if tables.iter().all(|t| t.name != table.name) {
+        tables.push(table);
+    }
+}
+
+/// `name` with `version` appended, as `events_1_0`, unless it already ends
+/// with it
+fn versioned_name(name: String, version: Option<&str>) -> String {
+    match version {
+        Some(version) if !version.is_empty() => {
+            let suffix = format!("_{}", version.replace('.', "_"));
+            if name.ends_with(&suffix) {
+                name
+            } else {
+                name + &suffix
+            }
+        }
+        _ => name,
+    }
+}
+
+fn column(column: &Column) -> ColumnInfo {
+    let column_type = clickhouse_type(&column.data_type);
+    ColumnInfo {
+        name: column.name.clone(),
+        column_type: if column.required {
+            column_type
+        } else {
+            nullable(column_type)
+        },
+        comment: column.comment.clone(),
+    }
+}
+
+/// `Nullable(column_type)`, inside `LowCardinality`, for the types that can
+/// be `NULL`
+fn nullable(column_type: String) -> String {
+    const NOT_NULLABLE: &[&str] = &["Array(", "Map(", "Nested(", "Tuple(", "JSON", "Nullable("];
+    if column_type.is_empty() || NOT_NULLABLE.iter().any(|p| column_type.starts_with(p)) {
+        return column_type;
+    }
+    match column_type
+        .strip_prefix("LowCardinality(")
+        .and_then(|inner| inner.strip_suffix(')'))
+    {
+        Some(inner) => format!("LowCardinality({})", nullable(inner.to_string())),
+        None => format!("Nullable({column_type})"),
+    }
+}
+
+/// The `ClickHouse` type of a data type in the map, or an empty string for
+/// one that is not understood
+fn clickhouse_type(data_type: &Value) -> String {
+    match data_type {
+        Value::String(name) => type_name(name),
+        Value::Object(fields) => composite_type(fields).unwrap_or_default(),
+        _ => String::new(),
+    }
+}
+
+/// The `ClickHouse` name of a named type
+fn type_name(name: &str) -> String {
+    let renamed = match name {
+        "Boolean" => "Bool",
+        "Int" => "Int64",
+        "Float" => "Float64",
+        "Json" => "JSON",
+        "Bytes" => "String",
+        "Uuid" => "UUID",
+        "Date16" => "Date",
+        "IpV4" => "IPv4",
+        "IpV6" => "IPv6",
+        _ => name,
+    };
+    // `DateTime(3)` is a precision, which only `DateTime64` takes
+    match renamed
+        .strip_prefix("DateTime(")
+        .and_then(|rest| rest.strip_suffix(')'))
+    {
+        Some(precision) if precision.trim().chars().all(|c| c.is_ascii_digit()) => {
+            format!("DateTime64({})", precision.trim())
+        }
+        _ => renamed.to_string(),
+    }
+}
+
+/// Arrays, maps, nested columns, enums, named tuples and nullable types
+fn composite_type(fields: &Map<String, Value>) -> Option<String> {
+    let field = |snake: &str, camel: &str| fields.get(snake).or_else(|| fields.get(camel));
+
+    if let Some(element) = field("element_type", "elementType") {
+        let element = clickhouse_type(element);
+        let element_nullable = field("element_nullable", "elementNullable")
+            .and_then(Value::as_bool)
+            .unwrap_or(false);
+        return Some(format!(
+            "Array({})",
+            if element_nullable {
+                nullable(element)
+            } else {
+                element
+            }
+        ));
+    }
+    if let (Some(key), Some(value)) = (
+        field("key_type", "keyType"),
+        field("value_type", "valueType"),
+    ) {
+        return Some(format!(
+            "Map({}, {})",
+            clickhouse_type(key),
+            clickhouse_type(value)
+        ));
+    }
+    if let Some(inner) = fields.get("nullable") {
+        return Some(nullable(clickhouse_type(inner)));
+    }
+    if let Some(columns) = fields.get("columns") {
+        let columns: Vec<Column> = serde_json::from_value(columns.clone()).ok()?;
+        let columns: Vec<String> = columns
+            .iter()
+            .map(|c| {
+                let c = column(c);
+                format!("{} {}", c.name, c.column_type)
+            })
+            .collect();
+        return Some(format!("Nested({})", columns.join(", ")));
+    }
+    if let Some(values) = fields.get("values").and_then(Value::as_array) {
+        return Some(enum_type(values));
+    }
+    if let Some(tuple_fields) = fields.get("fields").and_then(Value::as_array) {
+        let elements: Vec<String> = tuple_fields
+            .iter()
+            .filter_map(|field| match field.as_array()?.as_slice() {
+                [Value::String(name), data_type] => {
+                    Some(format!("{name} {}", clickhouse_type(data_type)))
+                }
+                _ => None,
+            })
+            .collect();
+        return Some(format!("Tuple({})", elements.join(", ")));
+    }
+    None
+}
+
+/// `Enum8`, or `Enum16` when a value does not fit, of members given as
+/// `{name, value}`, plain or tagged as `{"Int": 1}`. Members with string
+/// values are numbered from 1, in order.
+fn enum_type(members: &[Value]) -> String {
+    let members: Vec<(&str, i64)> = members
+        .iter()
+        .zip(1..)
+        .filter_map(|(member, position)| {
+            let name = member.get("name")?.as_str()?;
+            let value = match member.get("value") {
+                Some(Value::Object(tagged)) => tagged.values().next().and_then(Value::as_i64),
+                Some(value) => value.as_i64(),
+                None => None,
+            };
+            Some((name, value.unwrap_or(position)))
+        })
+        .collect();
+    let width = if members.iter().all(|(_, v)| i8::try_from(*v).is_ok()) {
+        8
+    } else {
+        16
+    };
+    let members: Vec<String> = members
+        .iter()
+        .map(|(name, value)| format!("'{}' = {value}", name.replace('\'', "\\'")))
+        .collect();
+    format!("Enum{width}({})", members.join(", "))
+}
+
+/// The engine with its arguments, from a name, an engine config such as
+/// `{"engine": "ReplacingMergeTree", "ver": "updated_at"}`, or an engine
+/// tagged with its parameters
+fn engine(engine: &Value) -> Option<String> {
+    match engine {
+        Value::String(name) if !name.is_empty() => Some(name.clone()),
+        Value::Object(fields) => {
+            if let Some(name) = fields.get("engine").and_then(Value::as_str) {
+                return Some(with_arguments(name, fields));
+            }
+            match fields.iter().next() {
+                Some((name, Value::Object(parameters))) if fields.len() == 1 => {
+                    Some(with_arguments(name, parameters))
+                }
+                Some((name, _)) if fields.len() == 1 => Some(name.clone()),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// `name(arguments)` with the columns an engine takes, in the order the
+/// engines that take them expect: `ReplacingMergeTree(ver, is_deleted)`,
+/// `CollapsingMergeTree(sign)` and `VersionedCollapsingMergeTree(sign,
+/// version)`
+fn with_arguments(name: &str, parameters: &Map<String, Value>) -> String {
+    const ARGUMENTS: &[(&str, &str)] = &[
+        ("ver", "ver"),
+        ("is_deleted", "isDeleted"),
+        ("sign", "sign"),
+        ("version", "version"),
+    ];
+    let arguments: Vec<&str> = ARGUMENTS
+        .iter()
+        .filter_map(|(snake, camel)| {
+            parameters
+                .get(*snake)
+                .or_else(|| parameters.get(*camel))
+                .and_then(Value::as_str)
+        })
+        .collect();
+    if arguments.is_empty() {
+        name.to_string()
+    } else {
+        format!("{name}({})", arguments.join(", "))
+    }
+}
+
+/// The expressions of a sorting key given as fields, as one expression, or
+/// as either tagged with `Fields` or `SingleExpr`
+fn order_by(key: &Value) -> Vec<String> {
+    match key {
+        Value::Array(fields) => fields
+            .iter()
+            .filter_map(Value::as_str)
+            .map(ToString::to_string)
+            .collect(),
+        Value::String(expression) => key_expressions(expression),
+        Value::Object(tagged) if tagged.len() == 1 => {
+            tagged.values().next().map(order_by).unwrap_or_default()
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// The elements of a key written as one expression, as `(a, b)` has `a`
+/// and `b`
+fn key_expressions(expression: &str) -> Vec<String> {
+    let dialect = ClickHouseDialect {};
+    let parsed = Parser::new(&dialect)
+        .try_with_sql(expression)
+        .and_then(|mut parser| parser.parse_expr());
+    match parsed {
+        Ok(Expr::Tuple(elements)) => elements.iter().map(ToString::to_string).collect(),
+        Ok(Expr::Nested(inner)) => vec![inner.to_string()],
+        _ if expression.trim().is_empty() => Vec::new(),
+        _ => vec![expression.trim().to_string()],
+    }
+}
+
+/// The table a view aliases
+fn source_table(view_type: &Value) -> Option<&str> {
+    let Value::Object(fields) = view_type else {
+        return None;
+    };
+    fields
+        .get("source_table_name")
+        .or_else(|| fields.get("sourceTableName"))
+        .and_then(Value::as_str)
+        .or_else(|| fields.values().find_map(source_table))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table<'a>(schema: &'a Schema, database: &str, name: &str) -> &'a TableInfo {
+        schema.table(Some(database), name).expect("table exists")
+    }
+
+    fn types(table: &TableInfo) -> Vec<(&str, &str)> {
+        table
+            .columns
+            .iter()
+            .map(|c| (c.name.as_str(), c.column_type.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn test_tables_from_the_moose_cli_map() {
+        let schema = schema_from_infrastructure_map(
+            r#"{
+                "topics": {"Events_1_0": {"name": "Events"}},
+                "tables": {
+                    "Events_1_0": {
+                        "name": "Events",
+                        "version": "1.0",
+                        "columns": [
+                            {"name": "id", "data_type": "String", "required": true},
+                            {"name": "ts", "data_type": "DateTime(3)", "required": true},
+                            {"name": "score", "data_type": "Float", "required": false},
+                            {"name": "tags", "data_type": {"element_type": "String", "element_nullable": false}, "required": false},
+                            {"name": "kind", "data_type": {"name": "Kind", "values": [
+                                {"name": "click", "value": {"Int": 1}},
+                                {"name": "view", "value": {"Int": 2}}
+                            ]}, "required": true, "comment": "What happened"}
+                        ],
+                        "order_by": ["id", "ts"],
+                        "partition_by": "toYYYYMM(ts)",
+                        "engine": {"ReplacingMergeTree": {"ver": "ts", "is_deleted": null}},
+                        "metadata": {"description": "Raw events"}
+                    },
+                    "Users": {
+                        "name": "Users",
+                        "database": "analytics",
+                        "columns": [
+                            {"name": "name", "data_type": "LowCardinality(String)", "required": false},
+                            {"name": "attributes", "data_type": {"key_type": "String", "value_type": "Int"}}
+                        ],
+                        "order_by": {"SingleExpr": "(name, cityHash64(name))"}
+                    }
+                },
+                "views": {
+                    "Latest": {"name": "Latest", "view_type": {"TableAlias": {"source_table_name": "Events_1_0"}}}
+                }
+            }"#,
+            "local",
+        )
+        .expect("valid map");
+
+        let events = table(&schema, "local", "Events_1_0");
+        assert_eq!(
+            types(events),
+            vec![
+                ("id", "String"),
+                ("ts", "DateTime64(3)"),
+                ("score", "Nullable(Float64)"),
+                ("tags", "Array(String)"),
+                ("kind", "Enum8('click' = 1, 'view' = 2)"),
+            ]
+        );
+        assert_eq!(events.columns[4].comment.as_deref(), Some("What happened"));
+        assert_eq!(events.order_by, vec!["id", "ts"]);
+        assert_eq!(events.partition_by.as_deref(), Some("toYYYYMM(ts)"));
+        assert_eq!(events.engine.as_deref(), Some("ReplacingMergeTree(ts)"));
+        assert_eq!(events.comment.as_deref(), Some("Raw events"));
+
+        let users = table(&schema, "analytics", "Users");
+        assert_eq!(
+            types(users),
+            vec![
+                ("name", "LowCardinality(Nullable(String))"),
+                ("attributes", "Map(String, Int64)"),
+            ]
+        );
+        assert_eq!(users.order_by, vec!["name", "cityHash64(name)"]);
+        assert_eq!(users.engine, None);
+
+        let latest = table(&schema, "local", "Latest");
+        assert_eq!(latest.kind, TableKind::View);
+        assert_eq!(latest.columns, events.columns);
+        assert!(schema.table(None, "Events").is_none());
+    }
+
+    #[test]
+    fn test_tables_from_the_data_model_dump() {
+        let schema = schema_from_infrastructure_map(
+            r#"{
+                "tables": [{
+                    "name": "Orders",
+                    "columns": [
+                        {"name": "id", "dataType": "Uuid"},
+                        {"name": "paid", "dataType": "Boolean"},
+                        {"name": "address", "dataType": {"name": "Address", "columns": [
+                            {"name": "city", "dataType": "String", "required": false}
+                        ], "jwt": false}},
+                        {"name": "point", "dataType": {"fields": [["x", "Float"], ["y", "Float"]]}},
+                        {"name": "raw", "dataType": {"something": "new"}}
+                    ],
+                    "orderBy": "id",
+                    "engineConfig": {"engine": "CollapsingMergeTree", "sign": "paid"}
+                }],
+                "sqlResources": [{
+                    "name": "OrderTotals",
+                    "setup": [
+                        "CREATE MATERIALIZED VIEW OrderTotals TO Orders AS SELECT * FROM Orders",
+                        "CREATE TABLE"
+                    ]
+                }]
+            }"#,
+            "local",
+        )
+        .expect("valid map");
+
+        let orders = table(&schema, "local", "Orders");
+        assert_eq!(
+            types(orders),
+            vec![
+                ("id", "UUID"),
+                ("paid", "Bool"),
+                ("address", "Nested(city Nullable(String))"),
+                ("point", "Tuple(x Float64, y Float64)"),
+                ("raw", ""),
+            ]
+        );
+        assert_eq!(orders.order_by, vec!["id"]);
+        assert_eq!(orders.engine.as_deref(), Some("CollapsingMergeTree(paid)"));
+
+        let totals = table(&schema, "local", "OrderTotals");
+        assert_eq!(totals.kind, TableKind::MaterializedView);
+        assert_eq!(totals.columns, orders.columns);
+
+        assert!(
+            schema_from_infrastructure_map(r#"{"tables": [{"columns": []}]}"#, "local").is_err()
+        );
+    }
+}
@@ -1,21 +1,256 @@
 use serde::{Deserialize, Serialize};
 use sqlparser::dialect::ClickHouseDialect;
 use sqlparser::parser::Parser;
-use sqlparser::tokenizer::{Token, Tokenizer};
-use std::sync::OnceLock;
+use std::borrow::Cow;
 use wasm_bindgen::prelude::*;
 
+mod clickhouse_index;
+mod codes;
+mod combinators;
+mod context;
+mod ddl;
+mod engine;
+mod fuzzy;
+mod infra_map;
+mod position;
+mod recovery;
+mod schema;
+mod scope;
+mod semantic;
+mod signature;
+mod suggest;
+mod template;
+mod values;
+mod word;
+
+pub use codes::{ErrorCategory, ErrorCode, ErrorCodeInfo};
+pub use ddl::{DdlSchema, SourceError};
+pub use engine::Engine;
+pub use schema::{ColumnInfo, DatabaseInfo, Schema, TableInfo, TableKind};
+
+use clickhouse_index::ClickHouseIndex;
+use combinators::CombinatorForm;
+use context::{detect_context, SqlContext};
+use position::LineIndex;
+use sqlparser::tokenizer::Span;
+use values::ValueKind;
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct ValidationResult {
     pub valid: bool,
+    /// First error, kept for callers that only show a single diagnostic
     pub error: Option<ValidationError>,
+    /// Every error found, in source order
+    #[serde(default)]
+    pub errors: Vec<ValidationError>,
+}
+
+impl ValidationResult {
+    /// The result for a call given an argument it cannot use, with an error
+    /// that has no position in the SQL
+    fn invalid_argument(message: String) -> Self {
+        let error = ValidationError::invalid_argument(message);
+        Self {
+            valid: false,
+            error: Some(error.clone()),
+            errors: vec![error],
+        }
+    }
+
+    /// The result with every offset and column of `sql` counted in `encoding`
+    fn encoded(mut self, sql: &str, encoding: OffsetEncoding) -> Self {
+        let lines = LineIndex::new(sql);
+        for error in self.errors.iter_mut().chain(self.error.as_mut()) {
+            error.encode(&lines, encoding);
+        }
+        self
+    }
+}
+
+/// What a piece of SQL is parsed as. Full statements get clause-level error
+/// recovery; `sql.fragment` templates hold smaller pieces.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ParseMode {
+    /// One or more statements separated by `;`
+    Statement,
+    /// A single expression: `price * quantity`
+    Expression,
+    /// A boolean expression: `status = 'active' AND ts > now()`
+    Condition,
+    /// A comma-separated `SELECT` list: `id, count() AS n`
+    SelectItems,
+    /// A comma-separated `ORDER BY` list: `ts DESC, id`
+    OrderBy,
+    /// Comma-separated column definitions: `id UInt64, name String`
+    ColumnDefinitions,
+}
+
+impl ParseMode {
+    /// Parses the `snake_case` name used over the wasm boundary
+    #[must_use]
+    pub fn from_name(name: &str) -> Option<Self> {
+        serde_json::from_value(serde_json::Value::String(name.to_string())).ok()
+    }
+}
+
+/// The unit offsets and columns are counted in across the wasm boundary
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum OffsetEncoding {
+    /// UTF-8 bytes, as Rust indexes strings
+    #[default]
+    Byte,
+    /// Unicode scalar values
+    Char,
+    /// UTF-16 code units, as JavaScript and LSP positions index strings
+    Utf16,
+}
+
+impl OffsetEncoding {
+    /// Parses the `snake_case` name used over the wasm boundary
+    #[must_use]
+    pub fn from_name(name: &str) -> Option<Self> {
+        serde_json::from_value(serde_json::Value::String(name.to_string())).ok()
+    }
+}
+
+/// A placeholder in the SQL: the byte range of a `${...}` interpolation,
+/// or of whatever text stands in for it
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Hole {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Hole {
+    fn contains(self, offset: usize) -> bool {
+        self.start <= offset && offset < self.end
+    }
+}
+
+/// What a placeholder turned out to stand for
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum HoleRole {
+    /// A literal: `LIMIT ${n}`, `Decimal(10, ${scale})`, `'${prefix}%'`
+    Value,
+    /// A name that is not an expression: `t.${column}`, `AS ${alias}`
+    Identifier,
+    /// A table: `FROM ${table}`, `INSERT INTO ${table}`
+    Table,
+    /// An expression: `WHERE ${condition}`, `x IN ${list}`
+    Expression,
 }
 
+/// A placeholder and its role, `None` when the SQL around it does not
+/// parse whatever it stands for
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HoleInfo {
+    pub start: usize,
+    pub end: usize,
+    pub role: Option<HoleRole>,
+}
+
+/// Validation of SQL with placeholders, plus what each placeholder was taken for
 #[derive(Serialize, Deserialize, Debug)]
+pub struct TemplateValidationResult {
+    #[serde(flatten)]
+    pub result: ValidationResult,
+    pub holes: Vec<HoleInfo>,
+}
+
+/// A validation failure and the range of source it applies to.
+/// Lines and columns are 1-based and the end is exclusive. Offsets count bytes
+/// and columns count chars, except in results of the wasm exports that take
+/// an encoding, where both count units of the requested `OffsetEncoding`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
 pub struct ValidationError {
+    pub code: ErrorCode,
+    pub category: ErrorCategory,
     pub message: String,
     pub line: Option<u32>,
     pub column: Option<u32>,
+    #[serde(default)]
+    pub end_line: Option<u32>,
+    #[serde(default)]
+    pub end_column: Option<u32>,
+    /// Byte offset of the first offending character
+    #[serde(default)]
+    pub start_offset: Option<usize>,
+    /// Byte offset just past the last offending character
+    #[serde(default)]
+    pub end_offset: Option<usize>,
+    /// Likely intended names, best first, for "did you mean" quick fixes
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub suggestions: Vec<String>,
+}
+
+impl ValidationError {
+    /// An error for an argument of an export call, with no position in the SQL
+    fn invalid_argument(message: String) -> Self {
+        Self {
+            code: ErrorCode::InvalidArgument,
+            category: ErrorCode::InvalidArgument.category(),
+            message,
+            line: None,
+            column: None,
+            end_line: None,
+            end_column: None,
+            start_offset: None,
+            end_offset: None,
+            suggestions: Vec::new(),
+        }
+    }
+
+    fn at_range(
+        code: ErrorCode,
+        message: String,
+        start: usize,
+        end: usize,
+        index: &LineIndex,
+    ) -> Self {
+        let start_location = index.location(start);
+        let end_location = index.location(end);
+        let to_u32 = |n: u64| u32::try_from(n).ok();
+
+        Self {
+            code,
+            category: code.category(),
+            message,
+            line: to_u32(start_location.line),
+            column: to_u32(start_location.column),
+            end_line: to_u32(end_location.line),
+            end_column: to_u32(end_location.column),
+            start_offset: Some(start),
+            end_offset: Some(end),
+            suggestions: Vec::new(),
+        }
+    }
+
+    fn at_span(code: ErrorCode, message: String, span: Span, index: &LineIndex) -> Self {
+        let (start, end) = index.range(span);
+        Self::at_range(code, message, start, end, index)
+    }
+
+    fn with_suggestions(mut self, suggestions: Vec<String>) -> Self {
+        self.suggestions = suggestions;
+        self
+    }
+
+    /// Re-counts offsets and columns, found as bytes and chars, in `encoding`
+    fn encode(&mut self, lines: &LineIndex, encoding: OffsetEncoding) {
+        let to_u32 = |n: u64| u32::try_from(n).ok();
+        if let Some(start) = self.start_offset {
+            self.column = to_u32(lines.encoded_column(start, encoding));
+            self.start_offset = Some(lines.encoded_offset(start, encoding));
+        }
+        if let Some(end) = self.end_offset {
+            self.end_column = to_u32(lines.encoded_column(end, encoding));
+            self.end_offset = Some(lines.encoded_offset(end, encoding));
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -26,7 +261,7 @@ pub struct FormatResult {
 }
 
 /// Completion item kind - domain categories for SQL completions
-#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
 #[serde(rename_all = "snake_case")]
 pub enum CompletionItemKind {
     Function,
@@ -37,10 +272,25 @@ pub enum CompletionItemKind {
     Setting,
     AggregateFunction,
     TableFunction,
+    TimeZone,
+    /// A table of the registered schema
+    Table,
+    /// A column of a table of the registered schema
+    Column,
+}
+
+impl CompletionItemKind {
+    /// Parses the `snake_case` name used over the wasm boundary
+    #[must_use]
+    pub fn from_name(name: &str) -> Option<Self> {
+        serde_json::from_value(serde_json::Value::String(name.to_string())).ok()
+    }
 }
 
 /// Completion item returned from Rust - domain data only
 /// TypeScript is responsible for mapping to LSP protocol (`CompletionItem`, `InsertTextFormat`, etc.)
+/// Documentation is left out and looked up by `resolve_completion` when an
+/// item is selected.
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct CompletionItem {
@@ -48,8 +298,6 @@ pub struct CompletionItem {
     pub kind: CompletionItemKind,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub detail: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub documentation: Option<Documentation>,
     /// Whether this completion accepts parameters (for functions)
     /// TypeScript uses this to construct snippet syntax
     #[serde(default)]
@@ -65,12 +313,58 @@ pub struct Documentation {
     pub value: String,
 }
 
+/// A range of the input SQL, as start and end offsets
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TextRange {
+    pub start: usize,
+    pub end: usize,
+}
+
+/// A completion item with how well it matches the word being typed
+#[derive(Serialize, Debug, Clone)]
+pub struct RankedCompletion<'a> {
+    #[serde(flatten)]
+    pub item: Cow<'a, CompletionItem>,
+    /// Fuzzy match score of the label; higher is better
+    pub score: u32,
+}
+
+/// Completions at a cursor, best first
+#[derive(Serialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct CompletionList<'a> {
+    pub items: Vec<RankedCompletion<'a>>,
+    /// The word at the cursor, which the chosen completion replaces: all of
+    /// it, with the quotes of a quoted identifier, or the contents of a string
+    pub replace_range: TextRange,
+    /// Whether matches beyond the requested number were left out, so the
+    /// list should be requested again as typing continues
+    pub is_incomplete: bool,
+    /// Set when the call's arguments are invalid, such as an unknown encoding
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<ValidationError>,
+}
+
+impl CompletionList<'_> {
+    /// The list with its range of `sql` counted in `encoding`
+    fn encoded(mut self, sql: &str, encoding: OffsetEncoding) -> Self {
+        let lines = LineIndex::new(sql);
+        self.replace_range = TextRange {
+            start: lines.encoded_offset(self.replace_range.start, encoding),
+            end: lines.encoded_offset(self.replace_range.end, encoding),
+        };
+        self
+    }
+}
+
 /// Function info from `ClickHouse` data
 #[derive(Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct FunctionInfo {
     pub name: String,
     pub is_aggregate: bool,
+    #[serde(default)]
+    pub case_insensitive: bool,
     pub alias_to: Option<String>,
     #[serde(default)]
     pub syntax: String,
@@ -89,6 +383,8 @@ pub struct FunctionInfo {
 #[serde(rename_all = "camelCase")]
 pub struct DataTypeInfo {
     pub name: String,
+    #[serde(default)]
+    pub case_insensitive: bool,
     pub alias_to: Option<String>,
 }
 
@@ -140,6 +436,8 @@ pub struct ClickHouseData {
     pub table_functions: Vec<TableFunctionInfo>,
     #[serde(default)]
     pub aggregate_combinators: Vec<String>,
+    #[serde(default)]
+    pub time_zones: Vec<String>,
     pub settings: Vec<SettingInfo>,
     pub merge_tree_settings: Vec<SettingInfo>,
 }
@@ -155,28 +453,27 @@ struct CompletionCache {
     formats: Vec<CompletionItem>,
     table_functions: Vec<CompletionItem>,
     settings: Vec<CompletionItem>,
+    // Time zone names, offered only inside string literals
+    time_zones: Vec<CompletionItem>,
     // Logical operators for WHERE/HAVING context
     logical_operators: Vec<CompletionItem>,
     // ORDER BY specific keywords
     order_by_keywords: Vec<CompletionItem>,
 }
 
-static COMPLETION_CACHE: OnceLock<CompletionCache> = OnceLock::new();
-
 // Sort priority constants - lower numbers appear first
 const SORT_PRIORITY_KEYWORD: &str = "0_";
+const SORT_PRIORITY_SCHEMA: &str = "0_";
 const SORT_PRIORITY_FUNCTION: &str = "1_";
 const SORT_PRIORITY_DATA_TYPE: &str = "2_";
 const SORT_PRIORITY_TABLE_ENGINE: &str = "3_";
 const SORT_PRIORITY_FORMAT: &str = "4_";
 const SORT_PRIORITY_TABLE_FUNCTION: &str = "5_";
 const SORT_PRIORITY_SETTING: &str = "6_";
+const SORT_PRIORITY_TIME_ZONE: &str = "7_";
 const SORT_PRIORITY_ALIAS: &str = "9_";
 
-fn build_function_completion(
-    func: &FunctionInfo,
-    all_functions: &[FunctionInfo],
-) -> CompletionItem {
+fn build_function_completion(func: &FunctionInfo) -> CompletionItem {
     let kind = if func.is_aggregate {
         CompletionItemKind::AggregateFunction
     } else {
@@ -197,10 +494,75 @@ fn build_function_completion(
         format!("{SORT_PRIORITY_FUNCTION}{}", func.name)
     };
 
+    CompletionItem {
+        label: func.name.clone(),
+        kind,
+        detail,
+        has_params: true, // All functions have parentheses
+        sort_text: Some(sort_text),
+    }
+}
+
+/// A combinator chain the data does not list, such as `avgMergeOrNull`
+fn build_combinator_completion(name: String) -> CompletionItem {
+    CompletionItem {
+        sort_text: Some(format!("{SORT_PRIORITY_FUNCTION}{name}")),
+        label: name,
+        kind: CompletionItemKind::AggregateFunction,
+        detail: Some("(aggregate function)".to_string()),
+        has_params: true,
+    }
+}
+
+/// Documentation for the completion item `label` of `kind`
+fn build_completion_documentation(
+    index: &ClickHouseIndex,
+    label: &str,
+    kind: CompletionItemKind,
+) -> Option<Documentation> {
+    match kind {
+        CompletionItemKind::Function | CompletionItemKind::AggregateFunction => {
+            match index.function(label) {
+                Some(func) => build_listed_function_documentation(func, index),
+                // Combinator chains the data does not list
+                None => index
+                    .combinator_form(label)
+                    .filter(|form| form.base.is_aggregate)
+                    .map(|form| build_combinator_documentation(label, &form)),
+            }
+        }
+        CompletionItemKind::TableFunction => index
+            .data
+            .table_functions
+            .iter()
+            .find(|tf| tf.name == label)
+            .and_then(|tf| build_description_documentation(&tf.description)),
+        CompletionItemKind::Setting => index
+            .setting(label)
+            .or_else(|| index.merge_tree_setting(label))
+            .and_then(|setting| build_description_documentation(&setting.description)),
+        CompletionItemKind::Keyword
+        | CompletionItemKind::DataType
+        | CompletionItemKind::TableEngine
+        | CompletionItemKind::Format
+        | CompletionItemKind::TimeZone
+        | CompletionItemKind::Table
+        | CompletionItemKind::Column => None,
+    }
+}
+
+/// Documentation for a function the data lists, which for an alias is that
+/// of its target
+fn build_listed_function_documentation(
+    func: &FunctionInfo,
+    index: &ClickHouseIndex,
+) -> Option<Documentation> {
     // For aliases, show "alias for X" header + target function's documentation
-    let documentation = if let Some(ref alias_to) = func.alias_to {
+    if let Some(ref alias_to) = func.alias_to {
         // Find the target function (case-insensitive)
-        let target = all_functions
+        let target = index
+            .data
+            .functions
             .iter()
             .find(|f| f.name.eq_ignore_ascii_case(alias_to));
 
@@ -217,17 +579,57 @@ fn build_function_completion(
             kind: "markdown".to_string(),
             value: parts.join("\n\n"),
         })
+    } else if let Some(form) = index.combinator_form(&func.name) {
+        // Generated combinator forms: explain the base and the combinator
+        Some(build_combinator_documentation(&func.name, &form))
     } else {
         build_function_documentation(func)
+    }
+}
+
+/// A plain description as markdown, if there is one
+fn build_description_documentation(description: &str) -> Option<Documentation> {
+    if description.is_empty() {
+        None
+    } else {
+        Some(Documentation {
+            kind: "markdown".to_string(),
+            value: description.trim().to_string(),
+        })
+    }
+}
+
+/// Documents `name` as its base aggregate function plus each combinator
+fn build_combinator_documentation(name: &str, form: &CombinatorForm) -> Documentation {
+    let combinators: Vec<String> = form.combinators.iter().map(|c| format!("`{c}`")).collect();
+    let applied = match combinators.as_slice() {
+        [single] => format!("the {single} combinator"),
+        [init @ .., last] => format!(
+            "the {} and {last} combinators, in that order,",
+            init.join(", ")
+        ),
+        [] => "no combinators".to_string(),
     };
+    let mut parts = vec![
+        format!("**{name}** _(aggregate function)_"),
+        format!(
+            "Applies {applied} to the aggregate function `{}`.",
+            form.base.name
+        ),
+        form.combinators
+            .iter()
+            .map(|c| format!("- **{c}:** {}", combinators::describe(c)))
+            .collect::<Vec<_>>()
+            .join("\n"),
+    ];
+    if let Some(base_doc) = build_function_documentation(form.base) {
+        parts.push(format!("**Base function `{}`:**", form.base.name));
+        parts.push(base_doc.value);
+    }
 
-    CompletionItem {
-        label: func.name.clone(),
-        kind,
-        detail,
-        documentation,
-        has_params: true, // All functions have parentheses
-        sort_text: Some(sort_text),
+    Documentation {
+        kind: "markdown".to_string(),
+        value: parts.join("\n\n"),
     }
 }
 
@@ -265,7 +667,6 @@ fn build_keyword_completion(keyword: &str) -> CompletionItem {
         label: keyword.to_string(),
         kind: CompletionItemKind::Keyword,
         detail: Some("(keyword)".to_string()),
-        documentation: None,
         has_params: false,
         sort_text: Some(format!("{SORT_PRIORITY_KEYWORD}{keyword}")),
     }
@@ -288,7 +689,6 @@ fn build_data_type_completion(dt: &DataTypeInfo) -> CompletionItem {
         label: dt.name.clone(),
         kind: CompletionItemKind::DataType,
         detail,
-        documentation: None,
         has_params: false,
         sort_text: Some(sort_text),
     }
@@ -299,7 +699,6 @@ fn build_table_engine_completion(engine: &TableEngineInfo) -> CompletionItem {
         label: engine.name.clone(),
         kind: CompletionItemKind::TableEngine,
         detail: Some("(table engine)".to_string()),
-        documentation: None,
         has_params: false,
         sort_text: Some(format!("{SORT_PRIORITY_TABLE_ENGINE}{}", engine.name)),
     }
@@ -317,27 +716,16 @@ fn build_format_completion(format: &FormatInfo) -> CompletionItem {
         label: format.name.clone(),
         kind: CompletionItemKind::Format,
         detail: Some(detail.to_string()),
-        documentation: None,
         has_params: false,
         sort_text: Some(format!("{SORT_PRIORITY_FORMAT}{}", format.name)),
     }
 }
 
 fn build_table_function_completion(tf: &TableFunctionInfo) -> CompletionItem {
-    let documentation = if tf.description.is_empty() {
-        None
-    } else {
-        Some(Documentation {
-            kind: "markdown".to_string(),
-            value: tf.description.trim().to_string(),
-        })
-    };
-
     CompletionItem {
         label: tf.name.clone(),
         kind: CompletionItemKind::TableFunction,
         detail: Some("(table function)".to_string()),
-        documentation,
         has_params: true, // Table functions always have parentheses
         sort_text: Some(format!("{SORT_PRIORITY_TABLE_FUNCTION}{}", tf.name)),
     }
@@ -350,31 +738,112 @@ fn build_setting_completion(setting: &SettingInfo, is_merge_tree: bool) -> Compl
         format!("(setting: {})", setting.setting_type)
     };
 
-    let documentation = if setting.description.is_empty() {
-        None
-    } else {
-        Some(Documentation {
-            kind: "markdown".to_string(),
-            value: setting.description.trim().to_string(),
-        })
-    };
-
     CompletionItem {
         label: setting.name.clone(),
         kind: CompletionItemKind::Setting,
         detail: Some(detail),
-        documentation,
         has_params: false,
         sort_text: Some(format!("{SORT_PRIORITY_SETTING}{}", setting.name)),
     }
 }
 
-fn build_completion_cache(data: &ClickHouseData) -> CompletionCache {
+fn build_time_zone_completion(time_zone: &str) -> CompletionItem {
+    CompletionItem {
+        label: time_zone.to_string(),
+        kind: CompletionItemKind::TimeZone,
+        detail: Some("(time zone)".to_string()),
+        has_params: false,
+        sort_text: Some(format!("{SORT_PRIORITY_TIME_ZONE}{time_zone}")),
+    }
+}
+
+fn build_table_completion(database: &DatabaseInfo, table: &TableInfo) -> CompletionItem {
+    CompletionItem {
+        label: table.name.clone(),
+        kind: CompletionItemKind::Table,
+        detail: Some(format!("({} in {})", table.kind.describe(), database.name)),
+        has_params: false,
+        sort_text: Some(format!("{SORT_PRIORITY_SCHEMA}{}", table.name)),
+    }
+}
+
+fn build_column_completion(table: &TableInfo, column: &ColumnInfo) -> CompletionItem {
+    CompletionItem {
+        label: column.name.clone(),
+        kind: CompletionItemKind::Column,
+        detail: Some(if column.column_type.is_empty() {
+            format!("({} column)", table.name)
+        } else {
+            format!("({} column: {})", table.name, column.column_type)
+        }),
+        has_params: false,
+        sort_text: Some(format!("{SORT_PRIORITY_SCHEMA}{}", column.name)),
+    }
+}
+
+/// Documentation for a table or column `label` of the registered schema: the
+/// comments of every table, or every column, of that name
+fn build_schema_documentation(
+    schema: &Schema,
+    label: &str,
+    kind: CompletionItemKind,
+) -> Option<Documentation> {
+    let parts: Vec<String> = match kind {
+        CompletionItemKind::Table => schema
+            .tables()
+            .filter(|(_, table)| table.name == label)
+            .map(|(database, table)| {
+                let mut part = format!("**{}.{}**", database.name, table.name);
+                if let Some(engine) = &table.engine {
+                    part.push_str(" `");
+                    part.push_str(engine);
+                    part.push('`');
+                }
+                if let Some(comment) = &table.comment {
+                    part.push_str("\n\n");
+                    part.push_str(comment.trim());
+                }
+                part
+            })
+            .collect(),
+        CompletionItemKind::Column => schema
+            .tables()
+            .flat_map(|(_, table)| {
+                table
+                    .columns
+                    .iter()
+                    .filter(|column| column.name == label)
+                    .map(move |column| (table, column))
+            })
+            .map(|(table, column)| {
+                let mut part = format!("**{}.{}**", table.name, column.name);
+                if !column.column_type.is_empty() {
+                    part.push_str(" `");
+                    part.push_str(&column.column_type);
+                    part.push('`');
+                }
+                if let Some(comment) = &column.comment {
+                    part.push_str("\n\n");
+                    part.push_str(comment.trim());
+                }
+                part
+            })
+            .collect(),
+        _ => Vec::new(),
+    };
+    (!parts.is_empty()).then(|| Documentation {
+        kind: "markdown".to_string(),
+        value: parts.join("\n\n---\n\n"),
+    })
+}
+
+fn build_completion_cache(index: &ClickHouseIndex) -> CompletionCache {
+    let data = &index.data;
     let mut cache = CompletionCache::default();
 
     // Build function completions
     for func in &data.functions {
-        let item = build_function_completion(func, &data.functions);
+        let item = build_function_completion(func);
         cache.functions.push(item.clone());
         cache.all.push(item);
     }
@@ -426,6 +895,12 @@ fn build_completion_cache(data: &ClickHouseData) -> CompletionCache {
         cache.all.push(item);
     }
 
+    cache.time_zones = data
+        .time_zones
+        .iter()
+        .map(|tz| build_time_zone_completion(tz))
+        .collect();
+
     // Build logical operator completions for WHERE/HAVING context
     let logical_ops = [
         "AND",
@@ -450,298 +925,472 @@ fn build_completion_cache(data: &ClickHouseData) -> CompletionCache {
     cache
 }
 
-/// SQL context detected from tokens before cursor
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum SqlContext {
-    /// After ENGINE = (show table engines)
-    Engine,
-    /// After FORMAT keyword (show formats)
-    Format,
-    /// In WHERE or HAVING clause (show functions + logical operators)
-    WhereClause,
-    /// After ORDER BY or GROUP BY (show functions + ASC/DESC/NULLS)
-    OrderByClause,
-    /// In SELECT before FROM (show functions, DISTINCT, AS)
-    SelectClause,
-    /// After FROM or JOIN (show table functions)
-    FromClause,
-    /// In column definition after CREATE TABLE ( (show data types)
-    ColumnDefinition,
-    /// After SETTINGS keyword (show settings)
-    Settings,
-    /// Unknown/default context (show all)
-    Default,
-}
-
-/// Detects SQL context from tokens before cursor position
-fn detect_context(sql: &str, cursor_offset: usize) -> SqlContext {
-    let sql_before_cursor = if cursor_offset <= sql.len() {
-        &sql[..cursor_offset]
-    } else {
-        sql
-    };
-
-    let dialect = ClickHouseDialect {};
-    let Ok(tokens) = Tokenizer::new(&dialect, sql_before_cursor).tokenize() else {
-        return SqlContext::Default;
-    };
-
-    // Filter out whitespace for easier pattern matching
-    let significant_tokens: Vec<&Token> = tokens
-        .iter()
-        .filter(|t| !matches!(t, Token::Whitespace(_)))
-        .collect();
-
-    if significant_tokens.is_empty() {
-        return SqlContext::Default;
-    }
-
-    // Look at last few tokens to determine context
-    let len = significant_tokens.len();
-
-    // Check for ENGINE = pattern (last token is = or Eq, before that is ENGINE)
-    if len >= 2
-        && matches!(significant_tokens[len - 1], Token::Eq)
-        && is_keyword_token(significant_tokens[len - 2], "ENGINE")
-    {
-        return SqlContext::Engine;
-    }
+/// Outcome of loading `ClickHouse` data into an `Engine`
+#[derive(Serialize, Deserialize, Debug)]
+pub struct InitResult {
+    pub success: bool,
+    pub error: Option<String>,
+}
 
-    // Check for ENGINE = X pattern (we're typing the engine name after =)
-    if len >= 3
-        && matches!(significant_tokens[len - 2], Token::Eq)
-        && is_keyword_token(significant_tokens[len - 3], "ENGINE")
-    {
-        return SqlContext::Engine;
+/// The `limit` completions at `cursor_offset` that best match the word being
+/// typed there, with byte offsets
+fn complete<'a>(
+    cache: &'a CompletionCache,
+    index: Option<&ClickHouseIndex>,
+    schema: &Schema,
+    sql: &str,
+    cursor_offset: usize,
+    limit: usize,
+) -> CompletionList<'a> {
+    let cursor_offset = position::floor_char_boundary(sql, cursor_offset);
+    let context = detect_context(sql, cursor_offset);
+    let word = word::typed_word(sql, cursor_offset, &context);
+
+    let mut items: Vec<RankedCompletion> =
+        completions_at(cache, index, schema, &context, sql, cursor_offset, &word)
+            .into_iter()
+            .filter_map(|item| {
+                let score = fuzzy::score(word.text, &item.label)?;
+                Some(RankedCompletion { item, score })
+            })
+            .collect();
+    items.sort_by(|a, b| {
+        b.score
+            .cmp(&a.score)
+            .then_with(|| a.item.sort_text.cmp(&b.item.sort_text))
+            .then_with(|| a.item.label.cmp(&b.item.label))
+    });
+    let is_incomplete = items.len() > limit;
+    items.truncate(limit);
+
+    CompletionList {
+        items,
+        replace_range: word.range,
+        is_incomplete,
+        error: None,
     }
+}
 
-    // Check if we're right after ENGINE keyword and = follows in remaining text
-    if len >= 1 && is_keyword_token(significant_tokens[len - 1], "ENGINE") {
-        let remaining = &sql[cursor_offset..];
-        if remaining.trim_start().starts_with('=') {
-            return SqlContext::Engine;
+fn completions_at<'a>(
+    cache: &'a CompletionCache,
+    index: Option<&ClickHouseIndex>,
+    schema: &Schema,
+    context: &SqlContext,
+    sql: &str,
+    cursor_offset: usize,
+    word: &word::TypedWord,
+) -> Vec<Cow<'a, CompletionItem>> {
+    let offers_columns = matches!(
+        context,
+        SqlContext::WhereClause | SqlContext::OrderByClause | SqlContext::SelectClause
+    );
+    let offers_tables = *context == SqlContext::FromClause;
+    if offers_columns || offers_tables {
+        // After `e.` or `db.` only what the name before the dot holds fits
+        if let Some(qualifier) = word::qualifier(sql, word.range.start) {
+            return qualified_completions(schema, offers_tables, qualifier, sql, cursor_offset)
+                .into_iter()
+                .map(Cow::Owned)
+                .collect();
         }
     }
 
-    // Check for FORMAT keyword (last significant token)
-    if is_keyword_token(significant_tokens[len - 1], "FORMAT") {
-        return SqlContext::Format;
-    }
-
-    // Check for SETTINGS keyword
-    if is_keyword_token(significant_tokens[len - 1], "SETTINGS") {
-        return SqlContext::Settings;
-    }
-
-    // Scan backwards to find clause context
-    for i in (0..len).rev() {
-        let token = significant_tokens[i];
-
-        // Check for WHERE or HAVING
-        if is_keyword_token(token, "WHERE") || is_keyword_token(token, "HAVING") {
-            // Make sure we haven't passed another major clause
-            if !has_clause_after(
-                &significant_tokens[i + 1..],
-                &["ORDER", "GROUP", "LIMIT", "FORMAT", "SETTINGS"],
-            ) {
-                return SqlContext::WhereClause;
-            }
+    let items: Vec<&CompletionItem> = match context {
+        SqlContext::Engine => cache.table_engines.iter().collect(),
+        SqlContext::Format => cache.formats.iter().collect(),
+        SqlContext::WhereClause => {
+            // Return functions + logical operators
+            cache
+                .functions
+                .iter()
+                .chain(cache.logical_operators.iter())
+                .collect()
         }
-
-        // Check for ORDER BY or GROUP BY
-        if is_keyword_token(token, "BY") && i > 0 {
-            let prev = significant_tokens[i - 1];
-            if (is_keyword_token(prev, "ORDER") || is_keyword_token(prev, "GROUP"))
-                && !has_clause_after(
-                    &significant_tokens[i + 1..],
-                    &["LIMIT", "FORMAT", "SETTINGS", "HAVING", "WHERE"],
-                )
-            {
-                return SqlContext::OrderByClause;
-            }
+        SqlContext::OrderByClause => {
+            // Return functions + ORDER BY keywords
+            cache
+                .functions
+                .iter()
+                .chain(cache.order_by_keywords.iter())
+                .collect()
         }
-
-        // Check for FROM or JOIN
-        if (is_keyword_token(token, "FROM") || is_keyword_token(token, "JOIN"))
-            && !has_clause_after(
-                &significant_tokens[i + 1..],
-                &["WHERE", "GROUP", "ORDER", "LIMIT", "FORMAT", "SETTINGS"],
-            )
-        {
-            return SqlContext::FromClause;
+        SqlContext::SelectClause => cache.functions.iter().collect(),
+        SqlContext::FromClause => cache.table_functions.iter().collect(),
+        SqlContext::ColumnDefinition => cache.data_types.iter().collect(),
+        SqlContext::Settings => cache.settings.iter().collect(),
+        SqlContext::Default => cache.all.iter().collect(),
+        SqlContext::StringArgument { function, argument } => {
+            let kind = index.and_then(|i| values::argument_kind(i, function, *argument));
+            string_values(cache, kind)
         }
-
-        // Check for SELECT (and no FROM yet)
-        if is_keyword_token(token, "SELECT")
-            && !has_clause_after(&significant_tokens[i + 1..], &["FROM"])
-        {
-            return SqlContext::SelectClause;
+        SqlContext::SettingValue { setting } => {
+            string_values(cache, index.and_then(|i| values::setting_kind(i, setting)))
         }
+        SqlContext::Comment | SqlContext::StringLiteral => Vec::new(),
+    };
+    let mut items: Vec<Cow<CompletionItem>> = items.into_iter().map(Cow::Borrowed).collect();
 
-        // Check for CREATE TABLE ... ( pattern for column definitions
-        if matches!(token, Token::LParen)
-            && i >= 2
-            && is_in_create_table_columns(&significant_tokens, i)
-        {
-            return SqlContext::ColumnDefinition;
+    if offers_tables {
+        items.extend(
+            schema
+                .tables()
+                .map(|(database, table)| Cow::Owned(build_table_completion(database, table))),
+        );
+    }
+    if offers_columns {
+        for table in scope::tables_in_scope(sql, cursor_offset) {
+            if let Some(table) = schema.table(table.database.as_deref(), &table.table) {
+                items.extend(
+                    table
+                        .columns
+                        .iter()
+                        .map(|column| Cow::Owned(build_column_completion(table, column))),
+                );
+            }
         }
     }
 
-    SqlContext::Default
+    let offers_functions = matches!(
+        context,
+        SqlContext::WhereClause
+            | SqlContext::OrderByClause
+            | SqlContext::SelectClause
+            | SqlContext::Default
+    );
+    if let (true, Some(index)) = (offers_functions, index) {
+        let word = word::word_before(sql, cursor_offset);
+        items.extend(
+            combinator_completions(index, word)
+                .into_iter()
+                .map(Cow::Owned),
+        );
+    }
+    items
 }
 
-/// Check if we're inside CREATE TABLE column definitions
-fn is_in_create_table_columns(tokens: &[&Token], paren_index: usize) -> bool {
-    // Look back for CREATE TABLE pattern
-    let mut found_table = false;
-    let mut found_create = false;
-    for j in (0..paren_index).rev() {
-        if is_keyword_token(tokens[j], "TABLE") {
-            found_table = true;
-        } else if is_keyword_token(tokens[j], "CREATE") && found_table {
-            found_create = true;
-            break;
-        }
+/// Completions after `qualifier.`: the tables of a database in `FROM`, and
+/// elsewhere the columns of the table the qualifier names. That is a table
+/// in scope at the cursor by its alias or name, or failing that any table of
+/// the schema by name.
+fn qualified_completions(
+    schema: &Schema,
+    offers_tables: bool,
+    qualifier: &str,
+    sql: &str,
+    cursor_offset: usize,
+) -> Vec<CompletionItem> {
+    if offers_tables {
+        return schema
+            .database(qualifier)
+            .map(|database| {
+                database
+                    .tables
+                    .iter()
+                    .map(|table| build_table_completion(database, table))
+                    .collect()
+            })
+            .unwrap_or_default();
     }
 
-    if !found_create || !found_table {
-        return false;
-    }
+    let table = scope::tables_in_scope(sql, cursor_offset)
+        .into_iter()
+        .find(|table| table.is_named(qualifier))
+        .map_or_else(
+            || schema.table(None, qualifier),
+            |table| schema.table(table.database.as_deref(), &table.table),
+        );
+    table
+        .map(|table| {
+            table
+                .columns
+                .iter()
+                .map(|column| build_column_completion(table, column))
+                .collect()
+        })
+        .unwrap_or_default()
+}
 
-    // Make sure we haven't closed the paren
-    let mut paren_depth = 1;
-    for tok in &tokens[paren_index + 1..] {
-        match tok {
-            Token::LParen => paren_depth += 1,
-            Token::RParen => {
-                paren_depth -= 1;
-                if paren_depth == 0 {
-                    return false;
-                }
-            }
-            _ => {}
-        }
+/// Completions for a string literal holding a value of `kind`
+fn string_values(cache: &CompletionCache, kind: Option<ValueKind>) -> Vec<&CompletionItem> {
+    match kind {
+        Some(ValueKind::TimeZone) => cache.time_zones.iter().collect(),
+        Some(ValueKind::Format) => cache.formats.iter().collect(),
+        None => Vec::new(),
     }
-    paren_depth > 0
 }
 
-fn is_keyword_token(token: &Token, keyword: &str) -> bool {
-    matches!(token, Token::Word(w) if w.value.eq_ignore_ascii_case(keyword))
-}
+/// Combinator chains extending `word` that the data does not list: once
+/// `avgMerge` or `avgMergeOr` is typed, offers `avgMergeOrNull` and friends
+fn combinator_completions(index: &ClickHouseIndex, word: &str) -> Vec<CompletionItem> {
+    // The longest prefix of the word that is already an aggregate function
+    let Some((stem, last)) = (1..=word.len())
+        .rev()
+        .filter(|&end| word.is_char_boundary(end))
+        .find_map(|end| {
+            let stem = &word[..end];
+            match index.combinator_form(stem) {
+                Some(form) if form.base.is_aggregate => {
+                    Some((stem, form.combinators.last().copied()))
+                }
+                Some(_) => None,
+                None => index
+                    .function(stem)
+                    .filter(|f| f.is_aggregate && f.alias_to.is_none())
+                    .map(|_| (stem, None)),
+            }
+        })
+    else {
+        return Vec::new();
+    };
 
-fn has_clause_after(tokens: &[&Token], keywords: &[&str]) -> bool {
-    tokens
+    index
+        .data
+        .aggregate_combinators
         .iter()
-        .any(|t| keywords.iter().any(|kw| is_keyword_token(t, kw)))
+        .filter(|c| last != Some(c.as_str()))
+        .map(|c| format!("{stem}{c}"))
+        .filter(|name| name.starts_with(word) && index.function(name).is_none())
+        .filter(|name| index.combinator_form(name).is_some())
+        .map(build_combinator_completion)
+        .collect()
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-pub struct InitResult {
-    pub success: bool,
-    pub error: Option<String>,
+/// Returns the catalog of validation error codes as a JSON array of
+/// `{ code, category, description }` objects.
+#[must_use]
+#[wasm_bindgen]
+pub fn get_error_codes() -> String {
+    serde_json::to_string(&codes::catalog()).unwrap_or_else(|_| "[]".to_string())
 }
 
-/// Initialize completion data. Called once at startup.
-/// Takes `ClickHouse` data as JSON string.
+/// Validates SQL, reporting every syntax error rather than only the first.
+/// Offsets in the result count bytes and columns count chars; see
+/// `validate_sql_with_encoding` for other units. Names are checked only by
+/// `Engine::validate_sql`, against the `ClickHouse` data of a loaded version.
+#[must_use]
 #[wasm_bindgen]
-pub fn init_completion_data(json: &str) -> String {
-    let result = match serde_json::from_str::<ClickHouseData>(json) {
-        Ok(data) => {
-            let cache = build_completion_cache(&data);
-            match COMPLETION_CACHE.set(cache) {
-                Ok(()) => InitResult {
-                    success: true,
-                    error: None,
-                },
-                Err(_) => InitResult {
-                    success: false,
-                    error: Some("Completion data already initialized".to_string()),
-                },
-            }
-        }
-        Err(e) => InitResult {
-            success: false,
-            error: Some(format!("Failed to parse ClickHouse data: {e}")),
-        },
-    };
+pub fn validate_sql(sql: &str) -> String {
+    let result = validate(sql, None, &Schema::default());
 
     serde_json::to_string(&result).unwrap_or_else(|_| {
-        r#"{"success":false,"error":"Internal serialization error"}"#.to_string()
+        r#"{"valid":false,"error":{"message":"Internal serialization error"}}"#.to_string()
     })
 }
 
-/// Get completions for SQL at cursor position.
-/// Returns JSON array of `CompletionItem` objects.
+/// Validates SQL as `validate_sql` does, with offsets and columns in the
+/// result counting `encoding` units: `byte`, `char` or `utf16`
+#[must_use]
 #[wasm_bindgen]
-pub fn get_completions(sql: &str, cursor_offset: usize) -> String {
-    let Some(cache) = COMPLETION_CACHE.get() else {
-        return "[]".to_string();
+pub fn validate_sql_with_encoding(sql: &str, encoding: &str) -> String {
+    validate_sql_with(sql, encoding, None, &Schema::default())
+}
+
+fn validate_sql_with(
+    sql: &str,
+    encoding: &str,
+    index: Option<&ClickHouseIndex>,
+    schema: &Schema,
+) -> String {
+    let result = match OffsetEncoding::from_name(encoding) {
+        Some(encoding) => validate(sql, index, schema).encoded(sql, encoding),
+        None => ValidationResult::invalid_argument(format!("Unknown offset encoding `{encoding}`")),
     };
 
-    let context = detect_context(sql, cursor_offset);
+    serde_json::to_string(&result).unwrap_or_else(|_| {
+        r#"{"valid":false,"error":{"message":"Internal serialization error"}}"#.to_string()
+    })
+}
 
-    let items: Vec<&CompletionItem> = match context {
-        SqlContext::Engine => cache.table_engines.iter().collect(),
-        SqlContext::Format => cache.formats.iter().collect(),
-        SqlContext::WhereClause => {
-            // Return functions + logical operators
-            cache
-                .functions
-                .iter()
-                .chain(cache.logical_operators.iter())
-                .collect()
-        }
-        SqlContext::OrderByClause => {
-            // Return functions + ORDER BY keywords
-            cache
-                .functions
-                .iter()
-                .chain(cache.order_by_keywords.iter())
-                .collect()
+/// Validates a template fragment, parsing it as `mode`: one of `statement`,
+/// `expression`, `condition`, `select_items`, `order_by` or
+/// `column_definitions`. Errors are reported as by `validate_sql`.
+#[must_use]
+#[wasm_bindgen]
+pub fn validate_sql_fragment(sql: &str, mode: &str, encoding: &str) -> String {
+    validate_sql_fragment_with(sql, mode, encoding, None, &Schema::default())
+}
+
+fn validate_sql_fragment_with(
+    sql: &str,
+    mode: &str,
+    encoding: &str,
+    index: Option<&ClickHouseIndex>,
+    schema: &Schema,
+) -> String {
+    let result = match (
+        ParseMode::from_name(mode),
+        OffsetEncoding::from_name(encoding),
+    ) {
+        (Some(mode), Some(encoding)) => {
+            validate_as(sql, mode, index, schema).encoded(sql, encoding)
         }
-        SqlContext::SelectClause => cache.functions.iter().collect(),
-        SqlContext::FromClause => cache.table_functions.iter().collect(),
-        SqlContext::ColumnDefinition => cache.data_types.iter().collect(),
-        SqlContext::Settings => cache.settings.iter().collect(),
-        SqlContext::Default => cache.all.iter().collect(),
+        _ => ValidationResult::invalid_argument(format!(
+            "Unknown parse mode `{mode}` or offset encoding `{encoding}`"
+        )),
     };
 
-    serde_json::to_string(&items).unwrap_or_else(|_| "[]".to_string())
+    serde_json::to_string(&result).unwrap_or_else(|_| {
+        r#"{"valid":false,"error":{"message":"Internal serialization error"}}"#.to_string()
+    })
 }
 
+/// Validates SQL containing placeholders. `holes` is a JSON array of
+/// `{ start, end }` ranges; each is tried as a value, a name, a table and
+/// an expression, and kept as the first that parses. `mode` is as for
+/// `validate_sql_fragment`. Returns the validation result, with errors
+/// positioned in `sql` itself, and a `holes` array giving the role each
+/// placeholder played. Hole ranges, offsets and columns, both given and
+/// returned, count `encoding` units.
 #[must_use]
 #[wasm_bindgen]
-pub fn validate_sql(sql: &str) -> String {
-    let dialect = ClickHouseDialect {};
-    let result = match Parser::parse_sql(&dialect, sql) {
-        Ok(_) => ValidationResult {
-            valid: true,
-            error: None,
-        },
-        Err(e) => {
-            // sqlparser errors include position information
-            // Format: "sql parser error: Expected X, found Y at Line: 1, Column: 5"
-            let message = e.to_string();
-
-            // Parse line/column from error message if available
-            let (line, column) = parse_error_position(&message);
-
-            ValidationResult {
-                valid: false,
-                error: Some(ValidationError {
-                    message,
-                    line,
-                    column,
-                }),
-            }
-        }
+pub fn validate_template(sql: &str, holes: &str, mode: &str, encoding: &str) -> String {
+    validate_template_with(sql, holes, mode, encoding, None, &Schema::default())
+}
+
+fn validate_template_with(
+    sql: &str,
+    holes: &str,
+    mode: &str,
+    encoding: &str,
+    index: Option<&ClickHouseIndex>,
+    schema: &Schema,
+) -> String {
+    let holes: Result<Vec<Hole>, _> = serde_json::from_str(holes);
+    let (Ok(holes), Some(mode), Some(encoding)) = (
+        holes,
+        ParseMode::from_name(mode),
+        OffsetEncoding::from_name(encoding),
+    ) else {
+        let result = TemplateValidationResult {
+            result: ValidationResult::invalid_argument(format!(
+                "Invalid placeholder spans, parse mode `{mode}` or offset encoding `{encoding}`"
+            )),
+            holes: Vec::new(),
+        };
+        return serde_json::to_string(&result).unwrap_or_else(|_| {
+            r#"{"valid":false,"error":{"message":"Internal serialization error"}}"#.to_string()
+        });
     };
+    let lines = LineIndex::new(sql);
+    let holes: Vec<Hole> = holes
+        .iter()
+        .map(|h| Hole {
+            start: lines.byte_offset(h.start, encoding),
+            end: lines.byte_offset(h.end, encoding),
+        })
+        .collect();
+    let mut result = validate_template_as(sql, &holes, mode, index, schema);
+    result.result = result.result.encoded(sql, encoding);
+    for hole in &mut result.holes {
+        hole.start = lines.encoded_offset(hole.start, encoding);
+        hole.end = lines.encoded_offset(hole.end, encoding);
+    }
 
     serde_json::to_string(&result).unwrap_or_else(|_| {
         r#"{"valid":false,"error":{"message":"Internal serialization error"}}"#.to_string()
     })
 }
 
+/// Builds a schema from the `CREATE TABLE` and `CREATE VIEW` statements of
+/// `sources`, a JSON array of SQL strings such as migration files, read in
+/// order. Returns the schema, which `Engine::register_schema` takes, and an
+/// `errors` array of syntax errors and conflicting definitions, each with the
+/// index of its `source`. Offsets and columns count `encoding` units.
+#[must_use]
+#[wasm_bindgen]
+pub fn build_schema_from_ddl(sources: &str, encoding: &str) -> String {
+    let sources: Result<Vec<String>, _> = serde_json::from_str(sources);
+    let (Ok(sources), Some(encoding)) = (sources, OffsetEncoding::from_name(encoding)) else {
+        let error = ValidationError::invalid_argument(format!(
+            "Invalid sources or offset encoding `{encoding}`"
+        ));
+        return serde_json::json!({
+            "schema": { "databases": [] },
+            "errors": [],
+            "error": error,
+        })
+        .to_string();
+    };
+    let mut built = ddl::build_schema(&sources);
+    let lines: Vec<LineIndex> = sources.iter().map(|sql| LineIndex::new(sql)).collect();
+    for error in &mut built.errors {
+        error.error.encode(&lines[error.source], encoding);
+    }
+
+    serde_json::to_string(&built).unwrap_or_else(|_| {
+        r#"{"schema":{"databases":[]},"errors":[],"error":{"message":"Internal serialization error"}}"#
+            .to_string()
+    })
+}
+
+fn validate_template_as(
+    sql: &str,
+    holes: &[Hole],
+    mode: ParseMode,
+    index: Option<&ClickHouseIndex>,
+    schema: &Schema,
+) -> TemplateValidationResult {
+    let holes = template::normalize_holes(sql, holes);
+    let filled = template::fill_holes(sql, &holes, mode);
+    let lines = LineIndex::new(sql);
+    let mut result = validate_as(&filled.sql, mode, index, schema);
+    result.errors = result
+        .errors
+        .into_iter()
+        .map(|e| filled.offsets.map_error(e, &lines))
+        .collect();
+    result.error = result.errors.first().cloned();
+
+    TemplateValidationResult {
+        result,
+        holes: holes
+            .iter()
+            .zip(filled.roles)
+            .map(|(hole, role)| HoleInfo {
+                start: hole.start,
+                end: hole.end,
+                role,
+            })
+            .collect(),
+    }
+}
+
+fn validate(sql: &str, index: Option<&ClickHouseIndex>, schema: &Schema) -> ValidationResult {
+    validate_as(sql, ParseMode::Statement, index, schema)
+}
+
+fn validate_as(
+    sql: &str,
+    mode: ParseMode,
+    index: Option<&ClickHouseIndex>,
+    schema: &Schema,
+) -> ValidationResult {
+    let parsed = recovery::parse_fragment(sql, mode);
+    let mut errors = parsed.errors;
+
+    if let Some(index) = index {
+        let lines = LineIndex::new(sql);
+        let ctx = semantic::CheckContext {
+            index,
+            schema,
+            lines: &lines,
+            tokens: &parsed.tokens,
+            table_settings: &parsed.table_settings,
+        };
+        errors.extend(semantic::check(&parsed.statements, &ctx));
+        if let Some(fragment) = &parsed.fragment {
+            errors.extend(semantic::check_fragment(fragment, &ctx));
+        }
+        errors.sort_by_key(|e| e.start_offset);
+    }
+
+    ValidationResult {
+        valid: errors.is_empty(),
+        error: errors.first().cloned(),
+        errors,
+    }
+}
+
 #[must_use]
 #[wasm_bindgen]
 pub fn format_sql(sql: &str) -> String {
@@ -780,30 +1429,10 @@ pub fn format_sql(sql: &str) -> String {
     })
 }
 
-fn parse_error_position(message: &str) -> (Option<u32>, Option<u32>) {
-    // Parse "at Line: X, Column: Y" from sqlparser error messages
-    let line = message.find("Line: ").and_then(|i| {
-        let start = i + 6;
-        let end = message[start..]
-            .find(',')
-            .map_or(message.len(), |j| start + j);
-        message[start..end].trim().parse().ok()
-    });
-
-    let column = message.find("Column: ").and_then(|i| {
-        let start = i + 8;
-        let end = message[start..]
-            .find(|c: char| !c.is_numeric())
-            .map_or(message.len(), |j| start + j);
-        message[start..end].trim().parse().ok()
-    });
-
-    (line, column)
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::sync::OnceLock;
 
     #[test]
     fn test_valid_select() {
@@ -822,6 +1451,148 @@ mod tests {
         assert!(parsed.error.is_some());
     }
 
+    #[test]
+    fn test_reports_every_error_in_query() {
+        let sql = "SELECT a,, b FROM t WHERE x = = 1";
+        let parsed: ValidationResult =
+            serde_json::from_str(&validate_sql(sql)).expect("valid JSON");
+        assert!(!parsed.valid);
+        assert_eq!(parsed.errors.len(), 2, "{:?}", parsed.errors);
+        assert_eq!(parsed.errors[0].column, Some(10));
+        assert_eq!(parsed.errors[1].column, Some(31));
+        // `error` mirrors the first entry for single-diagnostic callers
+        assert_eq!(
+            parsed.error.map(|e| e.column),
+            Some(parsed.errors[0].column)
+        );
+    }
+
+    #[test]
+    fn test_recovers_after_closing_paren() {
+        let sql = "SELECT f(a,,b), c FROM t WHERE (a = = 1) AND b";
+        let parsed: ValidationResult =
+            serde_json::from_str(&validate_sql(sql)).expect("valid JSON");
+        let columns: Vec<_> = parsed.errors.iter().map(|e| e.column).collect();
+        assert_eq!(columns, vec![Some(12), Some(37)]);
+    }
+
+    #[test]
+    fn test_recovers_at_statement_boundary() {
+        let sql = "SELECT 1 +; SELECT ok; CREATE TABLE t (a UInt64,, b String)";
+        let parsed: ValidationResult =
+            serde_json::from_str(&validate_sql(sql)).expect("valid JSON");
+        assert_eq!(parsed.errors.len(), 2, "{:?}", parsed.errors);
+        assert!(parsed.errors[0].message.contains("EOF"));
+        assert_eq!(parsed.errors[1].column, Some(49));
+    }
+
+    #[test]
+    fn test_error_span_covers_offending_token() {
+        let sql = "SELECT id\nFROM users\nWHERE status = = 'active'";
+        let parsed: ValidationResult =
+            serde_json::from_str(&validate_sql(sql)).expect("valid JSON");
+        let error = parsed.error.expect("has error");
+        let start = error.start_offset.expect("has start offset");
+        let end = error.end_offset.expect("has end offset");
+        assert_eq!(&sql[start..end], "=");
+        assert_eq!(start, sql.rfind("= '").expect("second ="));
+        assert_eq!((error.line, error.column), (Some(3), Some(16)));
+        assert_eq!((error.end_line, error.end_column), (Some(3), Some(17)));
+    }
+
+    #[test]
+    fn test_error_span_is_whole_token() {
+        let sql = "SELECT * FROM users WHERE id = 1 ORDER BY name DESCENDING";
+        let parsed: ValidationResult =
+            serde_json::from_str(&validate_sql(sql)).expect("valid JSON");
+        let error = parsed.error.expect("has error");
+        let range = error.start_offset.zip(error.end_offset).expect("has range");
+        assert_eq!(&sql[range.0..range.1], "DESCENDING");
+    }
+
+    #[test]
+    fn test_error_at_end_of_input_points_at_last_token() {
+        let sql = "SELECT * FROM";
+        let parsed: ValidationResult =
+            serde_json::from_str(&validate_sql(sql)).expect("valid JSON");
+        let error = parsed.error.expect("has error");
+        assert_eq!(error.start_offset, Some(9));
+        assert_eq!(error.end_offset, Some(13));
+    }
+
+    #[test]
+    fn test_tokenizer_error_span() {
+        let sql = "SELECT 'unterminated";
+        let parsed: ValidationResult =
+            serde_json::from_str(&validate_sql(sql)).expect("valid JSON");
+        let error = parsed.error.expect("has error");
+        assert_eq!(error.start_offset, Some(7));
+        assert_eq!(error.end_offset, Some(8));
+    }
+
+    fn first_error_code(sql: &str) -> ErrorCode {
+        let parsed: ValidationResult =
+            serde_json::from_str(&validate_sql(sql)).expect("valid JSON");
+        parsed.error.expect("has error").code
+    }
+
+    #[test]
+    fn test_syntax_error_codes() {
+        assert_eq!(
+            first_error_code("SELCT * FROM users"),
+            ErrorCode::UnexpectedToken
+        );
+        assert_eq!(first_error_code("SELECT * FROM"), ErrorCode::UnexpectedEnd);
+        assert_eq!(
+            first_error_code("SELECT count(x FROM t"),
+            ErrorCode::UnclosedParen
+        );
+        assert_eq!(first_error_code("SELECT (1 + 2"), ErrorCode::UnclosedParen);
+        assert_eq!(
+            first_error_code("SELECT a) FROM t"),
+            ErrorCode::UnmatchedParen
+        );
+        assert_eq!(
+            first_error_code("SELECT 'abc"),
+            ErrorCode::UnterminatedString
+        );
+        assert_eq!(
+            first_error_code("SELECT `abc"),
+            ErrorCode::UnterminatedIdentifier
+        );
+        assert_eq!(
+            first_error_code("SELECT 1 /* note"),
+            ErrorCode::UnterminatedComment
+        );
+    }
+
+    #[test]
+    fn test_error_json_has_code_and_category() {
+        let result = validate_sql("SELECT * FROM");
+        let json: serde_json::Value = serde_json::from_str(&result).expect("valid JSON");
+        assert_eq!(json["errors"][0]["code"], "syntax/unexpected-end");
+        assert_eq!(json["errors"][0]["category"], "syntax");
+    }
+
+    #[test]
+    fn test_get_error_codes_catalog() {
+        let json: serde_json::Value = serde_json::from_str(&get_error_codes()).expect("valid JSON");
+        let entries = json.as_array().expect("array");
+        assert_eq!(entries.len(), ErrorCode::ALL.len());
+        assert!(entries
+            .iter()
+            .any(|e| e["code"] == "syntax/unclosed-paren" && e["category"] == "syntax"));
+    }
+
+    #[test]
+    fn test_valid_multi_statement() {
+        let sql = "SELECT 1; SELECT * FROM t WHERE x = 1 FORMAT JSON;";
+        let parsed: ValidationResult =
+            serde_json::from_str(&validate_sql(sql)).expect("valid JSON");
+        assert!(parsed.valid, "{:?}", parsed.errors);
+        assert!(parsed.errors.is_empty());
+    }
+
     #[test]
     fn test_clickhouse_materialized_view() {
         let sql = "CREATE MATERIALIZED VIEW mv AS SELECT * FROM source";
@@ -874,103 +1645,412 @@ mod tests {
         assert!(formatted.contains("_ph_4"), "Should preserve _ph_4");
     }
 
-    // Context detection tests
+    fn test_completions(sql: &str) -> Vec<CompletionItem> {
+        static CACHE: OnceLock<CompletionCache> = OnceLock::new();
+        let index = clickhouse_index::test_data::clickhouse_25_8();
+        let cache = CACHE.get_or_init(|| build_completion_cache(index));
+        complete(
+            cache,
+            Some(index),
+            &Schema::default(),
+            sql,
+            sql.len(),
+            usize::MAX,
+        )
+        .items
+        .into_iter()
+        .map(|ranked| ranked.item.into_owned())
+        .collect()
+    }
+
+    fn find<'a>(items: &'a [CompletionItem], label: &str) -> Option<&'a CompletionItem> {
+        items.iter().find(|item| item.label == label)
+    }
+
     #[test]
-    fn test_detect_context_engine() {
-        assert_eq!(
-            detect_context("CREATE TABLE t ENGINE = ", 24),
-            SqlContext::Engine
-        );
-        assert_eq!(
-            detect_context("CREATE TABLE t ENGINE =", 23),
-            SqlContext::Engine
-        );
-        assert_eq!(detect_context("ENGINE = M", 10), SqlContext::Engine);
+    fn test_combinator_completions() {
+        let items = test_completions("SELECT sum");
+        assert!(find(&items, "sumIf").is_some());
+
+        let items = test_completions("SELECT uniqExact");
+        assert!(find(&items, "uniqExactState").is_some());
+
+        // Chains are offered once their start is typed
+        let items = test_completions("SELECT avgMergeOr");
+        let chain = find(&items, "avgMergeOrNull").expect("chain offered");
+        assert_eq!(chain.kind, CompletionItemKind::AggregateFunction);
+        assert!(find(&items, "avgMergeOrDefault").is_some());
+        assert!(find(&items, "avgMergeIf").is_none());
+        assert!(find(&items, "avgMergeMerge").is_none());
+
+        // Not for non-aggregates or outside expressions
+        assert!(find(&test_completions("SELECT lengthOr"), "lengthOrNull").is_none());
+        assert!(find(
+            &test_completions("CREATE TABLE t (a UInt8) ENGINE = avgMerge"),
+            "avgMergeOrNull"
+        )
+        .is_none());
     }
 
     #[test]
-    fn test_detect_context_format() {
-        assert_eq!(
-            detect_context("SELECT * FROM t FORMAT ", 23),
-            SqlContext::Format
-        );
-        assert_eq!(detect_context("SELECT * FORMAT ", 16), SqlContext::Format);
+    fn test_completions_in_comments_and_strings() {
+        assert!(test_completions("SELECT 1 -- a comment about co").is_empty());
+        assert!(test_completions("SELECT 'some string").is_empty());
+
+        let items = test_completions("SELECT toDateTime(ts, 'Europe/");
+        assert!(find(&items, "Europe/Berlin").is_some());
+        assert!(items.iter().all(|i| i.kind == CompletionItemKind::TimeZone));
+
+        let items = test_completions("SELECT * FROM file('日本/🦀.csv', '");
+        assert!(find(&items, "CSVWithNames").is_some());
+        assert!(items.iter().all(|i| i.kind == CompletionItemKind::Format));
+
+        let items = test_completions("SELECT now() SETTINGS session_timezone = '");
+        assert!(find(&items, "UTC").is_some());
     }
 
     #[test]
-    fn test_detect_context_where() {
-        assert_eq!(
-            detect_context("SELECT * FROM t WHERE ", 22),
-            SqlContext::WhereClause
-        );
-        assert_eq!(
-            detect_context("SELECT * FROM t WHERE x = 1 AND ", 32),
-            SqlContext::WhereClause
-        );
+    fn test_completions_are_ranked_by_the_typed_word() {
+        let index = clickhouse_index::test_data::clickhouse_25_8();
+        let cache = build_completion_cache(index);
+        let sql = "SELECT tSOH";
+        let list = complete(&cache, Some(index), &Schema::default(), sql, sql.len(), 5);
+        assert_eq!(list.items[0].item.label, "toStartOfHour");
+        assert_eq!(list.items.len(), 5);
+        assert!(list.is_incomplete);
+        assert!(list.items.windows(2).all(|w| w[0].score >= w[1].score));
+        assert_eq!(list.replace_range, TextRange { start: 7, end: 11 });
+
+        let sql = "SELECT count(*) FROM t WHERE x = 1";
+        let list = complete(&cache, Some(index), &Schema::default(), sql, 10, 100);
+        assert_eq!(list.items[0].item.label, "count");
+        // The whole word is replaced, not only what is before the cursor
+        assert_eq!(list.replace_range, TextRange { start: 7, end: 12 });
+
+        let sql = "SELECT toDateTime(ts, 'Europe/Ber";
+        let list = complete(&cache, Some(index), &Schema::default(), sql, sql.len(), 100);
+        assert_eq!(list.items[0].item.label, "Europe/Berlin");
+        assert_eq!(list.replace_range.start, sql.len() - "Europe/Ber".len());
     }
 
     #[test]
-    fn test_detect_context_having() {
-        assert_eq!(
-            detect_context("SELECT * FROM t GROUP BY x HAVING ", 34),
-            SqlContext::WhereClause
-        );
+    fn test_completion_range_in_utf16() {
+        let index = clickhouse_index::test_data::clickhouse_25_8();
+        let cache = build_completion_cache(index);
+        let sql = "SELECT '🦀', toStartOfH";
+        let list = complete(&cache, Some(index), &Schema::default(), sql, sql.len(), 10)
+            .encoded(sql, OffsetEncoding::Utf16);
+        assert_eq!(list.items[0].item.label, "toStartOfHour");
+        assert_eq!(list.replace_range, TextRange { start: 13, end: 23 });
     }
 
     #[test]
-    fn test_detect_context_order_by() {
+    fn test_combinator_documentation() {
+        let index = clickhouse_index::test_data::clickhouse_25_8();
+        let resolve = |label: &str| {
+            build_completion_documentation(index, label, CompletionItemKind::AggregateFunction)
+                .expect("documented")
+                .value
+        };
+
+        assert!(find(&test_completions("SELECT count"), "countIf").is_some());
+        let doc = resolve("countIf");
+        assert!(doc.contains("Applies the `If` combinator to the aggregate function `count`."));
+        assert!(doc.contains(combinators::describe("If")));
+
+        assert!(find(&test_completions("SELECT avgMerge"), "avgMergeOrNull").is_some());
+        let doc = resolve("avgMergeOrNull");
+        assert!(doc.contains("the `Merge` and `OrNull` combinators, in that order,"));
+        assert!(doc.contains(combinators::describe("OrNull")));
+    }
+
+    #[test]
+    fn test_completion_items_are_resolved_lazily() {
+        let index = clickhouse_index::test_data::clickhouse_25_8();
+        let items = test_completions("SELECT toStartOfHou");
+        let item = find(&items, "toStartOfHour").expect("listed");
+        let json = serde_json::to_value(item).expect("serializes");
+        assert!(json.get("documentation").is_none());
+
+        let resolve = |label, kind| build_completion_documentation(index, label, kind);
+        let doc = resolve("toStartOfHour", item.kind).expect("documented");
+        assert!(doc.value.contains("**Syntax:**"));
+        // Aliases carry the documentation of their target
+        let alias = index
+            .data
+            .functions
+            .iter()
+            .find(|f| f.alias_to.is_some())
+            .expect("an alias");
+        let doc = resolve(&alias.name, CompletionItemKind::Function).expect("documented");
+        assert!(doc.value.contains("_(alias for `"));
+        assert!(resolve("max_threads", CompletionItemKind::Setting).is_some());
+        assert!(resolve("SELECT", CompletionItemKind::Keyword).is_none());
+        assert!(resolve("noSuchFunction", CompletionItemKind::Function).is_none());
         assert_eq!(
-            detect_context("SELECT * FROM t ORDER BY ", 25),
-            SqlContext::OrderByClause
+            CompletionItemKind::from_name("time_zone"),
+            Some(CompletionItemKind::TimeZone)
         );
+    }
+
+    #[test]
+    fn test_context_at_utf16_cursor() {
+        let sql = "-- 日本語 🦀\nSELECT '🦀' FROM t WHERE x = 1";
+        let utf16 = |text: &str| text.encode_utf16().count();
+        let context_at = |cursor| {
+            let cursor = LineIndex::new(sql).byte_offset(cursor, OffsetEncoding::Utf16);
+            detect_context(sql, cursor)
+        };
+
+        // Just after `WHERE `, which as a byte offset would still be in FROM
+        let after_where = utf16("-- 日本語 🦀\nSELECT '🦀' FROM t WHERE ");
+        assert_eq!(context_at(after_where), SqlContext::WhereClause);
         assert_eq!(
-            detect_context("SELECT * FROM t ORDER BY x ", 27),
-            SqlContext::OrderByClause
+            detect_context(sql, after_where),
+            SqlContext::SelectClause,
+            "{after_where} read as bytes lands earlier"
         );
+        assert_eq!(context_at(utf16(sql)), SqlContext::WhereClause);
+
+        // Offsets inside a char or past the end do not panic
+        for cursor in 0..sql.len() + 4 {
+            let _ = detect_context(sql, cursor);
+            let _ = word::word_before(sql, cursor);
+        }
     }
 
     #[test]
-    fn test_detect_context_group_by() {
+    fn test_validation_positions_in_each_encoding() {
+        let sql = "SELECT '日本🦀', count(x FROM t";
+        let from = sql.find("FROM").expect("FROM present");
+        let error_at = |encoding| {
+            let result: ValidationResult =
+                serde_json::from_str(&validate_sql_with_encoding(sql, encoding))
+                    .expect("valid JSON");
+            let error = result.error.expect("an error");
+            (error.start_offset, error.column)
+        };
+
+        assert_eq!(error_at("byte"), (Some(from), Some(30)));
+        assert_eq!(error_at("char"), (Some(22), Some(23)));
+        assert_eq!(error_at("utf16"), (Some(23), Some(24)));
+
+        // Without an encoding, offsets count bytes and columns chars
+        let result: ValidationResult = serde_json::from_str(&validate_sql(sql)).expect("json");
+        let error = result.error.expect("an error");
+        assert_eq!((error.start_offset, error.column), (Some(from), Some(23)));
+
+        let result: ValidationResult =
+            serde_json::from_str(&validate_sql_with_encoding(sql, "utf8")).expect("json");
+        let error = result.error.expect("error");
+        assert_eq!(error.code, ErrorCode::InvalidArgument);
+        assert_eq!(error.category, ErrorCategory::Usage);
+        assert_eq!(error.message, "Unknown offset encoding `utf8`");
+
+        let built: serde_json::Value =
+            serde_json::from_str(&build_schema_from_ddl("[]", "utf8")).expect("json");
+        assert_eq!(built["error"]["code"], "usage/invalid-argument");
+        assert_eq!(built["error"]["category"], "usage");
+    }
+
+    #[test]
+    fn test_template_holes_in_utf16() {
+        let hole = |sql: &str, text: &str| {
+            let start = sql[..sql.find(text).expect("hole present")]
+                .encode_utf16()
+                .count();
+            format!(r#"{{"start":{start},"end":{}}}"#, start + text.len())
+        };
+        let sql = "SELECT '🦀', ${col} FROM ${table}";
+        let holes = format!("[{},{}]", hole(sql, "${col}"), hole(sql, "${table}"));
+        let json = validate_template(sql, &holes, "statement", "utf16");
+        let result: TemplateValidationResult = serde_json::from_str(&json).expect("json");
+        let roles: Vec<Option<HoleRole>> = result.holes.iter().map(|h| h.role).collect();
         assert_eq!(
-            detect_context("SELECT * FROM t GROUP BY ", 25),
-            SqlContext::OrderByClause
+            roles,
+            vec![Some(HoleRole::Expression), Some(HoleRole::Table)]
         );
+        assert_eq!((result.holes[0].start, result.holes[0].end), (13, 19));
+
+        let sql = "SELECT '🦀' FROM ${table} WHERE = 1";
+        let holes = format!("[{}]", hole(sql, "${table}"));
+        let json = validate_template(sql, &holes, "statement", "utf16");
+        let result: TemplateValidationResult = serde_json::from_str(&json).expect("json");
+        let error = result.result.error.expect("an error");
+        let equals = sql[..sql.find("= 1").expect("= present")]
+            .encode_utf16()
+            .count();
+        assert_eq!(error.start_offset, Some(equals));
     }
 
     #[test]
-    fn test_detect_context_select() {
-        assert_eq!(detect_context("SELECT ", 7), SqlContext::SelectClause);
-        assert_eq!(detect_context("SELECT x, ", 10), SqlContext::SelectClause);
+    fn test_fragment_modes_accept_their_fragments() {
+        let cases = [
+            (ParseMode::Condition, "status = 'active' AND ts > _ph_1"),
+            (ParseMode::Expression, "price * quantity"),
+            (ParseMode::SelectItems, "id, count() AS n, t.*"),
+            (ParseMode::OrderBy, "ts DESC, id ASC NULLS LAST"),
+            (
+                ParseMode::ColumnDefinitions,
+                "id UInt64, name LowCardinality(String) DEFAULT ''",
+            ),
+            (ParseMode::Statement, "SELECT 1; SELECT 2"),
+            (ParseMode::Condition, "  "),
+        ];
+        for (mode, sql) in cases {
+            let result = validate_as(
+                sql,
+                mode,
+                Some(clickhouse_index::test_data::clickhouse_25_8()),
+                &Schema::default(),
+            );
+            assert!(result.valid, "{mode:?} {sql}: {:?}", result.errors);
+        }
+        // Fragments are not statements
+        assert!(!validate("status = 'active'", None, &Schema::default()).valid);
     }
 
     #[test]
-    fn test_detect_context_from() {
-        assert_eq!(detect_context("SELECT * FROM ", 14), SqlContext::FromClause);
+    fn test_fragment_errors_are_reported_like_statement_errors() {
+        let result = validate_as("price *", ParseMode::Expression, None, &Schema::default());
+        assert_eq!(result.errors.len(), 1);
+        assert_eq!(result.errors[0].code, ErrorCode::UnexpectedEnd);
+        assert_eq!(result.errors[0].start_offset, Some(6));
+
+        let result = validate_as("ts DESC id", ParseMode::OrderBy, None, &Schema::default());
+        assert_eq!(result.errors[0].code, ErrorCode::UnexpectedToken);
+        assert_eq!(result.errors[0].start_offset, Some(8));
+        assert!(result.errors[0].message.contains("end of fragment"));
+
+        let result = validate_as("a = (1", ParseMode::Condition, None, &Schema::default());
+        assert_eq!(result.errors[0].code, ErrorCode::UnclosedParen);
     }
 
     #[test]
-    fn test_detect_context_settings() {
-        assert_eq!(
-            detect_context("SELECT * FROM t SETTINGS ", 25),
-            SqlContext::Settings
+    fn test_fragments_get_semantic_checks() {
+        let index = Some(clickhouse_index::test_data::clickhouse_25_8());
+        let result = validate_as(
+            "toStartOfHourr(ts) > now()",
+            ParseMode::Condition,
+            index,
+            &Schema::default(),
+        );
+        let codes: Vec<ErrorCode> = result.errors.iter().map(|e| e.code).collect();
+        assert_eq!(codes, vec![ErrorCode::UnknownFunction]);
+
+        let result = validate_as(
+            "id UInt64, name Strng",
+            ParseMode::ColumnDefinitions,
+            index,
+            &Schema::default(),
         );
+        let codes: Vec<ErrorCode> = result.errors.iter().map(|e| e.code).collect();
+        assert_eq!(codes, vec![ErrorCode::UnknownDataType]);
+        assert_eq!(result.errors[0].start_offset, Some(16));
     }
 
     #[test]
-    fn test_detect_context_default() {
-        assert_eq!(detect_context("", 0), SqlContext::Default);
-        assert_eq!(detect_context("SEL", 3), SqlContext::Default);
+    fn test_validate_sql_fragment_modes() {
+        assert_eq!(
+            ParseMode::from_name("select_items"),
+            Some(ParseMode::SelectItems)
+        );
+        assert_eq!(ParseMode::from_name("fragment"), None);
+
+        let result: ValidationResult =
+            serde_json::from_str(&validate_sql_fragment("a AND", "condition", "byte"))
+                .expect("json");
+        assert!(!result.valid);
+
+        let result: ValidationResult =
+            serde_json::from_str(&validate_sql_fragment("a", "fragment", "byte")).expect("json");
+        assert_eq!(result.errors[0].code, ErrorCode::InvalidArgument);
+        assert!(result.errors[0]
+            .message
+            .contains("Unknown parse mode `fragment`"));
     }
 
     #[test]
-    fn test_detect_context_column_definition() {
-        assert_eq!(
-            detect_context("CREATE TABLE t (id ", 19),
-            SqlContext::ColumnDefinition
+    fn test_validate_template_reports_hole_roles() {
+        let sql = "SELECT * FROM _ph_1 WHERE x IN _ph_2 LIMIT _ph_3";
+        assert!(!validate(sql, None, &Schema::default()).valid);
+
+        let json = validate_template(
+            sql,
+            r#"[{"start":14,"end":19},{"start":31,"end":36},{"start":43,"end":48}]"#,
+            "statement",
+            "byte",
         );
+        let result: TemplateValidationResult = serde_json::from_str(&json).expect("json");
+        assert!(result.result.valid, "{:?}", result.result.errors);
+        let roles: Vec<Option<HoleRole>> = result.holes.iter().map(|h| h.role).collect();
         assert_eq!(
-            detect_context("CREATE TABLE t (id UInt64, name ", 32),
-            SqlContext::ColumnDefinition
+            roles,
+            vec![
+                Some(HoleRole::Table),
+                Some(HoleRole::Expression),
+                Some(HoleRole::Value)
+            ]
         );
+        assert!(json.contains(r#""role":"table""#));
+    }
+
+    #[test]
+    fn test_template_errors_keep_their_positions() {
+        let sql = "SELECT _ph_1 FROM t WHERE x IN _ph_2 AND = 1";
+        let holes = [Hole { start: 7, end: 12 }, Hole { start: 31, end: 36 }];
+        let result =
+            validate_template_as(sql, &holes, ParseMode::Statement, None, &Schema::default());
+        assert_eq!(result.result.errors.len(), 1);
+        assert_eq!(result.result.errors[0].start_offset, Some(41));
+
+        let json = validate_template("SELECT 1", "[oops]", "statement", "byte");
+        let result: ValidationResult = serde_json::from_str(&json).expect("json");
+        assert_eq!(result.errors[0].code, ErrorCode::InvalidArgument);
+        assert!(result.errors[0]
+            .message
+            .contains("Invalid placeholder spans"));
+    }
+
+    #[test]
+    fn test_template_errors_are_mapped_back_through_holes() {
+        // `_ph_1` is shorter than the hole, so the call after it moves left
+        let sql = "SELECT ${someLongExpression}, toStartOfHuor(ts) FROM t";
+        let holes = [Hole { start: 7, end: 28 }];
+        let index = clickhouse_index::test_data::clickhouse_25_8();
+        let result = validate_template_as(
+            sql,
+            &holes,
+            ParseMode::Statement,
+            Some(index),
+            &Schema::default(),
+        );
+        let error = result.result.error.expect("an error");
+        assert_eq!(error.code, ErrorCode::UnknownFunction);
+        assert_eq!(error.start_offset, Some(30));
+        assert_eq!(error.end_offset, Some(43));
+        assert_eq!((error.line, error.column), (Some(1), Some(31)));
+
+        // A hole spanning lines is filled on one, and lines after it move up
+        let sql = "SELECT a\nFROM ${\n  table\n} WHERE = 1";
+        let holes = [Hole { start: 14, end: 27 }];
+        let result =
+            validate_template_as(sql, &holes, ParseMode::Statement, None, &Schema::default());
+        let error = result.result.error.expect("an error");
+        assert_eq!(error.start_offset, Some(33));
+        assert_eq!((error.line, error.column), (Some(4), Some(9)));
+        assert_eq!((error.end_line, error.end_column), (Some(4), Some(10)));
+    }
+
+    #[test]
+    fn test_errors_inside_holes_cover_the_whole_hole() {
+        // No stand-in fits after a name, so the error lands on the hole
+        let sql = "SELECT * FROM t ORDER BY x ${dir}";
+        let holes = [Hole { start: 27, end: 33 }];
+        let result =
+            validate_template_as(sql, &holes, ParseMode::Statement, None, &Schema::default());
+        let error = result.result.error.expect("an error");
+        assert_eq!(error.start_offset, Some(27));
+        assert_eq!(error.end_offset, Some(33));
     }
 }
@@ -0,0 +1,210 @@
+//! Conversions between sqlparser's line/column locations and byte offsets.
+//!
+//! The tokenizer reports 1-based lines and 1-based columns counted in chars,
+//! with only `\n` starting a new line. `LineIndex` mirrors those rules so a
+//! token's `Span` can be turned into an exact byte range of the input.
+//!
+//! Callers outside Rust count in other units: JavaScript strings index UTF-16
+//! code units. Offsets crossing the wasm boundary are converted between bytes
+//! and the caller's `OffsetEncoding` here, so nothing else slices the input
+//! with an offset that is not a byte offset on a char boundary.
+
+use crate::OffsetEncoding;
+use sqlparser::tokenizer::{Location, Span};
+
+#[derive(Debug)]
+pub(crate) struct LineIndex<'a> {
+    text: &'a str,
+    /// Byte offset at which each line starts; `line_starts[0]` is always 0
+    line_starts: Vec<usize>,
+}
+
+impl<'a> LineIndex<'a> {
+    pub fn new(text: &'a str) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        Self { text, line_starts }
+    }
+
+    /// Byte offset of a tokenizer location, clamped to the end of its line.
+    /// Empty locations (line 0) map to the end of the text.
+    pub fn offset(&self, location: Location) -> usize {
+        let Some(line_start) = usize::try_from(location.line)
+            .ok()
+            .and_then(|line| line.checked_sub(1))
+            .and_then(|line| self.line_starts.get(line).copied())
+        else {
+            return self.text.len();
+        };
+
+        let line_text = self.text[line_start..]
+            .split('\n')
+            .next()
+            .unwrap_or_default();
+        let chars_before = usize::try_from(location.column.saturating_sub(1)).unwrap_or(usize::MAX);
+        let within_line = line_text
+            .char_indices()
+            .nth(chars_before)
+            .map_or(line_text.len(), |(i, _)| i);
+
+        line_start + within_line
+    }
+
+    /// Tokenizer-style location (1-based line and char column) of a byte offset
+    pub fn location(&self, offset: usize) -> Location {
+        let offset = self.floor_char_boundary(offset);
+        let line = self
+            .line_starts
+            .partition_point(|&start| start <= offset)
+            .saturating_sub(1);
+        let line_start = self.line_starts.get(line).copied().unwrap_or_default();
+        let column = self.text[line_start..offset].chars().count() + 1;
+
+        Location::new(line as u64 + 1, column as u64)
+    }
+
+    /// Byte range covered by a span; an empty or inverted span yields a zero-width range
+    pub fn range(&self, span: Span) -> (usize, usize) {
+        let start = self.offset(span.start);
+        let end = self.offset(span.end).max(start);
+        (start, end)
+    }
+
+    /// Byte offset just past the char starting at `offset`, for widening a
+    /// point location into a one-character range
+    pub fn next_char_end(&self, offset: usize) -> usize {
+        self.text[offset.min(self.text.len())..]
+            .chars()
+            .next()
+            .map_or(self.text.len(), |c| offset + c.len_utf8())
+    }
+
+    /// Byte offset of an `encoding` offset. Offsets past the end clamp to it
+    /// and offsets inside a char move back to its start.
+    pub fn byte_offset(&self, offset: usize, encoding: OffsetEncoding) -> usize {
+        match encoding {
+            OffsetEncoding::Byte => floor_char_boundary(self.text, offset),
+            OffsetEncoding::Char => self
+                .text
+                .char_indices()
+                .nth(offset)
+                .map_or(self.text.len(), |(i, _)| i),
+            OffsetEncoding::Utf16 => {
+                let mut units = 0;
+                for (i, c) in self.text.char_indices() {
+                    units += c.len_utf16();
+                    if units > offset {
+                        return i;
+                    }
+                }
+                self.text.len()
+            }
+        }
+    }
+
+    /// `offset`, a byte offset, counted in `encoding` units
+    pub fn encoded_offset(&self, offset: usize, encoding: OffsetEncoding) -> usize {
+        encoding.len(&self.text[..floor_char_boundary(self.text, offset)])
+    }
+
+    /// 1-based column of a byte offset, counted in `encoding` units
+    pub fn encoded_column(&self, offset: usize, encoding: OffsetEncoding) -> u64 {
+        let offset = floor_char_boundary(self.text, offset);
+        let line = self.line_starts.partition_point(|&start| start <= offset);
+        let line_start = self
+            .line_starts
+            .get(line.saturating_sub(1))
+            .copied()
+            .unwrap_or_default();
+        encoding.len(&self.text[line_start..offset]) as u64 + 1
+    }
+
+    fn floor_char_boundary(&self, offset: usize) -> usize {
+        floor_char_boundary(self.text, offset)
+    }
+}
+
+impl OffsetEncoding {
+    /// Length of `text` in this encoding's units
+    fn len(self, text: &str) -> usize {
+        match self {
+            OffsetEncoding::Byte => text.len(),
+            OffsetEncoding::Char => text.chars().count(),
+            OffsetEncoding::Utf16 => text.encode_utf16().count(),
+        }
+    }
+}
+
+/// The largest char boundary of `text` at or before `offset`
+pub(crate) fn floor_char_boundary(text: &str, offset: usize) -> usize {
+    let mut offset = offset.min(text.len());
+    while !text.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    offset
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_offset_round_trip() {
+        let text = "SELECT\n  'é' AS x,\n  y";
+        let index = LineIndex::new(text);
+
+        let y = text.rfind('y').expect("y present");
+        assert_eq!(index.location(y), Location::new(3, 3));
+        assert_eq!(index.offset(Location::new(3, 3)), y);
+
+        // Column counts chars, not bytes
+        let as_kw = text.find("AS").expect("AS present");
+        assert_eq!(index.location(as_kw), Location::new(2, 7));
+        assert_eq!(index.offset(Location::new(2, 7)), as_kw);
+    }
+
+    #[test]
+    fn test_offset_clamps_out_of_range() {
+        let index = LineIndex::new("SELECT 1");
+        assert_eq!(index.offset(Location::new(1, 99)), 8);
+        assert_eq!(index.offset(Location::new(5, 1)), 8);
+        assert_eq!(index.offset(Location::empty()), 8);
+    }
+
+    #[test]
+    fn test_encodings_round_trip() {
+        // 'é' is 2 bytes, '日本' 3 bytes each, '🦀' 4 bytes and 2 UTF-16 units
+        let text = "SELECT 'é日本🦀' AS x";
+        let index = LineIndex::new(text);
+        let as_kw = text.find("AS").expect("AS present");
+
+        assert_eq!(index.encoded_offset(as_kw, OffsetEncoding::Byte), 22);
+        assert_eq!(index.encoded_offset(as_kw, OffsetEncoding::Char), 14);
+        assert_eq!(index.encoded_offset(as_kw, OffsetEncoding::Utf16), 15);
+        for encoding in [
+            OffsetEncoding::Byte,
+            OffsetEncoding::Char,
+            OffsetEncoding::Utf16,
+        ] {
+            let encoded = index.encoded_offset(as_kw, encoding);
+            assert_eq!(index.byte_offset(encoded, encoding), as_kw);
+        }
+        assert_eq!(index.encoded_column(as_kw, OffsetEncoding::Utf16), 16);
+    }
+
+    #[test]
+    fn test_offsets_inside_a_char_move_to_its_start() {
+        let text = "-- 🦀\n日";
+        let index = LineIndex::new(text);
+        let crab = text.find('🦀').expect("crab present");
+        // Between the two UTF-16 units of the surrogate pair
+        assert_eq!(index.byte_offset(4, OffsetEncoding::Utf16), crab);
+        assert_eq!(index.byte_offset(crab + 2, OffsetEncoding::Byte), crab);
+        assert_eq!(index.byte_offset(99, OffsetEncoding::Char), text.len());
+        assert_eq!(
+            index.encoded_column(text.len() - 1, OffsetEncoding::Byte),
+            1
+        );
+    }
+}
@@ -0,0 +1,778 @@
+//! Error-recovering parser used by `validate_sql`.
+//!
+//! `Parser::parse_sql` gives up at the first error. Here the input is split into
+//! statements at `;`, each statement is parsed on its own, and after a failure
+//! inside a query the parser resumes at the next clause boundary (`FROM`,
+//! `WHERE`, `GROUP BY`, ...) or after the closing paren that ends the broken
+//! sub-expression. Every independent mistake is reported in a single pass.
+//!
+//! Fragments (`sql.fragment` templates) are parsed whole with the parser
+//! method for their `ParseMode` and report their first error.
+
+use crate::position::LineIndex;
+use crate::{ErrorCode, ParseMode, ValidationError};
+use sqlparser::ast::{
+    ColumnDef, CreateTable, CreateTableOptions, Expr, OrderByExpr, SelectItem, Setting, SqlOption,
+    Statement,
+};
+use sqlparser::dialect::ClickHouseDialect;
+use sqlparser::keywords::Keyword;
+use sqlparser::parser::{Parser, ParserError};
+use sqlparser::tokenizer::{Location, Span, Token, TokenWithSpan, Tokenizer, Whitespace};
+use std::borrow::Cow;
+
+/// Keywords that start a clause which can be parsed independently of whatever
+/// came before it in the same query
+const CLAUSE_KEYWORDS: &[Keyword] = &[
+    Keyword::FROM,
+    Keyword::PREWHERE,
+    Keyword::WHERE,
+    Keyword::GROUP,
+    Keyword::HAVING,
+    Keyword::ORDER,
+    Keyword::LIMIT,
+    Keyword::SETTINGS,
+    Keyword::FORMAT,
+];
+
+/// Statements that parsed cleanly plus every error found along the way
+#[derive(Debug, Default)]
+pub(crate) struct RecoveredParse {
+    pub statements: Vec<Statement>,
+    pub errors: Vec<ValidationError>,
+    /// Tokens of the input minus whitespace and comments, for checks that
+    /// need source details the AST drops
+    pub tokens: Vec<TokenWithSpan>,
+    /// `SETTINGS` of parsed `CREATE TABLE` statements, which sqlparser has
+    /// no AST node for
+    pub table_settings: Vec<TableSettings>,
+    /// The parsed fragment, when parsing in a mode other than statements
+    pub fragment: Option<Fragment>,
+}
+
+/// The `SETTINGS` clause of one `CREATE TABLE` and the engine it configures
+#[derive(Debug)]
+pub(crate) struct TableSettings {
+    /// Engine name, `None` when the statement gives none
+    pub engine: Option<String>,
+    pub settings: Vec<Setting>,
+}
+
+/// A parsed fragment, one variant per non-statement `ParseMode`
+#[derive(Debug)]
+pub(crate) enum Fragment {
+    /// An expression or condition
+    Expr(Box<Expr>),
+    SelectItems(Vec<SelectItem>),
+    OrderBy(Vec<OrderByExpr>),
+    Columns(Vec<ColumnDef>),
+}
+
+/// Parses `sql`, collecting all syntax errors instead of stopping at the first one
+pub(crate) fn parse_with_recovery(sql: &str) -> RecoveredParse {
+    let index = LineIndex::new(sql);
+    let mut result = RecoveredParse::default();
+    let Some(tokens) = tokenize(sql, &index, &mut result) else {
+        return result;
+    };
+
+    for chunk in tokens.split(|t| t.token == Token::SemiColon) {
+        if chunk
+            .iter()
+            .all(|t| matches!(t.token, Token::Whitespace(_)))
+        {
+            continue;
+        }
+        let chunk = match take_engine_arguments(chunk) {
+            Some(blanked) => Cow::Owned(blanked),
+            None => Cow::Borrowed(chunk),
+        };
+        let (chunk, partition_by) = match take_partition_by(&chunk) {
+            Some((blanked, expr)) => (Cow::Owned(blanked), Some(expr)),
+            None => (chunk, None),
+        };
+        let parsed = result.statements.len();
+        match take_table_settings(&chunk) {
+            Some((blanked, settings)) => {
+                parse_statement_chunk(&blanked, &index, &mut result);
+                if let Some(Statement::CreateTable(create)) = result.statements.get(parsed) {
+                    result.table_settings.push(TableSettings {
+                        engine: engine_name(create),
+                        settings,
+                    });
+                }
+            }
+            None => parse_statement_chunk(&chunk, &index, &mut result),
+        }
+        if let (Some(expr), Some(Statement::CreateTable(create))) =
+            (partition_by, result.statements.get_mut(parsed))
+        {
+            create.partition_by = Some(Box::new(expr));
+        }
+    }
+
+    result.tokens = significant(tokens);
+    result
+}
+
+/// Name given in the `ENGINE` clause of `create`
+fn engine_name(create: &CreateTable) -> Option<String> {
+    let CreateTableOptions::Plain(options) = &create.table_options else {
+        return None;
+    };
+    options.iter().find_map(|option| match option {
+        SqlOption::NamedParenthesizedList(list)
+            if list.key.value.eq_ignore_ascii_case("ENGINE") =>
+        {
+            list.name.as_ref().map(|name| name.value.clone())
+        }
+        _ => None,
+    })
+}
+
+/// Parses `sql` as a fragment of the given kind. Statements go through
+/// `parse_with_recovery`; other fragments must parse whole, and an empty
+/// fragment is not an error.
+pub(crate) fn parse_fragment(sql: &str, mode: ParseMode) -> RecoveredParse {
+    let index = LineIndex::new(sql);
+    let mut result = RecoveredParse::default();
+    let Some(tokens) = tokenize(sql, &index, &mut result) else {
+        return result;
+    };
+    if tokens
+        .iter()
+        .all(|t| matches!(t.token, Token::Whitespace(_)))
+    {
+        return result;
+    }
+
+    let dialect = ClickHouseDialect {};
+    let mut parser = Parser::new(&dialect).with_tokens_with_locations(tokens.clone());
+    let parsed = match mode {
+        ParseMode::Statement => return parse_with_recovery(sql),
+        ParseMode::Expression | ParseMode::Condition => {
+            parser.parse_expr().map(|e| Fragment::Expr(Box::new(e)))
+        }
+        ParseMode::SelectItems => parser.parse_projection().map(Fragment::SelectItems),
+        ParseMode::OrderBy => parser
+            .parse_comma_separated(Parser::parse_order_by_expr)
+            .map(Fragment::OrderBy),
+        ParseMode::ColumnDefinitions => parser
+            .parse_comma_separated(Parser::parse_column_def)
+            .map(Fragment::Columns),
+    };
+    let error = match parsed {
+        Ok(fragment) if parser.peek_token().token == Token::EOF => {
+            result.fragment = Some(fragment);
+            None
+        }
+        Ok(_) => parser
+            .expected::<()>("end of fragment", parser.peek_token())
+            .err(),
+        Err(e) => Some(e),
+    };
+
+    if let Some(error) = error {
+        if let Some(site) = locate_error(&parser, &tokens, &error, 0) {
+            let code = classify_parse_error(&error, &tokens, site.index, site.at_end);
+            let (start_offset, end_offset) = index.range(tokens[site.index].span);
+            push_error(
+                &mut result,
+                ValidationError::at_range(
+                    code,
+                    error.to_string(),
+                    start_offset,
+                    end_offset,
+                    &index,
+                ),
+            );
+        }
+    }
+
+    result.tokens = significant(tokens);
+    result
+}
+
+/// Tokenizes `sql`, recording a tokenizer failure in `result`
+fn tokenize(
+    sql: &str,
+    index: &LineIndex,
+    result: &mut RecoveredParse,
+) -> Option<Vec<TokenWithSpan>> {
+    let dialect = ClickHouseDialect {};
+    match Tokenizer::new(&dialect, sql).tokenize_with_location() {
+        Ok(tokens) => Some(tokens),
+        Err(e) => {
+            // Tokenizer errors only carry a point; widen it to one char
+            let start = index.offset(e.location);
+            let end = index.next_char_end(start);
+            let code = classify_tokenizer_error(sql, start);
+            let message = ParserError::from(e).to_string();
+            result
+                .errors
+                .push(ValidationError::at_range(code, message, start, end, index));
+            None
+        }
+    }
+}
+
+/// `tokens` minus whitespace and comments
+fn significant(tokens: Vec<TokenWithSpan>) -> Vec<TokenWithSpan> {
+    tokens
+        .into_iter()
+        .filter(|t| !matches!(t.token, Token::Whitespace(_)))
+        .collect()
+}
+
+/// Parses the tokens of a single statement, resuming after errors in queries
+fn parse_statement_chunk(chunk: &[TokenWithSpan], index: &LineIndex, result: &mut RecoveredParse) {
+    let dialect = ClickHouseDialect {};
+    let recoverable = is_query(chunk);
+    let mut start = 0;
+    let mut prefix: Vec<TokenWithSpan> = Vec::new();
+
+    loop {
+        let stream: Vec<TokenWithSpan> = prefix
+            .iter()
+            .cloned()
+            .chain(chunk[start..].iter().cloned())
+            .collect();
+        let mut parser = Parser::new(&dialect).with_tokens_with_locations(stream.clone());
+
+        let error = match parser.parse_statement() {
+            Ok(statement) if parser.peek_token().token == Token::EOF => {
+                // Statements resumed from a synthetic prefix are partial, so only
+                // keep the ones that were parsed in full
+                if prefix.is_empty() {
+                    result.statements.push(statement);
+                }
+                return;
+            }
+            Ok(_) => parser
+                .expected::<()>("end of statement", parser.peek_token())
+                .err(),
+            Err(e) => Some(e),
+        };
+        let Some(error) = error else {
+            return;
+        };
+
+        let Some(site) = locate_error(&parser, &stream, &error, prefix.len()) else {
+            return;
+        };
+        let error_index = start + site.index - prefix.len();
+        let code = classify_parse_error(&error, chunk, error_index, site.at_end);
+        let (start_offset, end_offset) = index.range(stream[site.index].span);
+        push_error(
+            result,
+            ValidationError::at_range(code, error.to_string(), start_offset, end_offset, index),
+        );
+
+        if !recoverable {
+            return;
+        }
+        let Some(resume) = find_resume_point(chunk, error_index.max(start + 1)) else {
+            return;
+        };
+        if chunk[resume..]
+            .iter()
+            .all(|t| matches!(t.token, Token::Whitespace(_)))
+        {
+            return;
+        }
+
+        start = resume;
+        prefix = synthetic_prefix(&chunk[resume..]);
+    }
+}
+
+/// Blanks the arguments of the `ENGINE = Name(...)` clause of a `CREATE`
+/// statement when sqlparser would reject them. It only accepts identifiers
+/// and strings there, not the tuples, numbers and expressions that engines
+/// such as `SummingMergeTree((a, b))` or `Distributed(c, db, t, rand())`
+/// take. The engine check reads the arguments from the tokens instead.
+/// Returns `None` if there is no such clause or sqlparser accepts it.
+fn take_engine_arguments(chunk: &[TokenWithSpan]) -> Option<Vec<TokenWithSpan>> {
+    let is_keyword = |t: &TokenWithSpan, keyword: Keyword| matches!(&t.token, Token::Word(w) if w.keyword == keyword && w.quote_style.is_none());
+    let significant: Vec<(usize, &TokenWithSpan)> = chunk
+        .iter()
+        .enumerate()
+        .filter(|(_, t)| !matches!(t.token, Token::Whitespace(_)))
+        .collect();
+    if !significant
+        .first()
+        .is_some_and(|(_, t)| is_keyword(t, Keyword::CREATE))
+    {
+        return None;
+    }
+
+    let mut depth = 0usize;
+    let keyword = significant.iter().position(|(_, t)| {
+        match t.token {
+            Token::LParen => depth += 1,
+            Token::RParen => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+        depth == 0 && (is_keyword(t, Keyword::ENGINE) || is_keyword(t, Keyword::SELECT))
+    })?;
+    if !is_keyword(significant[keyword].1, Keyword::ENGINE) {
+        return None;
+    }
+    let mut k = keyword + 1;
+    if significant
+        .get(k)
+        .is_some_and(|(_, t)| t.token == Token::Eq)
+    {
+        k += 1;
+    }
+    if !significant
+        .get(k)
+        .is_some_and(|(_, t)| matches!(t.token, Token::Word(_)))
+        || significant
+            .get(k + 1)
+            .is_none_or(|(_, t)| t.token != Token::LParen)
+    {
+        return None;
+    }
+
+    let open = significant[k + 1].0;
+    let mut depth = 0usize;
+    let mut accepted = true;
+    let mut close = None;
+    for (i, token) in chunk.iter().enumerate().skip(open + 1) {
+        match token.token {
+            Token::RParen if depth == 0 => {
+                close = Some(i);
+                break;
+            }
+            Token::LParen => depth += 1,
+            Token::RParen => depth -= 1,
+            _ => {}
+        }
+        accepted &= depth == 0
+            && matches!(
+                token.token,
+                Token::Word(_) | Token::SingleQuotedString(_) | Token::Comma | Token::Whitespace(_)
+            );
+    }
+    if accepted {
+        return None;
+    }
+
+    let mut blanked = chunk.to_vec();
+    for token in &mut blanked[open + 1..close?] {
+        token.token = Token::Whitespace(Whitespace::Space);
+    }
+    Some(blanked)
+}
+
+/// Cuts the `PARTITION BY` clause out of a `CREATE ... ENGINE = ...`
+/// statement, which sqlparser only accepts in other dialects. Returns the
+/// chunk with the clause blanked to whitespace and the partition key parsed
+/// on its own, for the caller to put back on the parsed `CreateTable`, or
+/// `None` if there is no such clause or its key does not parse.
+fn take_partition_by(chunk: &[TokenWithSpan]) -> Option<(Vec<TokenWithSpan>, Expr)> {
+    take_engine_clause(chunk, &[Keyword::PARTITION, Keyword::BY], |parser| {
+        parser.parse_expr().ok()
+    })
+}
+
+/// Cuts the table-level `SETTINGS` clause out of a `CREATE ... ENGINE = ...`
+/// statement, which sqlparser rejects. Returns the chunk with the clause
+/// blanked to whitespace (keeping every other span intact) and the settings
+/// parsed on their own, or `None` if there is no such clause or it does not
+/// parse, in which case the syntax error is reported as usual.
+fn take_table_settings(chunk: &[TokenWithSpan]) -> Option<(Vec<TokenWithSpan>, Vec<Setting>)> {
+    take_engine_clause(chunk, &[Keyword::SETTINGS], |parser| {
+        parser
+            .parse_comma_separated(|p| {
+                let key = p.parse_identifier()?;
+                p.expect_token(&Token::Eq)?;
+                let value = p.parse_expr()?;
+                Ok(Setting { key, value })
+            })
+            .ok()
+    })
+}
+
+/// Cuts the clause starting with `keywords` that follows the engine clause
+/// of a `CREATE` statement, and precedes any `AS SELECT`, out of `chunk`.
+/// `parse` parses the clause from just after its keywords; the clause ends
+/// where it stops. Returns the chunk with the clause blanked to whitespace,
+/// keeping every other span intact, and what `parse` returned.
+fn take_engine_clause<T>(
+    chunk: &[TokenWithSpan],
+    keywords: &[Keyword],
+    parse: impl FnOnce(&mut Parser) -> Option<T>,
+) -> Option<(Vec<TokenWithSpan>, T)> {
+    let is_keyword = |t: &TokenWithSpan, keyword: Keyword| matches!(&t.token, Token::Word(w) if w.keyword == keyword && w.quote_style.is_none());
+    let significant: Vec<(usize, &TokenWithSpan)> = chunk
+        .iter()
+        .enumerate()
+        .filter(|(_, t)| !matches!(t.token, Token::Whitespace(_)))
+        .collect();
+    if !significant
+        .first()
+        .is_some_and(|(_, t)| is_keyword(t, Keyword::CREATE))
+    {
+        return None;
+    }
+    let starts_clause = |k: usize| {
+        keywords.iter().enumerate().all(|(j, keyword)| {
+            significant
+                .get(k + j)
+                .is_some_and(|(_, t)| is_keyword(t, *keyword))
+        })
+    };
+
+    let mut depth = 0usize;
+    let mut seen_engine = false;
+    let mut start = None;
+    for (k, &(i, token)) in significant.iter().enumerate().skip(1) {
+        match token.token {
+            Token::LParen => depth += 1,
+            Token::RParen => depth = depth.saturating_sub(1),
+            _ if depth > 0 => {}
+            _ if is_keyword(token, Keyword::SELECT) => return None,
+            _ if is_keyword(token, Keyword::ENGINE) => seen_engine = true,
+            _ if seen_engine && starts_clause(k) => {
+                start = Some(i);
+                break;
+            }
+            _ => {}
+        }
+    }
+    let start = start?;
+
+    let dialect = ClickHouseDialect {};
+    let mut parser = Parser::new(&dialect).with_tokens_with_locations(chunk[start..].to_vec());
+    if !parser.parse_keywords(keywords) {
+        return None;
+    }
+    let parsed = parse(&mut parser)?;
+    let end = start + parser.index();
+
+    let mut blanked = chunk.to_vec();
+    for token in &mut blanked[start..end] {
+        token.token = Token::Whitespace(Whitespace::Space);
+    }
+    Some((blanked, parsed))
+}
+
+/// The token a parse error is attributed to
+struct ErrorSite {
+    /// Index into the parsed token stream
+    index: usize,
+    /// The parser ran out of tokens; `index` is then the statement's last token
+    at_end: bool,
+}
+
+/// Finds the index in `stream` of the token a parse error is about.
+///
+/// sqlparser's errors either point at the token just consumed or at the one
+/// about to be consumed. Of those two tokens, the one the error names as
+/// `found` is taken. When that does not settle it, the location appended to
+/// the message selects the token, and failing that the parser's current
+/// token is used. Errors at end of input are attributed to the statement's
+/// last token. Returns `None` if the error falls on the synthetic prefix of a
+/// resumed clause.
+fn locate_error(
+    parser: &Parser,
+    stream: &[TokenWithSpan],
+    error: &ParserError,
+    prefix_len: usize,
+) -> Option<ErrorSite> {
+    let located = found_token(parser, stream, error)
+        .or_else(|| reported_location(error).and_then(|location| find_token_at(stream, location)));
+    let at_end = located.is_none() && parser.peek_token().token == Token::EOF;
+    let index = located.or_else(|| {
+        let current = parser.get_current_index();
+        stream
+            .get(current)
+            .filter(|t| !matches!(t.token, Token::EOF | Token::Whitespace(_)))
+            .map(|_| current)
+            .or_else(|| {
+                stream
+                    .iter()
+                    .rposition(|t| !matches!(t.token, Token::Whitespace(_)))
+            })
+    })?;
+    (index >= prefix_len).then_some(ErrorSite { index, at_end })
+}
+
+/// Picks the code for a parser error from the shape of the statement around it
+fn classify_parse_error(
+    error: &ParserError,
+    chunk: &[TokenWithSpan],
+    error_index: usize,
+    at_end: bool,
+) -> ErrorCode {
+    if matches!(error, ParserError::RecursionLimitExceeded) {
+        return ErrorCode::TooDeeplyNested;
+    }
+
+    let paren_balance = |tokens: &[TokenWithSpan]| {
+        tokens.iter().fold(0_i64, |depth, t| match t.token {
+            Token::LParen => depth + 1,
+            Token::RParen => depth - 1,
+            _ => depth,
+        })
+    };
+
+    let is_close_paren = chunk
+        .get(error_index)
+        .is_some_and(|t| t.token == Token::RParen);
+    if is_close_paren && paren_balance(&chunk[..=error_index]) < 0 {
+        ErrorCode::UnmatchedParen
+    } else if paren_balance(chunk) > 0 {
+        ErrorCode::UnclosedParen
+    } else if at_end {
+        ErrorCode::UnexpectedEnd
+    } else {
+        ErrorCode::UnexpectedToken
+    }
+}
+
+/// Tokenizer errors carry no kind, so look at what starts at the error location
+fn classify_tokenizer_error(sql: &str, offset: usize) -> ErrorCode {
+    match sql[offset..].chars().next() {
+        Some('\'') => ErrorCode::UnterminatedString,
+        Some('"' | '`') => ErrorCode::UnterminatedIdentifier,
+        // Unclosed block comments are reported at end of input
+        None if sql.rfind("/*") > sql.rfind("*/") => ErrorCode::UnterminatedComment,
+        _ => ErrorCode::InvalidToken,
+    }
+}
+
+/// The token just consumed or the next one, whichever `error` names as the
+/// token it found; `None` at end of input or when both or neither match
+fn found_token(parser: &Parser, stream: &[TokenWithSpan], error: &ParserError) -> Option<usize> {
+    let ParserError::ParserError(message) = error else {
+        return None;
+    };
+    let (_, found) = message.split_once(", found: ")?;
+    let found = found
+        .split_once(" at Line: ")
+        .map_or(found, |(found, _)| found);
+
+    let current = parser.get_current_index();
+    let next =
+        (current + 1..stream.len()).find(|&i| !matches!(stream[i].token, Token::Whitespace(_)));
+    let mut named = [Some(current), next].into_iter().flatten().filter(|&i| {
+        stream.get(i).is_some_and(|t| {
+            !matches!(t.token, Token::EOF | Token::Whitespace(_)) && t.token.to_string() == found
+        })
+    });
+    match (named.next(), named.next()) {
+        (Some(index), None) => Some(index),
+        _ => None,
+    }
+}
+
+/// The location sqlparser appends to error messages (`... at Line: 1, Column: 5`)
+fn reported_location(error: &ParserError) -> Option<Location> {
+    let (ParserError::ParserError(message) | ParserError::TokenizerError(message)) = error else {
+        return None;
+    };
+    let (_, position) = message.rsplit_once(" at Line: ")?;
+    let (line, column) = position.split_once(", Column: ")?;
+    Some(Location::new(line.parse().ok()?, column.parse().ok()?))
+}
+
+/// Records an error unless one was already reported at the same position
+fn push_error(result: &mut RecoveredParse, error: ValidationError) {
+    let duplicate = result
+        .errors
+        .iter()
+        .any(|e| e.start_offset == error.start_offset);
+    if !duplicate {
+        result.errors.push(error);
+    }
+}
+
+/// Only queries get clause-level recovery; other statements (DDL, INSERT)
+/// have no clause boundaries that can be parsed on their own
+fn is_query(chunk: &[TokenWithSpan]) -> bool {
+    chunk
+        .iter()
+        .find(|t| !matches!(t.token, Token::Whitespace(_)))
+        .is_some_and(|t| {
+            matches!(&t.token, Token::Word(w) if w.keyword == Keyword::SELECT || w.keyword == Keyword::WITH)
+        })
+}
+
+/// Finds where parsing can resume at or after `from`: either a clause keyword
+/// outside any parentheses, or just past the paren that closes the outermost
+/// group still open at `from`
+fn find_resume_point(chunk: &[TokenWithSpan], from: usize) -> Option<usize> {
+    let mut depth: usize = 0;
+    for (i, token) in chunk.iter().enumerate() {
+        match &token.token {
+            Token::LParen => depth += 1,
+            Token::RParen if depth > 0 => {
+                depth -= 1;
+                if depth == 0 && i >= from {
+                    return Some(i + 1);
+                }
+            }
+            Token::Word(w) if depth == 0 && i >= from && CLAUSE_KEYWORDS.contains(&w.keyword) => {
+                return Some(i);
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+fn find_token_at(tokens: &[TokenWithSpan], location: Location) -> Option<usize> {
+    tokens
+        .iter()
+        .position(|t| !matches!(t.token, Token::Whitespace(_)) && t.span.start == location)
+}
+
+/// Builds the tokens prepended to a resumed clause so it parses as a query:
+/// `SELECT 1` before a `FROM` or an expression continuation, `SELECT 1 FROM t`
+/// before any later clause. The tokens have empty spans, so errors can never
+/// be attributed to them.
+fn synthetic_prefix(rest: &[TokenWithSpan]) -> Vec<TokenWithSpan> {
+    let mut tokens = vec![
+        Token::make_keyword("SELECT"),
+        Token::Number("1".to_string(), false),
+    ];
+
+    let next = rest
+        .iter()
+        .find(|t| !matches!(t.token, Token::Whitespace(_)));
+    if let Some(Token::Word(w)) = next.map(|t| &t.token) {
+        if w.keyword != Keyword::FROM && CLAUSE_KEYWORDS.contains(&w.keyword) {
+            tokens.push(Token::make_keyword("FROM"));
+            tokens.push(Token::make_word("t", None));
+        }
+    }
+
+    tokens
+        .into_iter()
+        .map(|token| TokenWithSpan::new(token, Span::empty()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_locate_error_without_reported_location() {
+        let dialect = ClickHouseDialect {};
+        let stream = Tokenizer::new(&dialect, "SELCT * FROM t")
+            .tokenize_with_location()
+            .expect("tokenizes");
+        let mut parser = Parser::new(&dialect).with_tokens_with_locations(stream.clone());
+        assert!(parser.parse_statement().is_err());
+
+        // A message in an unknown format still resolves via the parser position
+        let error = ParserError::ParserError("unexpected token".to_string());
+        let site = locate_error(&parser, &stream, &error, 0).expect("attributed");
+        assert_eq!(site.index, 0);
+        assert!(!site.at_end);
+    }
+
+    #[test]
+    fn test_locate_error_prefers_the_found_token() {
+        let dialect = ClickHouseDialect {};
+        let stream = Tokenizer::new(&dialect, "SELCT * FROM t")
+            .tokenize_with_location()
+            .expect("tokenizes");
+        let mut parser = Parser::new(&dialect).with_tokens_with_locations(stream.clone());
+        assert!(parser.parse_statement().is_err());
+
+        // The token the parser stopped on wins over a location pointing at `FROM`
+        let error = ParserError::ParserError(
+            "Expected: an SQL statement, found: SELCT at Line: 1, Column: 9".to_string(),
+        );
+        let site = locate_error(&parser, &stream, &error, 0).expect("attributed");
+        assert_eq!(site.index, 0);
+
+        // A found token that is not at the parser position falls back to the location
+        let error = ParserError::ParserError(
+            "Expected: an SQL statement, found: FROM at Line: 1, Column: 9".to_string(),
+        );
+        let site = locate_error(&parser, &stream, &error, 0).expect("attributed");
+        assert_eq!(stream[site.index].token.to_string(), "FROM");
+    }
+
+    #[test]
+    fn test_reported_location() {
+        let error = ParserError::ParserError(
+            "Expected: an expression, found: , at Line: 3, Column: 14".to_string(),
+        );
+        assert_eq!(reported_location(&error), Some(Location::new(3, 14)));
+        let error = ParserError::ParserError("Expected: identifier, found: EOF".to_string());
+        assert_eq!(reported_location(&error), None);
+    }
+
+    #[test]
+    fn test_table_settings_are_parsed_separately() {
+        let parsed = parse_with_recovery(
+            "CREATE TABLE t (a UInt64) ENGINE = MergeTree ORDER BY a \
+             SETTINGS index_granularity = 8192, ttl_only_drop_parts = 1",
+        );
+        assert!(parsed.errors.is_empty(), "{:?}", parsed.errors);
+        assert_eq!(parsed.statements.len(), 1);
+        let [table] = parsed.table_settings.as_slice() else {
+            unreachable!("one SETTINGS clause");
+        };
+        assert_eq!(table.engine.as_deref(), Some("MergeTree"));
+        let keys: Vec<&str> = table
+            .settings
+            .iter()
+            .map(|s| s.key.value.as_str())
+            .collect();
+        assert_eq!(keys, vec!["index_granularity", "ttl_only_drop_parts"]);
+
+        // Query settings after `AS SELECT` stay with the query
+        let parsed = parse_with_recovery(
+            "CREATE TABLE t ENGINE = Memory AS SELECT 1 FROM src SETTINGS max_threads = 1",
+        );
+        assert!(parsed.table_settings.is_empty());
+    }
+
+    #[test]
+    fn test_engine_arguments_sqlparser_rejects_are_skipped() {
+        for sql in [
+            "CREATE TABLE t (a UInt64, x UInt64) ENGINE = SummingMergeTree((a, x)) ORDER BY a",
+            "CREATE TABLE t (a UInt64) ENGINE = Distributed(cluster, db, local_t, rand())",
+        ] {
+            let parsed = parse_with_recovery(sql);
+            assert!(parsed.errors.is_empty(), "{sql}: {:?}", parsed.errors);
+            assert_eq!(parsed.statements.len(), 1);
+        }
+        // Arguments sqlparser accepts are kept
+        let sql =
+            "CREATE TABLE t (a UInt64) ENGINE = ReplicatedMergeTree('/t', '{replica}') ORDER BY a";
+        let tokens = Tokenizer::new(&ClickHouseDialect {}, sql)
+            .tokenize_with_location()
+            .expect("tokenizes");
+        assert!(take_engine_arguments(&tokens).is_none());
+    }
+
+    #[test]
+    fn test_partition_keys_are_parsed_separately() {
+        let parsed = parse_with_recovery(
+            "CREATE TABLE t (ts DateTime) ENGINE = MergeTree \
+             PARTITION BY toYYYYMM(ts) ORDER BY ts SETTINGS index_granularity = 8192",
+        );
+        assert!(parsed.errors.is_empty(), "{:?}", parsed.errors);
+        let Some(Statement::CreateTable(create)) = parsed.statements.first() else {
+            unreachable!("parsed as a table");
+        };
+        let partition_by = create.partition_by.as_ref().map(ToString::to_string);
+        assert_eq!(partition_by.as_deref(), Some("toYYYYMM(ts)"));
+        assert_eq!(parsed.table_settings.len(), 1);
+
+        // Window partitions stay with the query
+        let parsed = parse_with_recovery(
+            "CREATE TABLE t ENGINE = Memory AS SELECT sum(a) OVER (PARTITION BY b) FROM src",
+        );
+        assert!(parsed.errors.is_empty(), "{:?}", parsed.errors);
+    }
+}
@@ -0,0 +1,124 @@
+//! Databases, tables and columns of a project, registered with an `Engine` so
+//! that completion knows the names queries are written against, not only the
+//! built-in ones. A schema is given as JSON or built from the project's DDL.
+
+use serde::{Deserialize, Serialize};
+
+/// The databases of a project, as registered with `Engine::register_schema`
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Schema {
+    #[serde(default)]
+    pub databases: Vec<DatabaseInfo>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct DatabaseInfo {
+    pub name: String,
+    #[serde(default)]
+    pub tables: Vec<TableInfo>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct TableInfo {
+    pub name: String,
+    #[serde(default)]
+    pub kind: TableKind,
+    #[serde(default)]
+    pub columns: Vec<ColumnInfo>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+    /// Table engine with its arguments, such as `ReplacingMergeTree(ts)`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub engine: Option<String>,
+    /// Expressions of the sorting key, one per element of `ORDER BY (...)`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub order_by: Vec<String>,
+    /// Expression of the partition key
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub partition_by: Option<String>,
+}
+
+/// What a name in `FROM` refers to
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum TableKind {
+    #[default]
+    Table,
+    View,
+    MaterializedView,
+}
+
+impl TableKind {
+    /// How the kind reads in completion details
+    pub(crate) fn describe(self) -> &'static str {
+        match self {
+            TableKind::Table => "table",
+            TableKind::View => "view",
+            TableKind::MaterializedView => "materialized view",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ColumnInfo {
+    pub name: String,
+    /// `ClickHouse` type, such as `LowCardinality(String)`; empty when it is
+    /// not known, as for computed columns of views built from DDL
+    #[serde(rename = "type", default)]
+    pub column_type: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+}
+
+impl Schema {
+    pub(crate) fn database(&self, name: &str) -> Option<&DatabaseInfo> {
+        self.databases.iter().find(|d| d.name == name)
+    }
+
+    /// Every table, with the database it is in
+    pub(crate) fn tables(&self) -> impl Iterator<Item = (&DatabaseInfo, &TableInfo)> {
+        self.databases
+            .iter()
+            .flat_map(|d| d.tables.iter().map(move |t| (d, t)))
+    }
+
+    /// The table `name` in `database`, or without one the first table of
+    /// that name in any database. Names are case-sensitive, as in `ClickHouse`.
+    pub(crate) fn table(&self, database: Option<&str>, name: &str) -> Option<&TableInfo> {
+        self.tables()
+            .find(|(d, t)| t.name == name && database.is_none_or(|db| d.name == db))
+            .map(|(_, t)| t)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tables_are_found_with_and_without_database() {
+        let schema: Schema = serde_json::from_str(
+            r#"{"databases": [
+                {"name": "local", "tables": [
+                    {"name": "events", "columns": [{"name": "id", "type": "UInt64"}]}
+                ]},
+                {"name": "staging", "tables": [
+                    {"name": "events", "comment": "Staged", "columns": []}
+                ]}
+            ]}"#,
+        )
+        .expect("valid schema");
+
+        let events = schema.table(None, "events").expect("found");
+        assert_eq!(events.columns[0].column_type, "UInt64");
+        let staged = schema.table(Some("staging"), "events").expect("found");
+        assert_eq!(staged.comment.as_deref(), Some("Staged"));
+        assert!(schema.table(Some("local"), "Events").is_none());
+        assert_eq!(schema.tables().count(), 2);
+        assert!(schema.database("staging").is_some());
+    }
+}
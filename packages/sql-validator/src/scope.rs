@@ -0,0 +1,318 @@
+//! The tables a query reads from, as completion sees them around the cursor.
+//!
+//! The query being typed seldom parses, and its `FROM` clause usually comes
+//! after the cursor, so the tables are read from the tokens of the whole
+//! statement rather than from an AST. Only the query the cursor is in counts:
+//! not subqueries inside it, the query around a subquery the cursor is in, or
+//! the other side of a `UNION`. Table functions and subqueries in `FROM` name
+//! no table and are skipped.
+
+use crate::position::LineIndex;
+use sqlparser::dialect::ClickHouseDialect;
+use sqlparser::keywords::Keyword;
+use sqlparser::tokenizer::{Token, Tokenizer, Word};
+
+/// A table named in a `FROM` or `JOIN` clause
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct TableReference {
+    pub database: Option<String>,
+    pub table: String,
+    pub alias: Option<String>,
+}
+
+impl TableReference {
+    /// Whether `name` before a `.` refers to this table: its alias if it has
+    /// one, and otherwise its name
+    pub fn is_named(&self, name: &str) -> bool {
+        self.alias.as_deref().unwrap_or(&self.table) == name
+    }
+}
+
+/// The tables named in the `FROM` and `JOIN` clauses of the query the byte
+/// offset `cursor_offset` is in
+pub(crate) fn tables_in_scope(sql: &str, cursor_offset: usize) -> Vec<TableReference> {
+    // Text left unterminated after the cursor is closed the way it was opened
+    let tokens = ["", "'", "`", "\"", "*/"]
+        .into_iter()
+        .find_map(|end| tokens(&format!("{sql}{end}")))
+        .or_else(|| tokens(&sql[..cursor_offset]));
+    let Some(tokens) = tokens else {
+        return Vec::new();
+    };
+    let start = query_start(&tokens, cursor_offset);
+
+    let mut tables = Vec::new();
+    let mut nesting = 0usize;
+    let mut i = start;
+    while let Some((token, offset)) = tokens.get(i) {
+        i += 1;
+        match token {
+            Token::LParen => nesting += 1,
+            Token::RParen | Token::SemiColon if nesting == 0 => break,
+            Token::RParen => nesting -= 1,
+            Token::Word(word) if nesting == 0 && is_set_operation(word) => {
+                // Each side of a set operation is a query of its own
+                if *offset < cursor_offset {
+                    tables.clear();
+                } else {
+                    break;
+                }
+            }
+            Token::Word(word) if nesting == 0 && starts_table_list(&tokens, i - 1, word) => {
+                while let Some((table, next)) = table_reference(&tokens, i) {
+                    tables.extend(table);
+                    i = next;
+                    if !matches!(tokens.get(i), Some((Token::Comma, _))) {
+                        break;
+                    }
+                    i += 1;
+                }
+            }
+            _ => {}
+        }
+    }
+    tables
+}
+
+/// Tokens without whitespace and comments, with the byte offsets they start at
+fn tokens(sql: &str) -> Option<Vec<(Token, usize)>> {
+    let lines = LineIndex::new(sql);
+    let tokens = Tokenizer::new(&ClickHouseDialect {}, sql)
+        .tokenize_with_location()
+        .ok()?;
+    Some(
+        tokens
+            .into_iter()
+            .filter(|t| !matches!(t.token, Token::Whitespace(_)))
+            .map(|t| {
+                let offset = lines.offset(t.span.start);
+                (t.token, offset)
+            })
+            .collect(),
+    )
+}
+
+/// Index of the first token of the query at `cursor_offset`
+fn query_start(tokens: &[(Token, usize)], cursor_offset: usize) -> usize {
+    // Index of each open parenthesis before the cursor, and whether a
+    // subquery starts inside it
+    let mut open: Vec<(usize, bool)> = Vec::new();
+    let mut statement_start = 0;
+    for (i, (token, offset)) in tokens.iter().enumerate() {
+        if *offset >= cursor_offset {
+            break;
+        }
+        match token {
+            Token::LParen => {
+                let subquery = matches!(
+                    tokens.get(i + 1),
+                    Some((Token::Word(w), _)) if matches!(w.keyword, Keyword::SELECT | Keyword::WITH)
+                );
+                open.push((i, subquery));
+            }
+            Token::RParen => {
+                open.pop();
+            }
+            Token::SemiColon if open.is_empty() => statement_start = i + 1,
+            _ => {}
+        }
+    }
+    open.iter()
+        .rev()
+        .find(|(_, subquery)| *subquery)
+        .map_or(statement_start, |(i, _)| i + 1)
+}
+
+/// Whether `word` starts another query of a set operation
+fn is_set_operation(word: &Word) -> bool {
+    matches!(
+        word.keyword,
+        Keyword::UNION | Keyword::EXCEPT | Keyword::INTERSECT
+    )
+}
+
+/// Whether the word at `i` is a `FROM` or a `JOIN` that tables follow, which
+/// excludes `ARRAY JOIN` and the column `t.from`, though not the `FROM` in
+/// `SELECT t. FROM t` while the column is being typed
+fn starts_table_list(tokens: &[(Token, usize)], i: usize, word: &Word) -> bool {
+    let Some((previous, previous_offset)) = i.checked_sub(1).and_then(|i| tokens.get(i)) else {
+        return matches!(word.keyword, Keyword::FROM | Keyword::JOIN);
+    };
+    let qualified = *previous == Token::Period && tokens[i].1 == previous_offset + 1;
+    match word.keyword {
+        Keyword::FROM => !qualified,
+        Keyword::JOIN => {
+            !qualified
+                && !matches!(
+                    previous,
+                    Token::Word(Word {
+                        keyword: Keyword::ARRAY,
+                        ..
+                    })
+                )
+        }
+        _ => false,
+    }
+}
+
+/// The table reference starting at token `i` and the index after it, or
+/// `None` when nothing that could be one starts there. The reference is
+/// `None` for table functions and subqueries.
+fn table_reference(
+    tokens: &[(Token, usize)],
+    mut i: usize,
+) -> Option<(Option<TableReference>, usize)> {
+    let mut names = Vec::new();
+    match tokens.get(i) {
+        // Names such as `local` that are also keywords are still names here
+        Some((Token::Word(word), _)) => {
+            names.push(word.value.clone());
+            i += 1;
+            while let (Some((Token::Period, _)), Some((Token::Word(word), _))) =
+                (tokens.get(i), tokens.get(i + 1))
+            {
+                names.push(word.value.clone());
+                i += 2;
+            }
+        }
+        Some((Token::LParen, _)) => {}
+        _ => return None,
+    }
+
+    // The arguments of a table function, or a subquery
+    let mut reads_table = !names.is_empty();
+    if let Some((Token::LParen, _)) = tokens.get(i) {
+        reads_table = false;
+        let mut nesting = 0usize;
+        while let Some((token, _)) = tokens.get(i) {
+            i += 1;
+            match token {
+                Token::LParen => nesting += 1,
+                Token::RParen => {
+                    nesting -= 1;
+                    if nesting == 0 {
+                        break;
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let explicit = matches!(
+        tokens.get(i),
+        Some((
+            Token::Word(Word {
+                keyword: Keyword::AS,
+                ..
+            }),
+            _
+        ))
+    );
+    if explicit {
+        i += 1;
+    }
+    let alias = match tokens.get(i) {
+        Some((Token::Word(word), _)) if explicit || is_name(word) => {
+            i += 1;
+            Some(word.value.clone())
+        }
+        _ => None,
+    };
+
+    let table = names
+        .pop()
+        .filter(|_| reads_table)
+        .map(|table| TableReference {
+            database: names.pop(),
+            table,
+            alias,
+        });
+    Some((table, i))
+}
+
+/// Whether `word` can be an alias rather than the keyword that ends the
+/// reference
+fn is_name(word: &Word) -> bool {
+    word.quote_style.is_some() || word.keyword == Keyword::NoKeyword
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The tables in scope at the `|` in `marked`, as `db.table alias`
+    fn scope_at(marked: &str) -> Vec<String> {
+        let cursor = marked.find('|').expect("cursor marked");
+        let sql = marked.replacen('|', "", 1);
+        tables_in_scope(&sql, cursor)
+            .into_iter()
+            .map(|t| {
+                let name = match t.database {
+                    Some(db) => format!("{db}.{}", t.table),
+                    None => t.table,
+                };
+                match t.alias {
+                    Some(alias) => format!("{name} {alias}"),
+                    None => name,
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_tables_after_the_cursor() {
+        assert_eq!(scope_at("SELECT | FROM events"), vec!["events"]);
+        assert_eq!(
+            scope_at("SELECT e.| FROM local.events AS e JOIN users u ON e.id = u.id"),
+            vec!["local.events e", "users u"]
+        );
+        assert_eq!(
+            scope_at("SELECT | FROM `my events` e, users WHERE 1"),
+            vec!["my events e", "users"]
+        );
+        assert_eq!(
+            scope_at("SELECT * FROM events FINAL WHERE |"),
+            vec!["events"]
+        );
+    }
+
+    #[test]
+    fn test_only_the_query_at_the_cursor() {
+        assert_eq!(
+            scope_at("SELECT | FROM events WHERE id IN (SELECT id FROM users)"),
+            vec!["events"]
+        );
+        assert_eq!(
+            scope_at("SELECT id FROM events WHERE id IN (SELECT | FROM users)"),
+            vec!["users"]
+        );
+        assert_eq!(
+            scope_at("SELECT a FROM t1 UNION ALL SELECT | FROM t2"),
+            vec!["t2"]
+        );
+        assert_eq!(scope_at("SELECT 1 FROM t1; SELECT | FROM t2"), vec!["t2"]);
+    }
+
+    #[test]
+    fn test_table_functions_and_subqueries_name_no_table() {
+        assert_eq!(
+            scope_at("SELECT | FROM numbers(10) n, (SELECT 1) s JOIN events e ON 1"),
+            vec!["events e"]
+        );
+        assert_eq!(
+            scope_at("SELECT | FROM events ARRAY JOIN tags AS tag"),
+            vec!["events"]
+        );
+    }
+
+    #[test]
+    fn test_unfinished_statements() {
+        assert_eq!(scope_at("SELECT * FROM events WHERE |"), vec!["events"]);
+        assert_eq!(
+            scope_at("SELECT | FROM events WHERE x = 'open"),
+            vec!["events"]
+        );
+        assert!(scope_at("SELECT |").is_empty());
+    }
+}
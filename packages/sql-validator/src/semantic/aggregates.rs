@@ -0,0 +1,432 @@
+//! Placement rules for aggregate functions.
+//!
+//! `ClickHouse` rejects aggregates in `WHERE`/`PREWHERE` (they run before
+//! grouping), aggregates nested inside other aggregates, and, when a query has
+//! a `GROUP BY`, selected columns that are neither grouped nor aggregated.
+
+use super::{is_template_placeholder, CheckContext};
+use crate::{ErrorCode, ValidationError};
+use sqlparser::ast::{
+    Expr, Function, FunctionArg, FunctionArgExpr, FunctionArguments, GroupByExpr, Ident, Query,
+    Select, SelectItem, SetExpr, Spanned, Statement, Value, Visit, Visitor,
+};
+use sqlparser::tokenizer::Span;
+use std::collections::HashMap;
+use std::ops::ControlFlow;
+
+/// Reports misplaced aggregate functions and ungrouped columns
+pub(super) fn check_aggregates(
+    statements: &[Statement],
+    ctx: &CheckContext,
+    errors: &mut Vec<ValidationError>,
+) {
+    let mut visitor = Placement { ctx, errors };
+    for statement in statements {
+        let _ = statement.visit(&mut visitor);
+    }
+}
+
+struct Placement<'a, 'b> {
+    ctx: &'a CheckContext<'a>,
+    errors: &'b mut Vec<ValidationError>,
+}
+
+impl Visitor for Placement<'_, '_> {
+    type Break = ();
+
+    fn pre_visit_query(&mut self, query: &Query) -> ControlFlow<()> {
+        // Subqueries are visited on their own, so only this query's selects
+        self.check_set_expr(&query.body);
+        ControlFlow::Continue(())
+    }
+}
+
+impl Placement<'_, '_> {
+    fn check_set_expr(&mut self, body: &SetExpr) {
+        match body {
+            SetExpr::Select(select) => self.check_select(select),
+            SetExpr::SetOperation { left, right, .. } => {
+                self.check_set_expr(left);
+                self.check_set_expr(right);
+            }
+            _ => {}
+        }
+    }
+
+    fn check_select(&mut self, select: &Select) {
+        for (clause, expr) in [("PREWHERE", &select.prewhere), ("WHERE", &select.selection)] {
+            let Some(expr) = expr else { continue };
+            for call in self.aggregate_calls(expr) {
+                let message = format!(
+                    "Aggregate function `{}` is not allowed in {clause}; filter on aggregated values in HAVING instead.",
+                    call.name
+                );
+                self.push(ErrorCode::AggregateInWhere, message, call.span);
+            }
+        }
+
+        let projection = select.projection.iter().filter_map(|item| match item {
+            SelectItem::UnnamedExpr(expr) | SelectItem::ExprWithAlias { expr, .. } => Some(expr),
+            _ => None,
+        });
+        for expr in projection.chain(&select.having) {
+            for call in self.aggregate_calls(expr) {
+                if let Some(outer) = call.enclosing {
+                    let message = format!(
+                        "Aggregate function `{}` cannot be used inside aggregate function `{outer}`.",
+                        call.name
+                    );
+                    self.push(ErrorCode::NestedAggregate, message, call.span);
+                }
+            }
+        }
+
+        if let GroupByExpr::Expressions(keys, _) = &select.group_by {
+            if !keys.is_empty() {
+                self.check_grouping(select, keys);
+            }
+        }
+    }
+
+    /// Reports the first ungrouped column of every selected expression
+    fn check_grouping(&mut self, select: &Select, keys: &[Expr]) {
+        let aliases: HashMap<&str, &Expr> = select
+            .projection
+            .iter()
+            .filter_map(|item| match item {
+                SelectItem::ExprWithAlias { expr, alias } => Some((alias.value.as_str(), expr)),
+                _ => None,
+            })
+            .collect();
+        // Every member of `CUBE`, `ROLLUP` and `GROUPING SETS` is a grouping key
+        let keys = keys
+            .iter()
+            .flat_map(|key| match key {
+                Expr::Cube(sets) | Expr::Rollup(sets) | Expr::GroupingSets(sets) => {
+                    sets.iter().flatten().collect()
+                }
+                key => vec![key],
+            })
+            .collect();
+        let grouping = Grouping {
+            ctx: self.ctx,
+            keys,
+            aliases,
+        };
+
+        for (position, item) in select.projection.iter().enumerate() {
+            let expr = match item {
+                SelectItem::UnnamedExpr(expr) => expr,
+                SelectItem::ExprWithAlias { expr, alias } => {
+                    if grouping.is_key_name(alias) {
+                        continue;
+                    }
+                    expr
+                }
+                _ => continue,
+            };
+            if grouping.is_key_position(position) {
+                continue;
+            }
+            if let Some(column) = grouping.ungrouped(expr, 0) {
+                let message = format!(
+                    "`{column}` is neither aggregated nor listed in GROUP BY. Add it to GROUP BY or wrap it in an aggregate such as `any({column})`."
+                );
+                self.push(ErrorCode::NotInGroupBy, message, column.span());
+            }
+        }
+    }
+
+    fn aggregate_calls(&self, expr: &Expr) -> Vec<AggregateCall> {
+        let mut finder = AggregateCalls {
+            ctx: self.ctx,
+            query_depth: 0,
+            enclosing: Vec::new(),
+            calls: Vec::new(),
+        };
+        let _ = expr.visit(&mut finder);
+        finder.calls
+    }
+
+    fn push(&mut self, code: ErrorCode, message: String, span: Span) {
+        self.errors.push(ValidationError::at_span(
+            code,
+            message,
+            span,
+            self.ctx.lines,
+        ));
+    }
+}
+
+/// An aggregate call and the aggregate it is nested in, if any
+struct AggregateCall {
+    name: String,
+    span: Span,
+    enclosing: Option<String>,
+}
+
+/// Collects aggregate calls in an expression, not descending into subqueries
+struct AggregateCalls<'a> {
+    ctx: &'a CheckContext<'a>,
+    query_depth: usize,
+    enclosing: Vec<String>,
+    calls: Vec<AggregateCall>,
+}
+
+impl Visitor for AggregateCalls<'_> {
+    type Break = ();
+
+    fn pre_visit_query(&mut self, _query: &Query) -> ControlFlow<()> {
+        self.query_depth += 1;
+        ControlFlow::Continue(())
+    }
+
+    fn post_visit_query(&mut self, _query: &Query) -> ControlFlow<()> {
+        self.query_depth -= 1;
+        ControlFlow::Continue(())
+    }
+
+    fn pre_visit_expr(&mut self, expr: &Expr) -> ControlFlow<()> {
+        match expr {
+            Expr::Function(func) if self.query_depth == 0 && is_aggregate(self.ctx, func) => {
+                let name = func.name.to_string();
+                self.calls.push(AggregateCall {
+                    name: name.clone(),
+                    span: func.name.span(),
+                    enclosing: self.enclosing.last().cloned(),
+                });
+                self.enclosing.push(name);
+            }
+            _ => {}
+        }
+        ControlFlow::Continue(())
+    }
+
+    fn post_visit_expr(&mut self, expr: &Expr) -> ControlFlow<()> {
+        match expr {
+            Expr::Function(func) if self.query_depth == 0 && is_aggregate(self.ctx, func) => {
+                self.enclosing.pop();
+            }
+            _ => {}
+        }
+        ControlFlow::Continue(())
+    }
+}
+
+/// Aggregate calls, including combinator forms; window calls (`OVER`) excluded
+fn is_aggregate(ctx: &CheckContext, func: &Function) -> bool {
+    func.over.is_none()
+        && func.name.0.len() == 1
+        && ctx
+            .index
+            .resolve_function(&func.name.to_string())
+            .is_some_and(|f| f.is_aggregate)
+}
+
+/// Decides whether selected expressions only use grouped values
+struct Grouping<'a> {
+    ctx: &'a CheckContext<'a>,
+    keys: Vec<&'a Expr>,
+    /// Select aliases, which other select expressions may refer to
+    aliases: HashMap<&'a str, &'a Expr>,
+}
+
+impl Grouping<'_> {
+    /// `GROUP BY alias`
+    fn is_key_name(&self, name: &Ident) -> bool {
+        self.keys
+            .iter()
+            .any(|key| matches!(key, Expr::Identifier(ident) if ident.value == name.value))
+    }
+
+    /// `GROUP BY 1` refers to the first selected expression
+    fn is_key_position(&self, position: usize) -> bool {
+        self.keys.iter().any(|key| match key {
+            Expr::Value(v) => match &v.value {
+                Value::Number(n, _) => n.parse::<usize>().is_ok_and(|n| n == position + 1),
+                _ => false,
+            },
+            _ => false,
+        })
+    }
+
+    fn is_key(&self, expr: &Expr) -> bool {
+        let text = expr.to_string();
+        self.keys.iter().any(|key| {
+            key.to_string() == text
+                || matches!((column_name(key), column_name(expr)), (Some(a), Some(b)) if a == b)
+        })
+    }
+
+    /// The first column reference in `expr` that is neither grouped nor
+    /// inside an aggregate. Unusual expression kinds are assumed fine.
+    fn ungrouped<'e>(&self, expr: &'e Expr, depth: usize) -> Option<&'e Expr> {
+        if self.is_key(expr) {
+            return None;
+        }
+        match expr {
+            Expr::Identifier(ident) => {
+                if is_template_placeholder(&ident.value) {
+                    return None;
+                }
+                // A reference to another select alias is as grouped as its expression
+                match self.aliases.get(ident.value.as_str()) {
+                    Some(aliased) if depth < 8 => self.ungrouped(aliased, depth + 1).map(|_| expr),
+                    _ => Some(expr),
+                }
+            }
+            Expr::CompoundIdentifier(_) => Some(expr),
+            Expr::Function(func) => {
+                if func.over.is_some() || is_aggregate(self.ctx, func) {
+                    return None;
+                }
+                let FunctionArguments::List(args) = &func.args else {
+                    return None;
+                };
+                args.args.iter().find_map(|arg| {
+                    let arg = match arg {
+                        FunctionArg::Unnamed(arg)
+                        | FunctionArg::Named { arg, .. }
+                        | FunctionArg::ExprNamed { arg, .. } => arg,
+                    };
+                    match arg {
+                        FunctionArgExpr::Expr(e) => self.ungrouped(e, depth),
+                        _ => None,
+                    }
+                })
+            }
+            Expr::BinaryOp { left, right, .. } => self
+                .ungrouped(left, depth)
+                .or_else(|| self.ungrouped(right, depth)),
+            Expr::UnaryOp { expr, .. }
+            | Expr::Nested(expr)
+            | Expr::Cast { expr, .. }
+            | Expr::IsNull(expr)
+            | Expr::IsNotNull(expr)
+            | Expr::IsTrue(expr)
+            | Expr::IsFalse(expr) => self.ungrouped(expr, depth),
+            Expr::Between {
+                expr, low, high, ..
+            } => [expr, low, high]
+                .into_iter()
+                .find_map(|e| self.ungrouped(e, depth)),
+            Expr::InList { expr, list, .. } => self
+                .ungrouped(expr, depth)
+                .or_else(|| list.iter().find_map(|e| self.ungrouped(e, depth))),
+            Expr::Tuple(items) => items.iter().find_map(|e| self.ungrouped(e, depth)),
+            Expr::Case {
+                operand,
+                conditions,
+                else_result,
+                ..
+            } => operand
+                .iter()
+                .chain(else_result)
+                .map(AsRef::as_ref)
+                .chain(conditions.iter().flat_map(|c| [&c.condition, &c.result]))
+                .find_map(|e| self.ungrouped(e, depth)),
+            _ => None,
+        }
+    }
+}
+
+/// The column name of a plain or qualified column reference
+fn column_name(expr: &Expr) -> Option<&str> {
+    match expr {
+        Expr::Identifier(ident) => Some(&ident.value),
+        Expr::CompoundIdentifier(parts) => parts.last().map(|p| p.value.as_str()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::clickhouse_index::test_data::clickhouse_25_8;
+    use crate::{validate, ErrorCode, Schema};
+
+    fn placement_errors(sql: &str) -> Vec<(ErrorCode, String)> {
+        validate(sql, Some(clickhouse_25_8()), &Schema::default())
+            .errors
+            .into_iter()
+            .filter(|e| {
+                matches!(
+                    e.code,
+                    ErrorCode::AggregateInWhere
+                        | ErrorCode::NestedAggregate
+                        | ErrorCode::NotInGroupBy
+                )
+            })
+            .map(|e| {
+                let start = e.start_offset.unwrap_or_default();
+                let end = e.end_offset.unwrap_or_default();
+                (e.code, sql[start..end].to_string())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_aggregates_in_where_and_prewhere() {
+        assert_eq!(
+            placement_errors("SELECT a FROM t PREWHERE max(b) > 1 WHERE count() > 10"),
+            vec![
+                (ErrorCode::AggregateInWhere, "max".to_string()),
+                (ErrorCode::AggregateInWhere, "count".to_string()),
+            ]
+        );
+        // Aggregates in a subquery of WHERE are fine
+        assert!(placement_errors("SELECT a FROM t WHERE b > (SELECT avg(b) FROM t)").is_empty());
+    }
+
+    #[test]
+    fn test_nested_aggregates() {
+        assert_eq!(
+            placement_errors("SELECT sum(count(x)), avg(sumIf(y, y > 0)) FROM t"),
+            vec![
+                (ErrorCode::NestedAggregate, "count".to_string()),
+                (ErrorCode::NestedAggregate, "sumIf".to_string()),
+            ]
+        );
+        // Window functions over aggregates are allowed
+        assert!(placement_errors("SELECT a, sum(count()) OVER () FROM t GROUP BY a").is_empty());
+    }
+
+    #[test]
+    fn test_columns_missing_from_group_by() {
+        assert_eq!(
+            placement_errors("SELECT a, b, count() FROM t GROUP BY a"),
+            vec![(ErrorCode::NotInGroupBy, "b".to_string())]
+        );
+        assert_eq!(
+            placement_errors("SELECT toStartOfHour(ts) AS h, user_id, count() FROM t GROUP BY h"),
+            vec![(ErrorCode::NotInGroupBy, "user_id".to_string())]
+        );
+        assert_eq!(
+            placement_errors("SELECT a, c, count() FROM t GROUP BY ROLLUP(a, b)"),
+            vec![(ErrorCode::NotInGroupBy, "c".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_grouped_expressions_are_accepted() {
+        let sqls = [
+            "SELECT a, b + 1, count() FROM t GROUP BY a, b",
+            "SELECT toStartOfHour(ts) AS h, count() AS c, c / 2 FROM t GROUP BY h",
+            "SELECT toStartOfHour(ts), sum(x) FROM t GROUP BY toStartOfHour(ts)",
+            "SELECT t.a, count() FROM t GROUP BY a",
+            "SELECT a, 'label', uniqExact(u) FROM t GROUP BY 1",
+            "SELECT a, b FROM t",
+            "SELECT a, b, count() FROM t GROUP BY ALL",
+            "SELECT a, if(count() > 1, 'many', 'one') FROM t GROUP BY a",
+            "SELECT a, b, count() FROM t GROUP BY CUBE(a, b)",
+            "SELECT a, b, count() FROM t GROUP BY ROLLUP(a, b)",
+            "SELECT a, b, count() FROM t GROUP BY GROUPING SETS ((a), (b))",
+        ];
+        for sql in sqls {
+            assert!(
+                placement_errors(sql).is_empty(),
+                "{sql}: {:?}",
+                placement_errors(sql)
+            );
+        }
+    }
+}
@@ -0,0 +1,306 @@
+//! Column data type validation.
+//!
+//! sqlparser normalizes type names (`Uint64` parses as `UInt64`) and does not
+//! know most `ClickHouse` types, so types are read back from the tokens that
+//! follow each column name rather than from the AST.
+
+use super::{is_template_placeholder, token_starting_at, CheckContext};
+use crate::suggest::{did_you_mean, suggest};
+use crate::{ErrorCode, ValidationError};
+use sqlparser::ast::{AlterTableOperation, ColumnDef, DataType, Ident, Statement};
+use sqlparser::tokenizer::{Span, Token, TokenWithSpan, Word};
+
+/// Reports unknown data types in `CREATE TABLE` columns and in
+/// `ALTER TABLE ... ADD COLUMN / MODIFY COLUMN`, including types nested
+/// inside `Nullable`, `Array`, `Map`, `Tuple` and friends
+pub(super) fn check_column_types(
+    statements: &[Statement],
+    ctx: &CheckContext,
+    errors: &mut Vec<ValidationError>,
+) {
+    check_types_after(typed_columns(statements), ctx, errors);
+}
+
+/// Reports unknown data types in a column definition list fragment
+pub(super) fn check_column_def_types(
+    columns: &[ColumnDef],
+    ctx: &CheckContext,
+    errors: &mut Vec<ValidationError>,
+) {
+    let names = columns
+        .iter()
+        .filter(|c| c.data_type != DataType::Unspecified)
+        .map(|c| &c.name)
+        .filter(|ident| ident.span != Span::empty())
+        .collect();
+    check_types_after(names, ctx, errors);
+}
+
+/// Reads and checks the type that follows each column name
+fn check_types_after(columns: Vec<&Ident>, ctx: &CheckContext, errors: &mut Vec<ValidationError>) {
+    for column in columns {
+        let Some(pos) = token_starting_at(ctx.tokens, column.span.start) else {
+            continue;
+        };
+        let mut reader = TypeReader {
+            tokens: ctx.tokens,
+            pos: pos + 1,
+            ctx,
+            errors,
+        };
+        reader.read_type();
+    }
+}
+
+/// Names of columns declared together with a data type
+fn typed_columns(statements: &[Statement]) -> Vec<&Ident> {
+    let mut columns = Vec::new();
+    for statement in statements {
+        match statement {
+            Statement::CreateTable(create) => {
+                columns.extend(
+                    create
+                        .columns
+                        .iter()
+                        .filter(|c| c.data_type != DataType::Unspecified)
+                        .map(|c| &c.name),
+                );
+            }
+            Statement::AlterTable { operations, .. } => {
+                for operation in operations {
+                    match operation {
+                        AlterTableOperation::AddColumn { column_def, .. } => {
+                            columns.push(&column_def.name);
+                        }
+                        AlterTableOperation::ModifyColumn { col_name, .. } => {
+                            columns.push(col_name);
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    columns.retain(|ident| ident.span != Span::empty());
+    columns
+}
+
+/// How the arguments of a parameterized type are read
+enum TypeArgs {
+    /// Every argument is a data type: `Nullable(T)`, `Map(K, V)`
+    Types,
+    /// Arguments are data types, optionally preceded by an element name:
+    /// `Tuple(a T, b U)`, `Nested(a T)`
+    NamedTypes,
+    /// An aggregate function followed by argument types:
+    /// `AggregateFunction(sum, UInt64)`
+    Aggregate,
+    /// Literal parameters such as `Decimal(10, 2)`, not checked
+    Literals,
+}
+
+struct TypeReader<'a, 'b> {
+    tokens: &'a [TokenWithSpan],
+    pos: usize,
+    ctx: &'a CheckContext<'a>,
+    errors: &'b mut Vec<ValidationError>,
+}
+
+impl TypeReader<'_, '_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|t| &t.token)
+    }
+
+    fn word_at(&self, pos: usize) -> Option<&Word> {
+        match self.tokens.get(pos).map(|t| &t.token) {
+            Some(Token::Word(word)) => Some(word),
+            _ => None,
+        }
+    }
+
+    /// Reads one data type, reporting it if unknown, then its arguments
+    fn read_type(&mut self) {
+        let Some(first) = self.word_at(self.pos) else {
+            return;
+        };
+        let start = self.tokens[self.pos].span.start;
+        let mut name = first.value.clone();
+        let mut end = self.pos;
+
+        // Multi-word names (`DOUBLE PRECISION`): take the longest run of
+        // words that forms a known type
+        let mut phrase = name.clone();
+        let mut next = self.pos + 1;
+        while let Some(word) = self.word_at(next) {
+            phrase = format!("{phrase} {}", word.value);
+            if !self.ctx.index.is_data_type_phrase(&phrase) {
+                break;
+            }
+            if self.ctx.index.data_type(&phrase).is_some() {
+                name.clone_from(&phrase);
+                end = next;
+            }
+            next += 1;
+        }
+        let span = Span::new(start, self.tokens[end].span.end);
+        self.pos = end + 1;
+
+        let args = match self.ctx.index.canonical_data_type(&name) {
+            Some("Nullable" | "Array" | "LowCardinality" | "Map" | "Variant") => TypeArgs::Types,
+            Some("Tuple" | "Nested") => TypeArgs::NamedTypes,
+            Some("AggregateFunction" | "SimpleAggregateFunction") => TypeArgs::Aggregate,
+            Some(_) => TypeArgs::Literals,
+            None => {
+                if !is_template_placeholder(&name) {
+                    self.report_unknown(&name, span);
+                }
+                TypeArgs::Literals
+            }
+        };
+
+        if self.peek() == Some(&Token::LParen) {
+            self.pos += 1;
+            self.read_args(&args);
+        }
+    }
+
+    fn read_args(&mut self, args: &TypeArgs) {
+        let mut first = true;
+        loop {
+            match args {
+                TypeArgs::Types => self.read_type(),
+                TypeArgs::NamedTypes => {
+                    // `name Type`, unless the two words are one type name
+                    if let (Some(a), Some(b)) = (self.word_at(self.pos), self.word_at(self.pos + 1))
+                    {
+                        let phrase = format!("{} {}", a.value, b.value);
+                        if !self.ctx.index.is_data_type_phrase(&phrase) {
+                            self.pos += 1;
+                        }
+                    }
+                    self.read_type();
+                }
+                TypeArgs::Aggregate if !first => self.read_type(),
+                TypeArgs::Aggregate | TypeArgs::Literals => {}
+            }
+            first = false;
+
+            self.skip_to_arg_end();
+            match self.peek() {
+                Some(Token::Comma) => self.pos += 1,
+                Some(Token::RParen) => {
+                    self.pos += 1;
+                    return;
+                }
+                _ => return,
+            }
+        }
+    }
+
+    /// Moves to the `,` or `)` ending the current argument
+    fn skip_to_arg_end(&mut self) {
+        let mut depth = 0usize;
+        while let Some(token) = self.peek() {
+            match token {
+                Token::Comma | Token::RParen if depth == 0 => return,
+                Token::LParen => depth += 1,
+                Token::RParen => depth -= 1,
+                _ => {}
+            }
+            self.pos += 1;
+        }
+    }
+
+    fn report_unknown(&mut self, name: &str, span: Span) {
+        let suggestions = suggest(name, self.ctx.index.data_type_names());
+        let message = format!("Unknown data type `{name}`.{}", did_you_mean(&suggestions));
+        self.errors.push(
+            ValidationError::at_span(ErrorCode::UnknownDataType, message, span, self.ctx.lines)
+                .with_suggestions(suggestions),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::clickhouse_index::test_data::clickhouse_25_8;
+    use crate::{validate, ErrorCode, Schema};
+
+    fn unknown_types(sql: &str) -> Vec<(String, Option<String>)> {
+        validate(sql, Some(clickhouse_25_8()), &Schema::default())
+            .errors
+            .into_iter()
+            .filter(|e| e.code == ErrorCode::UnknownDataType)
+            .map(|e| {
+                let start = e.start_offset.unwrap_or_default();
+                let end = e.end_offset.unwrap_or_default();
+                (sql[start..end].to_string(), e.suggestions.first().cloned())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_reports_misspelled_types_with_suggestions() {
+        let found = unknown_types(
+            "CREATE TABLE t (id Uint64, ts DateTime46, name Strng) ENGINE = MergeTree ORDER BY id",
+        );
+        assert_eq!(
+            found,
+            vec![
+                ("Uint64".to_string(), Some("UInt64".to_string())),
+                ("DateTime46".to_string(), Some("DateTime64".to_string())),
+                ("Strng".to_string(), Some("String".to_string())),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_checks_nested_types() {
+        let found = unknown_types(
+            "CREATE TABLE t (\
+                a Nullable(Strin), \
+                b Array(LowCardinality(Strng)), \
+                c Map(String, Array(Uint8)), \
+                d Tuple(x UInt64, y Flaot64), \
+                e Nested(k String, v Int23)\
+            ) ENGINE = Memory",
+        );
+        let names: Vec<&str> = found.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, vec!["Strin", "Strng", "Uint8", "Flaot64", "Int23"]);
+    }
+
+    #[test]
+    fn test_accepts_valid_types_and_aliases() {
+        let sql = "CREATE TABLE t (\
+                a UInt64, b Nullable(String), c LowCardinality(Nullable(String)), \
+                d Array(Tuple(String, UInt8)), e Map(String, Array(Float64)), \
+                f Tuple(name String, score Nullable(Float32)), g Nested(k String, v UInt32), \
+                h DateTime64(3, 'UTC'), i Decimal(18, 4), j Enum8('a' = 1, 'b' = 2), \
+                k AggregateFunction(sum, UInt64), l SimpleAggregateFunction(max, DateTime), \
+                m BIGINT UNSIGNED, n DOUBLE PRECISION, o datetime, p FixedString(16), \
+                q Variant(String, UInt64), r bool DEFAULT true CODEC(ZSTD(1))\
+            ) ENGINE = MergeTree ORDER BY a";
+        assert!(unknown_types(sql).is_empty());
+    }
+
+    #[test]
+    fn test_type_names_are_case_sensitive() {
+        let found = unknown_types("CREATE TABLE t (a uint64, b string) ENGINE = Memory");
+        assert_eq!(
+            found,
+            vec![
+                ("uint64".to_string(), Some("UInt64".to_string())),
+                ("string".to_string(), Some("String".to_string())),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_checks_alter_table_columns() {
+        let found =
+            unknown_types("ALTER TABLE t ADD COLUMN a Strng, MODIFY COLUMN b Nullable(Int65)");
+        let names: Vec<&str> = found.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, vec!["Strng", "Int65"]);
+    }
+}
@@ -0,0 +1,522 @@
+//! Table engine validation for the `ENGINE = ...` clause of `CREATE TABLE`.
+//!
+//! Engine names are checked against the loaded table engine list. Engines
+//! with well-known signatures also have their arguments checked: how many
+//! there are, and whether column arguments name columns of the table.
+
+use super::{is_template_placeholder, token_starting_at, CheckContext};
+use crate::suggest::{did_you_mean, suggest};
+use crate::{ErrorCode, ValidationError};
+use sqlparser::ast::{CreateTable, Spanned, Statement};
+use sqlparser::keywords::Keyword;
+use sqlparser::tokenizer::{Span, Token, TokenWithSpan};
+
+/// Reports unknown engines and engine arguments that do not fit the engine
+pub(super) fn check_engines(
+    statements: &[Statement],
+    ctx: &CheckContext,
+    errors: &mut Vec<ValidationError>,
+) {
+    for statement in statements {
+        if let Statement::CreateTable(create) = statement {
+            check_create_table(create, ctx, errors);
+        }
+    }
+}
+
+fn check_create_table(create: &CreateTable, ctx: &CheckContext, errors: &mut Vec<ValidationError>) {
+    let Some(start) = token_starting_at(ctx.tokens, create.name.span().start) else {
+        return;
+    };
+    let Some(clause) = EngineClause::find(&ctx.tokens[start..]) else {
+        return;
+    };
+    let Token::Word(name) = &clause.name.token else {
+        return;
+    };
+    let engine = name.value.as_str();
+    if is_template_placeholder(engine) {
+        return;
+    }
+
+    if !ctx.index.is_table_engine(engine) {
+        let suggestions = suggest(engine, ctx.index.table_engine_names());
+        let message = format!(
+            "Unknown table engine `{engine}`.{}",
+            did_you_mean(&suggestions)
+        );
+        errors.push(
+            ValidationError::at_span(
+                ErrorCode::UnknownTableEngine,
+                message,
+                clause.name.span,
+                ctx.lines,
+            )
+            .with_suggestions(suggestions),
+        );
+        return;
+    }
+
+    let Some(signature) = Signature::of(engine, &clause.args) else {
+        return;
+    };
+    let columns: Vec<&str> = create
+        .columns
+        .iter()
+        .map(|c| c.name.value.as_str())
+        .collect();
+    let mut check = ArgumentCheck {
+        engine,
+        columns: &columns,
+        ctx,
+        errors,
+    };
+    check.arity(&signature, &clause);
+    for (param, arg) in signature.params().zip(&clause.args) {
+        check.argument(*param, arg);
+    }
+}
+
+/// The tokens of an `ENGINE [=] Name[(args)]` clause
+struct EngineClause<'a> {
+    /// Covers the clause from `ENGINE` to the closing paren
+    span: Span,
+    name: &'a TokenWithSpan,
+    /// Tokens of each top-level argument, without the separating commas
+    args: Vec<&'a [TokenWithSpan]>,
+}
+
+impl<'a> EngineClause<'a> {
+    /// Finds the first `ENGINE` clause outside parentheses, stopping at the
+    /// end of the statement
+    fn find(tokens: &'a [TokenWithSpan]) -> Option<Self> {
+        let mut depth = 0usize;
+        let keyword = tokens.iter().position(|t| {
+            match &t.token {
+                Token::LParen => depth += 1,
+                Token::RParen => depth = depth.saturating_sub(1),
+                _ => {}
+            }
+            depth == 0
+                && matches!(&t.token, Token::Word(w) if w.keyword == Keyword::ENGINE && w.quote_style.is_none())
+                || matches!(t.token, Token::SemiColon)
+        })?;
+        if matches!(tokens[keyword].token, Token::SemiColon) {
+            return None;
+        }
+
+        let mut pos = keyword + 1;
+        if tokens.get(pos).is_some_and(|t| t.token == Token::Eq) {
+            pos += 1;
+        }
+        let name = tokens
+            .get(pos)
+            .filter(|t| matches!(t.token, Token::Word(_)))?;
+        let mut clause = Self {
+            span: Span::new(tokens[keyword].span.start, name.span.end),
+            name,
+            args: Vec::new(),
+        };
+        pos += 1;
+        if tokens.get(pos).is_none_or(|t| t.token != Token::LParen) {
+            return Some(clause);
+        }
+
+        pos += 1;
+        let mut arg_start = pos;
+        let mut depth = 0usize;
+        while let Some(token) = tokens.get(pos) {
+            match token.token {
+                Token::LParen => depth += 1,
+                Token::RParen if depth > 0 => depth -= 1,
+                Token::Comma | Token::RParen if depth == 0 => {
+                    let arg = &tokens[arg_start..pos];
+                    // `Engine()` has no arguments rather than one empty one
+                    if !arg.is_empty() || token.token == Token::Comma || !clause.args.is_empty() {
+                        clause.args.push(arg);
+                    }
+                    if token.token == Token::RParen {
+                        clause.span.end = token.span.end;
+                        return Some(clause);
+                    }
+                    arg_start = pos + 1;
+                }
+                Token::SemiColon | Token::EOF => break,
+                _ => {}
+            }
+            pos += 1;
+        }
+        Some(clause)
+    }
+}
+
+/// What an engine expects in an argument position
+#[derive(Clone, Copy)]
+enum Param {
+    /// A column of the table, e.g. the `ReplacingMergeTree` version column
+    Column(&'static str),
+    /// A column or a tuple of columns, e.g. the `SummingMergeTree` columns
+    Columns(&'static str),
+    /// A string literal, e.g. a replica path
+    String(&'static str),
+    /// Anything; only counted
+    Any(&'static str),
+}
+
+impl Param {
+    fn name(self) -> &'static str {
+        match self {
+            Param::Column(name) | Param::Columns(name) | Param::String(name) | Param::Any(name) => {
+                name
+            }
+        }
+    }
+}
+
+/// Required parameters followed by optional ones
+struct Signature {
+    required: Vec<Param>,
+    optional: Vec<Param>,
+}
+
+impl Signature {
+    /// Signature of the engines whose arguments are checked; `None` for the rest
+    fn of(engine: &str, args: &[&[TokenWithSpan]]) -> Option<Self> {
+        if let Some(base) = engine.strip_prefix("Replicated") {
+            let mut signature = Self::of(base, &[])?;
+            // The ZooKeeper path and replica name are optional, but come as a pair
+            if args.first().is_some_and(
+                |arg| matches!(arg, [t] if matches!(t.token, Token::SingleQuotedString(_))),
+            ) {
+                signature.required.splice(
+                    0..0,
+                    [
+                        Param::String("ZooKeeper path"),
+                        Param::String("replica name"),
+                    ],
+                );
+            }
+            return Some(signature);
+        }
+
+        let (required, optional): (&[Param], &[Param]) = match engine {
+            "MergeTree"
+            | "AggregatingMergeTree"
+            | "Memory"
+            | "Log"
+            | "TinyLog"
+            | "StripeLog"
+            | "Null"
+            | "Set" => (&[], &[]),
+            "ReplacingMergeTree" => (
+                &[],
+                &[
+                    Param::Column("version column"),
+                    Param::Column("is_deleted column"),
+                ],
+            ),
+            "SummingMergeTree" | "CoalescingMergeTree" => {
+                (&[], &[Param::Columns("columns to sum")])
+            }
+            "CollapsingMergeTree" => (&[Param::Column("sign column")], &[]),
+            "VersionedCollapsingMergeTree" => (
+                &[
+                    Param::Column("sign column"),
+                    Param::Column("version column"),
+                ],
+                &[],
+            ),
+            "GraphiteMergeTree" => (&[Param::String("config section")], &[]),
+            "Distributed" => (
+                &[
+                    Param::Any("cluster"),
+                    Param::Any("database"),
+                    Param::Any("table"),
+                ],
+                &[Param::Any("sharding key"), Param::String("policy name")],
+            ),
+            "Dictionary" => (&[Param::Any("dictionary")], &[]),
+            "Merge" => (&[Param::Any("database"), Param::Any("table regexp")], &[]),
+            _ => return None,
+        };
+        Some(Self {
+            required: required.to_vec(),
+            optional: optional.to_vec(),
+        })
+    }
+
+    fn params(&self) -> impl Iterator<Item = &Param> {
+        self.required.iter().chain(&self.optional)
+    }
+
+    /// Parameter names, with the optional ones marked as such
+    fn describe(&self) -> String {
+        let names = |params: &[Param]| -> String {
+            let names: Vec<&str> = params.iter().map(|p| p.name()).collect();
+            names.join(", ")
+        };
+        match (self.required.is_empty(), self.optional.is_empty()) {
+            (_, true) => names(&self.required),
+            (true, false) => format!("optional: {}", names(&self.optional)),
+            (false, false) => format!(
+                "{}; optional: {}",
+                names(&self.required),
+                names(&self.optional)
+            ),
+        }
+    }
+}
+
+struct ArgumentCheck<'a, 'b> {
+    engine: &'a str,
+    columns: &'a [&'a str],
+    ctx: &'a CheckContext<'a>,
+    errors: &'b mut Vec<ValidationError>,
+}
+
+impl ArgumentCheck<'_, '_> {
+    fn arity(&mut self, signature: &Signature, clause: &EngineClause) {
+        let engine = self.engine;
+        let given = clause.args.len();
+        let min = signature.required.len();
+        let max = min + signature.optional.len();
+        let plural = |n: usize| if n == 1 { "argument" } else { "arguments" };
+
+        let message = if given < min {
+            format!(
+                "`{engine}` requires {min} {} ({}), got {given}.",
+                plural(min),
+                signature.describe()
+            )
+        } else if given > max && max == 0 {
+            format!("`{engine}` takes no arguments.")
+        } else if given > max {
+            format!(
+                "`{engine}` takes at most {max} {} ({}), got {given}.",
+                plural(max),
+                signature.describe()
+            )
+        } else {
+            return;
+        };
+        self.report(message, clause.span, Vec::new());
+    }
+
+    fn argument(&mut self, param: Param, arg: &[TokenWithSpan]) {
+        let (Some(first), Some(last)) = (arg.first(), arg.last()) else {
+            return;
+        };
+        let span = Span::new(first.span.start, last.span.end);
+        let engine = self.engine;
+
+        match param {
+            Param::Column(name) => match arg {
+                [column] => self.column(name, column),
+                _ => self.report(
+                    format!("The {name} of `{engine}` must be a column name."),
+                    span,
+                    Vec::new(),
+                ),
+            },
+            Param::Columns(name) => match arg {
+                [column] => self.column(name, column),
+                [open, inner @ .., close]
+                    if open.token == Token::LParen && close.token == Token::RParen =>
+                {
+                    // Columns at even positions, separated by single commas
+                    let separated = inner.len() % 2 == 1
+                        && inner
+                            .iter()
+                            .enumerate()
+                            .all(|(i, t)| (t.token == Token::Comma) == (i % 2 == 1));
+                    if separated {
+                        for column in inner.iter().step_by(2) {
+                            self.column(name, column);
+                        }
+                    } else {
+                        self.report(
+                            format!("The {name} of `{engine}` must be a tuple of column names."),
+                            span,
+                            Vec::new(),
+                        );
+                    }
+                }
+                _ => self.report(
+                    format!("The {name} of `{engine}` must be a column name or a tuple of column names."),
+                    span,
+                    Vec::new(),
+                ),
+            },
+            Param::String(name) => {
+                if !matches!(arg, [t] if matches!(t.token, Token::SingleQuotedString(_))) {
+                    self.report(
+                        format!("The {name} of `{engine}` must be a string literal."),
+                        span,
+                        Vec::new(),
+                    );
+                }
+            }
+            Param::Any(_) => {}
+        }
+    }
+
+    /// Checks that a single-token argument names a column of the table
+    fn column(&mut self, param: &str, token: &TokenWithSpan) {
+        let engine = self.engine;
+        let Token::Word(word) = &token.token else {
+            self.report(
+                format!("The {param} of `{engine}` must be a column name."),
+                token.span,
+                Vec::new(),
+            );
+            return;
+        };
+        // Columns are unknown for `CREATE TABLE ... AS other`
+        if self.columns.is_empty()
+            || self.columns.contains(&word.value.as_str())
+            || is_template_placeholder(&word.value)
+        {
+            return;
+        }
+
+        let suggestions = suggest(&word.value, self.columns.iter().copied());
+        let message = format!(
+            "`{}` is not a column of this table; `{engine}` expects its {param} here.{}",
+            word.value,
+            did_you_mean(&suggestions)
+        );
+        self.report(message, token.span, suggestions);
+    }
+
+    fn report(&mut self, message: String, span: Span, suggestions: Vec<String>) {
+        self.errors.push(
+            ValidationError::at_span(
+                ErrorCode::InvalidEngineArguments,
+                message,
+                span,
+                self.ctx.lines,
+            )
+            .with_suggestions(suggestions),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::clickhouse_index::test_data::clickhouse_25_8;
+    use crate::{validate, ErrorCode, Schema, ValidationError};
+
+    fn engine_errors(sql: &str) -> Vec<ValidationError> {
+        validate(sql, Some(clickhouse_25_8()), &Schema::default())
+            .errors
+            .into_iter()
+            .filter(|e| {
+                matches!(
+                    e.code,
+                    ErrorCode::UnknownTableEngine | ErrorCode::InvalidEngineArguments
+                )
+            })
+            .collect()
+    }
+
+    fn text<'a>(sql: &'a str, error: &ValidationError) -> &'a str {
+        &sql[error.start_offset.unwrap_or_default()..error.end_offset.unwrap_or_default()]
+    }
+
+    #[test]
+    fn test_reports_unknown_engine_with_suggestion() {
+        let sql = "CREATE TABLE t (a UInt64) ENGINE = MergeTre() ORDER BY a";
+        let errors = engine_errors(sql);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].code, ErrorCode::UnknownTableEngine);
+        assert_eq!(text(sql, &errors[0]), "MergeTre");
+        assert_eq!(
+            errors[0].suggestions.first().map(String::as_str),
+            Some("MergeTree")
+        );
+    }
+
+    #[test]
+    fn test_accepts_valid_engines() {
+        let sqls = [
+            "CREATE TABLE t (engine String) ENGINE = Memory",
+            "CREATE TABLE t (a UInt64) ENGINE MergeTree ORDER BY a",
+            "CREATE TABLE t (a UInt64, ver UInt32) ENGINE = ReplacingMergeTree(ver) ORDER BY a",
+            "CREATE TABLE t (a UInt64, s Int8, v UInt8) ENGINE = VersionedCollapsingMergeTree(s, v) ORDER BY a",
+            "CREATE TABLE t (a UInt64, x UInt64) ENGINE = SummingMergeTree(x) ORDER BY a",
+            "CREATE TABLE t (a UInt64, v UInt32) ENGINE = ReplicatedReplacingMergeTree('/t/{shard}', '{replica}', v) ORDER BY a",
+            "CREATE TABLE t (a UInt64) ENGINE = Distributed(cluster, db, local_t, a)",
+            "CREATE TABLE t AS other ENGINE = ReplacingMergeTree(ver) ORDER BY a",
+            "CREATE TABLE t (a UInt64, x UInt64, y UInt64) ENGINE = SummingMergeTree((x, y)) ORDER BY a",
+            "CREATE TABLE t (a UInt64) ENGINE = Distributed(cluster, db, local_t, rand())",
+            "CREATE TABLE t (a UInt64) ENGINE = Distributed(cluster, 'db', local_t, cityHash64(a), 'hot')",
+        ];
+        for sql in sqls {
+            assert!(engine_errors(sql).is_empty(), "{sql}");
+        }
+    }
+
+    #[test]
+    fn test_reports_unknown_column_arguments() {
+        let sql = "CREATE TABLE t (id UInt64, version UInt32) ENGINE = ReplacingMergeTree(versoin) ORDER BY id";
+        let errors = engine_errors(sql);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(text(sql, &errors[0]), "versoin");
+        assert_eq!(errors[0].suggestions, vec!["version".to_string()]);
+    }
+
+    #[test]
+    fn test_reports_bad_column_tuples() {
+        let sql =
+            "CREATE TABLE t (a UInt64, x UInt64) ENGINE = SummingMergeTree((x, y)) ORDER BY a";
+        let errors = engine_errors(sql);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(text(sql, &errors[0]), "y");
+
+        for sql in [
+            "CREATE TABLE t (a UInt64) ENGINE = SummingMergeTree(()) ORDER BY a",
+            "CREATE TABLE t (a UInt64) ENGINE = SummingMergeTree((a,, a)) ORDER BY a",
+        ] {
+            let errors = engine_errors(sql);
+            assert_eq!(errors.len(), 1, "{sql}");
+            assert!(errors[0].message.contains("tuple of column names"), "{sql}");
+        }
+    }
+
+    #[test]
+    fn test_reports_wrong_argument_count_on_engine_clause() {
+        let sql = "CREATE TABLE t (a UInt64) ENGINE = Distributed(cluster, db)";
+        let errors = engine_errors(sql);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(text(sql, &errors[0]), "ENGINE = Distributed(cluster, db)");
+        assert_eq!(
+            errors[0].message,
+            "`Distributed` requires 3 arguments (cluster, database, table; optional: sharding key, policy name), got 2."
+        );
+
+        let sql = "CREATE TABLE t (a UInt64) ENGINE = MergeTree(a) ORDER BY a";
+        assert_eq!(
+            engine_errors(sql)[0].message,
+            "`MergeTree` takes no arguments."
+        );
+
+        let sql = "CREATE TABLE t (a UInt64) ENGINE = CollapsingMergeTree() ORDER BY a";
+        assert!(engine_errors(sql)[0].message.contains("sign column"));
+    }
+
+    #[test]
+    fn test_replicated_engines_take_path_and_replica_pair() {
+        let sql = "CREATE TABLE t (a UInt64) ENGINE = ReplicatedMergeTree('/t/{shard}') ORDER BY a";
+        let errors = engine_errors(sql);
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].message.contains("replica name"));
+    }
+
+    #[test]
+    fn test_string_arguments() {
+        let sql = "CREATE TABLE t (a UInt64) ENGINE = GraphiteMergeTree(rollup) ORDER BY a";
+        let errors = engine_errors(sql);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(text(sql, &errors[0]), "rollup");
+    }
+}
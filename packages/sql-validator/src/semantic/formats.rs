@@ -0,0 +1,170 @@
+//! `FORMAT` clause validation.
+//!
+//! `SELECT ... FORMAT X` writes its result in `X`, so `X` must be an output
+//! format; `INSERT ... FORMAT X` reads data in `X`, so it must be an input one.
+
+use super::{is_template_placeholder, CheckContext};
+use crate::suggest::{did_you_mean, suggest};
+use crate::{ErrorCode, FormatInfo, ValidationError};
+use sqlparser::ast::{FormatClause, Ident, Query, Statement, Visit, Visitor};
+use std::ops::ControlFlow;
+
+/// Reports unknown formats and formats used in the wrong direction
+pub(super) fn check_formats(
+    statements: &[Statement],
+    ctx: &CheckContext,
+    errors: &mut Vec<ValidationError>,
+) {
+    let mut visitor = Formats { ctx, errors };
+    for statement in statements {
+        let _ = statement.visit(&mut visitor);
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Direction {
+    Input,
+    Output,
+}
+
+impl Direction {
+    fn supported_by(self, format: &FormatInfo) -> bool {
+        match self {
+            Direction::Input => format.is_input,
+            Direction::Output => format.is_output,
+        }
+    }
+}
+
+struct Formats<'a, 'b> {
+    ctx: &'a CheckContext<'a>,
+    errors: &'b mut Vec<ValidationError>,
+}
+
+impl Visitor for Formats<'_, '_> {
+    type Break = ();
+
+    fn pre_visit_query(&mut self, query: &Query) -> ControlFlow<()> {
+        if let Some(FormatClause::Identifier(ident)) = &query.format_clause {
+            self.check(ident, Direction::Output);
+        }
+        ControlFlow::Continue(())
+    }
+
+    fn pre_visit_statement(&mut self, statement: &Statement) -> ControlFlow<()> {
+        if let Statement::Insert(insert) = statement {
+            if let Some(format) = &insert.format_clause {
+                self.check(&format.ident, Direction::Input);
+            }
+        }
+        ControlFlow::Continue(())
+    }
+}
+
+impl Formats<'_, '_> {
+    fn check(&mut self, ident: &Ident, direction: Direction) {
+        let name = ident.value.as_str();
+        if is_template_placeholder(name) {
+            return;
+        }
+
+        let candidates = self
+            .ctx
+            .index
+            .data
+            .formats
+            .iter()
+            .filter(|f| direction.supported_by(f))
+            .map(|f| f.name.as_str());
+        let suggestions = suggest(name, candidates);
+
+        let (code, message) = match self.ctx.index.format(name) {
+            None => (
+                ErrorCode::UnknownFormat,
+                format!("Unknown format `{name}`.{}", did_you_mean(&suggestions)),
+            ),
+            Some(format) if direction.supported_by(format) => return,
+            Some(format) => {
+                let problem = match direction {
+                    Direction::Output => "is input-only and cannot be used to output query results",
+                    Direction::Input => "is output-only and cannot be used to read inserted data",
+                };
+                (
+                    ErrorCode::WrongFormatDirection,
+                    format!(
+                        "Format `{}` {problem}.{}",
+                        format.name,
+                        did_you_mean(&suggestions)
+                    ),
+                )
+            }
+        };
+
+        self.errors.push(
+            ValidationError::at_span(code, message, ident.span, self.ctx.lines)
+                .with_suggestions(suggestions),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::clickhouse_index::test_data::clickhouse_25_8;
+    use crate::{validate, ErrorCode, Schema, ValidationError};
+
+    fn format_errors(sql: &str) -> Vec<ValidationError> {
+        validate(sql, Some(clickhouse_25_8()), &Schema::default())
+            .errors
+            .into_iter()
+            .filter(|e| {
+                matches!(
+                    e.code,
+                    ErrorCode::UnknownFormat | ErrorCode::WrongFormatDirection
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_accepts_formats_in_the_right_direction() {
+        assert!(format_errors("SELECT * FROM t FORMAT JSONEachRow").is_empty());
+        assert!(format_errors("SELECT * FROM t FORMAT Pretty").is_empty());
+        assert!(format_errors("INSERT INTO t FORMAT CSV").is_empty());
+        assert!(format_errors("INSERT INTO t FORMAT JSONAsString").is_empty());
+    }
+
+    #[test]
+    fn test_reports_unknown_format_with_suggestion() {
+        let sql = "SELECT * FROM t FORMAT JSONEachRo";
+        let errors = format_errors(sql);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].code, ErrorCode::UnknownFormat);
+        assert_eq!(errors[0].start_offset, Some(23));
+        assert_eq!(
+            errors[0].suggestions.first().map(String::as_str),
+            Some("JSONEachRow")
+        );
+    }
+
+    #[test]
+    fn test_select_with_input_only_format() {
+        let errors = format_errors("SELECT * FROM t FORMAT JSONAsString");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].code, ErrorCode::WrongFormatDirection);
+        assert!(errors[0].message.contains("input-only"));
+        assert!(!errors[0].suggestions.contains(&"JSONAsString".to_string()));
+    }
+
+    #[test]
+    fn test_insert_with_output_only_format() {
+        let errors = format_errors("INSERT INTO t FORMAT PrettyCompact");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].code, ErrorCode::WrongFormatDirection);
+        assert!(errors[0].message.contains("output-only"));
+        // Suggestions only offer formats that can be read
+        let index = clickhouse_25_8();
+        for suggestion in &errors[0].suggestions {
+            assert!(index.format(suggestion).is_some_and(|f| f.is_input));
+        }
+    }
+}
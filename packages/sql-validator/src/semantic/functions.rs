@@ -0,0 +1,338 @@
+//! Unknown-function detection and argument counts.
+
+use super::{is_template_placeholder, CheckContext};
+use crate::combinators::{self, CombinatorForm};
+use crate::signature::Signature;
+use crate::suggest::{did_you_mean, suggest};
+use crate::{ErrorCode, FunctionInfo, ValidationError};
+use sqlparser::ast::{Expr, Function, FunctionArguments, Spanned, Visit, Visitor};
+use std::ops::ControlFlow;
+
+/// Reports every function call whose name is not a known function, alias or
+/// aggregate function with combinator suffixes, and calls whose argument count
+/// no known overload accepts once combinators are accounted for. Overloads come
+/// from the documented syntax, or for common functions documented without one
+/// from `undocumented_syntax`; other functions get no argument count check.
+pub(super) fn check_unknown_functions<T: Visit>(
+    nodes: &[T],
+    ctx: &CheckContext,
+    errors: &mut Vec<ValidationError>,
+) {
+    let mut visitor = UnknownFunctions { ctx, errors };
+    for node in nodes {
+        let _ = node.visit(&mut visitor);
+    }
+}
+
+struct UnknownFunctions<'a, 'b> {
+    ctx: &'a CheckContext<'a>,
+    errors: &'b mut Vec<ValidationError>,
+}
+
+impl Visitor for UnknownFunctions<'_, '_> {
+    type Break = ();
+
+    fn pre_visit_expr(&mut self, expr: &Expr) -> ControlFlow<()> {
+        if let Expr::Function(func) = expr {
+            self.check(func);
+        }
+        ControlFlow::Continue(())
+    }
+}
+
+impl UnknownFunctions<'_, '_> {
+    fn check(&mut self, func: &Function) {
+        // Keyword-style calls without parentheses (CURRENT_TIMESTAMP) and
+        // qualified names are not plain `ClickHouse` functions
+        if matches!(func.args, FunctionArguments::None) || func.name.0.len() != 1 {
+            return;
+        }
+        let name = func.name.to_string();
+        // `COLUMNS('regex')` is a column matcher rather than a function
+        if is_template_placeholder(&name) || name.eq_ignore_ascii_case("COLUMNS") {
+            return;
+        }
+        let index = self.ctx.index;
+        if let Some(info) = index
+            .function(&name)
+            .filter(|f| !combinators::is_generated(f))
+        {
+            self.check_arity(func, info);
+            return;
+        }
+
+        let message = match index.combinator_form(&name) {
+            Some(form) if form.base.is_aggregate => {
+                self.check_combinator_arity(func, &form);
+                return;
+            }
+            Some(form) => format!(
+                "`{}` is not an aggregate function, so the `{}` combinator cannot be applied to it.",
+                form.base.name,
+                form.combinators.first().copied().unwrap_or_default()
+            ),
+            // Generated combinator forms matched only case-insensitively
+            None if index.function(&name).is_some() => return,
+            None => format!("Unknown function `{name}`."),
+        };
+
+        let suggestions = suggest(&name, index.function_names());
+        let message = format!("{message}{}", did_you_mean(&suggestions));
+        self.errors.push(
+            ValidationError::at_span(
+                ErrorCode::UnknownFunction,
+                message,
+                func.name.span(),
+                self.ctx.lines,
+            )
+            .with_suggestions(suggestions),
+        );
+    }
+
+    fn check_arity(&mut self, func: &Function, info: &FunctionInfo) {
+        let FunctionArguments::List(args) = &func.args else {
+            return;
+        };
+        let signatures = self.usable_signatures(func, info);
+        let count = args.args.len();
+        if signatures.is_empty() || signatures.iter().any(|s| s.accepts(count)) {
+            return;
+        }
+        let (min, max) = bounds(&signatures);
+        self.report_count(func, &info.name, count, min, max);
+    }
+
+    /// Combinators add arguments to the base function's, or in the case of
+    /// `Merge` replace them with one state argument
+    fn check_combinator_arity(&mut self, func: &Function, form: &CombinatorForm) {
+        let FunctionArguments::List(args) = &func.args else {
+            return;
+        };
+        let count = args.args.len();
+        let (min, max) = match form.extra_arguments() {
+            (_, Some(exact)) => (exact, Some(exact)),
+            (extra, None) => {
+                let signatures = self.usable_signatures(func, form.base);
+                if signatures.is_empty() {
+                    (extra, None)
+                } else if count >= extra && signatures.iter().any(|s| s.accepts(count - extra)) {
+                    return;
+                } else {
+                    let (min, max) = bounds(&signatures);
+                    (min + extra, max.map(|max| max + extra))
+                }
+            }
+        };
+        if count >= min && max.is_none_or(|max| count <= max) {
+            return;
+        }
+        self.report_count(func, &func.name.to_string(), count, min, max);
+    }
+
+    /// Documented overloads of `info`, or none when they cannot describe
+    /// this call (parameters given but no parametric overload documented)
+    fn usable_signatures(&self, func: &Function, info: &FunctionInfo) -> Vec<Signature> {
+        let signatures = self.ctx.index.signatures(info);
+        let has_parameters = matches!(func.parameters, FunctionArguments::List(_));
+        if has_parameters && !signatures.iter().any(|s| s.parametric) {
+            return Vec::new();
+        }
+        signatures
+    }
+
+    fn report_count(
+        &mut self,
+        func: &Function,
+        name: &str,
+        count: usize,
+        min: usize,
+        max: Option<usize>,
+    ) {
+        let plural = |n: usize| if n == 1 { "argument" } else { "arguments" };
+        let expected = match max {
+            None => format!("at least {min} {}", plural(min)),
+            Some(0) => "no arguments".to_string(),
+            Some(max) if max == min => format!("{min} {}", plural(min)),
+            Some(max) => format!("{min} to {max} arguments"),
+        };
+        let message = format!("`{name}` expects {expected}, got {count}.");
+        self.errors.push(ValidationError::at_span(
+            ErrorCode::WrongArgumentCount,
+            message,
+            func.name.span(),
+            self.ctx.lines,
+        ));
+    }
+}
+
+/// The fewest and most arguments any of `signatures` accepts
+fn bounds(signatures: &[Signature]) -> (usize, Option<usize>) {
+    let min = signatures
+        .iter()
+        .map(Signature::min_args)
+        .min()
+        .unwrap_or(0);
+    let max = signatures
+        .iter()
+        .map(Signature::max_args)
+        .try_fold(0, |max, n| n.map(|n| max.max(n)));
+    (min, max)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::clickhouse_index::test_data::clickhouse_25_8;
+    use crate::{validate, ErrorCode, Schema};
+
+    fn unknown_functions(sql: &str) -> Vec<(String, Vec<String>)> {
+        validate(sql, Some(clickhouse_25_8()), &Schema::default())
+            .errors
+            .into_iter()
+            .filter(|e| e.code == ErrorCode::UnknownFunction)
+            .map(|e| {
+                let start = e.start_offset.unwrap_or_default();
+                let end = e.end_offset.unwrap_or_default();
+                (sql[start..end].to_string(), e.suggestions)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_reports_misspelled_function_with_suggestion() {
+        let found = unknown_functions("SELECT toStartOfHourr(ts) FROM events");
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].0, "toStartOfHourr");
+        assert_eq!(
+            found[0].1.first().map(String::as_str),
+            Some("toStartOfHour")
+        );
+    }
+
+    #[test]
+    fn test_function_names_are_case_sensitive() {
+        let found = unknown_functions("SELECT tostartofhour(ts), COUNT(*), Sum(x) FROM events");
+        let names: Vec<&str> = found.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, vec!["tostartofhour"]);
+    }
+
+    #[test]
+    fn test_accepts_known_functions_aliases_and_combinators() {
+        let sql = "SELECT if(a, 1, 2), date_diff('day', a, b), sumIf(x, y > 0), \
+                   avgMergeOrNull(s), uniqExactState(id), lower(name) \
+                   FROM events WHERE toDate(ts) = today() GROUP BY toStartOfDay(ts)";
+        assert!(unknown_functions(sql).is_empty());
+    }
+
+    #[test]
+    fn test_checks_nested_calls_and_ddl_expressions() {
+        let found = unknown_functions(
+            "CREATE TABLE t (ts DateTime DEFAULT nowz()) ENGINE = MergeTree ORDER BY toYYYYMMM(ts)",
+        );
+        let names: Vec<&str> = found.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, vec!["nowz", "toYYYYMMM"]);
+    }
+
+    #[test]
+    fn test_ignores_table_functions_and_placeholders() {
+        let sql = "SELECT _ph_1(x), count() FROM numbers(10)";
+        assert!(unknown_functions(sql).is_empty());
+    }
+
+    #[test]
+    fn test_ignores_column_matchers() {
+        let sql = "SELECT COLUMNS('u.*'), columns('^id$') FROM events";
+        assert!(unknown_functions(sql).is_empty());
+    }
+
+    fn argument_count_errors(sql: &str) -> Vec<String> {
+        validate(sql, Some(clickhouse_25_8()), &Schema::default())
+            .errors
+            .into_iter()
+            .filter(|e| e.code == ErrorCode::WrongArgumentCount)
+            .map(|e| e.message)
+            .collect()
+    }
+
+    #[test]
+    fn test_reports_wrong_argument_counts() {
+        assert_eq!(
+            argument_count_errors("SELECT if(a, 1), round(), e(1) FROM t"),
+            vec![
+                "`if` expects 3 arguments, got 2.",
+                "`round` expects 1 to 2 arguments, got 0.",
+                "`e` expects no arguments, got 1.",
+            ]
+        );
+        assert_eq!(
+            argument_count_errors("SELECT greatest() FROM t"),
+            vec!["`greatest` expects at least 1 argument, got 0."]
+        );
+    }
+
+    #[test]
+    fn test_accepts_documented_argument_counts() {
+        let sql = "SELECT toStartOfInterval(ts, INTERVAL 1 HOUR), \
+                   toStartOfInterval(ts, INTERVAL 1 HOUR, origin, 'UTC'), \
+                   formatDateTime(ts, '%F'), concat(), concat(a, b, c, d), \
+                   multiIf(a, 1, b, 2, 3), round(x, 2), now(), sumIf(x, y > 0) FROM t";
+        assert!(argument_count_errors(sql).is_empty());
+    }
+
+    #[test]
+    fn test_common_undocumented_functions_are_counted() {
+        assert_eq!(
+            argument_count_errors("SELECT toDateTime() FROM events"),
+            vec!["`toDateTime` expects 1 to 2 arguments, got 0."]
+        );
+        assert_eq!(
+            argument_count_errors("SELECT dateDiff('day', ts) FROM events"),
+            vec!["`dateDiff` expects 3 to 4 arguments, got 2."]
+        );
+        assert_eq!(
+            argument_count_errors("SELECT date_diff('day', ts), toInt64OrNull(a, b) FROM t"),
+            vec![
+                "`date_diff` expects 3 to 4 arguments, got 2.",
+                "`toInt64OrNull` expects 1 argument, got 2.",
+            ]
+        );
+        let sql = "SELECT toDateTime(ts, 'UTC'), dateDiff('day', a, b), toDecimal64(x, 2), \
+                   quantile(0.9)(x), quantile(x), uniq(a, b, c), avgIf(x, x > 0), \
+                   sumMap(keys, values), toUInt64OrDefault(s, 0) FROM t";
+        assert!(argument_count_errors(sql).is_empty());
+    }
+
+    #[test]
+    fn test_combinator_argument_counts() {
+        assert_eq!(
+            argument_count_errors(
+                "SELECT sumIf(), avgMergeOrNull(s, 1), sumMergeIf(s), countIf(x > 1), \
+                 uniqExactMerge(s), sumMergeIf(s, x > 1) FROM t"
+            ),
+            vec![
+                "`sumIf` expects at least 1 argument, got 0.",
+                "`avgMergeOrNull` expects 1 argument, got 2.",
+                "`sumMergeIf` expects 2 arguments, got 1.",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_combinators_need_an_aggregate_base() {
+        let result = validate(
+            "SELECT toStartOfHourIf(ts, x > 1) FROM t",
+            Some(clickhouse_25_8()),
+            &Schema::default(),
+        );
+        assert_eq!(result.errors.len(), 1);
+        assert_eq!(result.errors[0].code, ErrorCode::UnknownFunction);
+        assert!(result.errors[0]
+            .message
+            .starts_with("`toStartOfHour` is not an aggregate function, so the `If` combinator"));
+    }
+
+    #[test]
+    fn test_no_semantic_errors_without_data() {
+        let result = validate("SELECT toStartOfHourr(ts)", None, &Schema::default());
+        assert!(result.valid);
+    }
+}
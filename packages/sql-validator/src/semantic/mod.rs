@@ -0,0 +1,82 @@
+//! Semantic checks over statements that parsed cleanly.
+//!
+//! Syntax validation only proves the text is grammatical. These passes walk
+//! the AST and report names and shapes that `ClickHouse` would reject at
+//! runtime, using the data of the version loaded into an `Engine`.
+
+mod aggregates;
+mod data_types;
+mod engines;
+mod formats;
+mod functions;
+mod names;
+mod settings;
+
+use crate::clickhouse_index::ClickHouseIndex;
+use crate::position::LineIndex;
+use crate::recovery::{Fragment, TableSettings};
+use crate::schema::Schema;
+use crate::ValidationError;
+use sqlparser::ast::Statement;
+use sqlparser::tokenizer::{Location, TokenWithSpan};
+
+/// Shared inputs for every semantic check
+pub(crate) struct CheckContext<'a> {
+    pub index: &'a ClickHouseIndex,
+    /// Tables registered with the `Engine`; names are resolved only when
+    /// there are some
+    pub schema: &'a Schema,
+    pub lines: &'a LineIndex<'a>,
+    /// Tokens of the whole input without whitespace or comments, for source
+    /// details the AST normalizes away
+    pub tokens: &'a [TokenWithSpan],
+    /// `SETTINGS` of `CREATE TABLE` statements, kept outside the AST
+    pub table_settings: &'a [TableSettings],
+}
+
+/// Runs all semantic checks, returning errors in source order
+pub(crate) fn check(statements: &[Statement], ctx: &CheckContext) -> Vec<ValidationError> {
+    let mut errors = Vec::new();
+    functions::check_unknown_functions(statements, ctx, &mut errors);
+    aggregates::check_aggregates(statements, ctx, &mut errors);
+    data_types::check_column_types(statements, ctx, &mut errors);
+    engines::check_engines(statements, ctx, &mut errors);
+    formats::check_formats(statements, ctx, &mut errors);
+    settings::check_settings(statements, ctx, &mut errors);
+    names::check_names(statements, ctx, &mut errors);
+    errors.sort_by_key(|e| e.start_offset);
+    errors
+}
+
+/// Runs the checks that apply to a fragment outside any statement
+pub(crate) fn check_fragment(fragment: &Fragment, ctx: &CheckContext) -> Vec<ValidationError> {
+    let mut errors = Vec::new();
+    match fragment {
+        Fragment::Expr(expr) => {
+            functions::check_unknown_functions(std::slice::from_ref(&**expr), ctx, &mut errors);
+        }
+        Fragment::SelectItems(items) => functions::check_unknown_functions(items, ctx, &mut errors),
+        Fragment::OrderBy(exprs) => functions::check_unknown_functions(exprs, ctx, &mut errors),
+        Fragment::Columns(columns) => {
+            functions::check_unknown_functions(columns, ctx, &mut errors);
+            data_types::check_column_def_types(columns, ctx, &mut errors);
+        }
+    }
+    errors.sort_by_key(|e| e.start_offset);
+    errors
+}
+
+/// `_ph_N` identifiers stand in for `${...}` template expressions
+fn is_template_placeholder(name: &str) -> bool {
+    name.strip_prefix("_ph_")
+        .is_some_and(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()))
+}
+
+/// Index of the token that starts exactly at `location`
+fn token_starting_at(tokens: &[TokenWithSpan], location: Location) -> Option<usize> {
+    let pos = tokens.partition_point(|t| t.span.start < location);
+    tokens
+        .get(pos)
+        .is_some_and(|t| t.span.start == location)
+        .then_some(pos)
+}
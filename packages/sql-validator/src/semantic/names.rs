@@ -0,0 +1,947 @@
+//! Name resolution against the schema registered with an `Engine`.
+//!
+//! Each query gets a scope of the tables its `FROM` clause reads, with the
+//! CTEs, select-list aliases and `ARRAY JOIN` aliases it defines, inside the
+//! scope of the query around it. Tables must be registered or be CTEs, and
+//! columns must be in one of the tables in scope, or in the scope of an
+//! enclosing query for correlated subqueries. An unqualified column in more
+//! than one joined table is ambiguous unless the join merges it with `USING`.
+//!
+//! Tables in databases the schema does not know, such as `system`, table
+//! functions, and queries over them, have columns that cannot be known, so
+//! columns are not reported in any scope that reads one; neither are those of
+//! tables the same input creates. Names starting with
+//! `_` may be virtual columns such as `_part` or template placeholders, and
+//! are not reported either.
+
+use super::{is_template_placeholder, CheckContext};
+use crate::suggest::{did_you_mean, suggest};
+use crate::{ErrorCode, ValidationError};
+use sqlparser::ast::{
+    Expr, FunctionArg, FunctionArgExpr, FunctionArguments, GroupByExpr, Ident, JoinConstraint,
+    JoinOperator, LimitClause, ObjectName, OrderBy, Query, Select, SelectItem,
+    SelectItemQualifiedWildcardKind, SetExpr, Spanned, Statement, TableAlias, TableFactor,
+    TableWithJoins, Visit, Visitor,
+};
+use sqlparser::tokenizer::Span;
+use std::ops::ControlFlow;
+
+/// Reports tables and columns that are not in scope, and ambiguous columns,
+/// once a schema is registered
+pub(super) fn check_names(
+    statements: &[Statement],
+    ctx: &CheckContext,
+    errors: &mut Vec<ValidationError>,
+) {
+    if ctx.schema.databases.is_empty() {
+        return;
+    }
+    let created = statements
+        .iter()
+        .filter_map(|statement| match statement {
+            Statement::CreateTable(create) => Some(&create.name),
+            Statement::CreateView { name, .. } => Some(name),
+            _ => None,
+        })
+        .filter_map(|name| Some(name.0.last()?.as_ident()?.value.clone()))
+        .collect();
+    let mut visitor = Statements {
+        resolver: Resolver {
+            ctx,
+            created,
+            errors,
+        },
+        query_depth: 0,
+    };
+    for statement in statements {
+        let _ = statement.visit(&mut visitor);
+    }
+}
+
+/// Resolves the outermost queries of statements, which resolve the queries
+/// inside them
+struct Statements<'a, 'b> {
+    resolver: Resolver<'a, 'b>,
+    query_depth: usize,
+}
+
+impl Visitor for Statements<'_, '_> {
+    type Break = ();
+
+    fn pre_visit_query(&mut self, query: &Query) -> ControlFlow<()> {
+        if self.query_depth == 0 {
+            self.resolver.query(query, None);
+        }
+        self.query_depth += 1;
+        ControlFlow::Continue(())
+    }
+
+    fn post_visit_query(&mut self, _query: &Query) -> ControlFlow<()> {
+        self.query_depth -= 1;
+        ControlFlow::Continue(())
+    }
+}
+
+/// Columns of a table or query, or `None` when they cannot be known
+type Columns = Option<Vec<String>>;
+
+/// A table in scope
+struct Relation {
+    /// Names that qualify its columns: its alias, its name and
+    /// `database.name`, in that order
+    names: Vec<String>,
+    columns: Columns,
+}
+
+impl Relation {
+    fn has_column(&self, name: &str) -> bool {
+        self.columns
+            .as_ref()
+            .is_some_and(|columns| columns.iter().any(|c| c == name))
+    }
+}
+
+/// The names a query can refer to
+#[derive(Default)]
+struct Scope<'a> {
+    /// CTEs of its `WITH` clause, with the columns they select
+    ctes: Vec<(String, Columns)>,
+    relations: Vec<Relation>,
+    /// Aliases of its select list and of `ARRAY JOIN`
+    aliases: Vec<String>,
+    /// Columns merged by `JOIN ... USING`
+    using: Vec<String>,
+    parent: Option<&'a Scope<'a>>,
+}
+
+/// What an unqualified column resolves to
+enum Lookup<'s> {
+    Found,
+    Unknown,
+    /// In more than one table of the same scope, given by their first names
+    Ambiguous(Vec<&'s str>),
+}
+
+impl<'a> Scope<'a> {
+    /// This scope and those of the queries around it, innermost first
+    fn ancestors(&self) -> impl Iterator<Item = &Scope<'a>> {
+        std::iter::successors(Some(self), |scope| scope.parent)
+    }
+
+    fn cte(&self, name: &str) -> Option<&Columns> {
+        self.ancestors()
+            .find_map(|scope| scope.ctes.iter().find(|(cte, _)| cte == name))
+            .map(|(_, columns)| columns)
+    }
+
+    /// The table in scope that `name` qualifies the columns of
+    fn relation(&self, name: &str) -> Option<&Relation> {
+        self.ancestors().find_map(|scope| {
+            scope
+                .relations
+                .iter()
+                .find(|r| r.names.iter().any(|n| n == name))
+        })
+    }
+
+    fn lookup(&self, name: &str) -> Lookup<'_> {
+        for scope in self.ancestors() {
+            if scope.aliases.iter().any(|alias| alias == name) {
+                return Lookup::Found;
+            }
+            let matching: Vec<&Relation> = scope
+                .relations
+                .iter()
+                .filter(|r| r.has_column(name))
+                .collect();
+            match matching.len() {
+                0 => {}
+                1 => return Lookup::Found,
+                _ if scope.using.iter().any(|column| column == name) => return Lookup::Found,
+                _ => {
+                    return Lookup::Ambiguous(
+                        matching
+                            .iter()
+                            .filter_map(|r| r.names.first().map(String::as_str))
+                            .collect(),
+                    )
+                }
+            }
+            if scope.relations.iter().any(|r| r.columns.is_none()) {
+                return Lookup::Found;
+            }
+        }
+        Lookup::Unknown
+    }
+
+    /// Columns and aliases of this scope, for suggestions
+    fn column_names(&self) -> impl Iterator<Item = &str> {
+        self.relations
+            .iter()
+            .filter_map(|r| r.columns.as_ref())
+            .flatten()
+            .chain(&self.aliases)
+            .map(String::as_str)
+    }
+}
+
+/// What a select's `FROM` clause brings into scope, and what is checked once
+/// all of it is
+#[derive(Default)]
+struct FromClause<'q> {
+    relations: Vec<Relation>,
+    using: Vec<String>,
+    /// Conditions of joins
+    conditions: Vec<&'q Expr>,
+    /// Arrays of `ARRAY JOIN`, which sqlparser reads as a join of a table
+    /// after one aliased `ARRAY`
+    arrays: Vec<&'q TableFactor>,
+}
+
+struct Resolver<'a, 'b> {
+    ctx: &'a CheckContext<'a>,
+    /// Tables and views the statements create, which are not registered
+    /// but exist once the statements before a query have run
+    created: Vec<String>,
+    errors: &'b mut Vec<ValidationError>,
+}
+
+impl Resolver<'_, '_> {
+    /// Resolves `query` inside `parent`, returning the columns it selects
+    fn query(&mut self, query: &Query, parent: Option<&Scope>) -> Columns {
+        let mut scope = Scope {
+            parent,
+            ..Scope::default()
+        };
+        let recursive = query.with.as_ref().is_some_and(|with| with.recursive);
+        for cte in query.with.iter().flat_map(|with| &with.cte_tables) {
+            let name = cte.alias.name.value.clone();
+            if recursive {
+                // A recursive CTE reads itself, with columns yet to be known
+                scope.ctes.push((name.clone(), None));
+            }
+            let mut columns = self.query(&cte.query, Some(&scope));
+            if !cte.alias.columns.is_empty() {
+                columns = Some(
+                    cte.alias
+                        .columns
+                        .iter()
+                        .map(|c| c.name.value.clone())
+                        .collect(),
+                );
+            }
+            scope.ctes.retain(|(cte, _)| *cte != name);
+            scope.ctes.push((name, columns));
+        }
+
+        match query.body.as_ref() {
+            SetExpr::Select(select) => {
+                let limit_by = match &query.limit_clause {
+                    Some(LimitClause::LimitOffset { limit_by, .. }) => limit_by.as_slice(),
+                    _ => &[],
+                };
+                self.select(select, &scope, query.order_by.as_ref(), limit_by)
+            }
+            body => self.set_expr(body, &scope),
+        }
+    }
+
+    fn set_expr(&mut self, body: &SetExpr, scope: &Scope) -> Columns {
+        match body {
+            SetExpr::Select(select) => self.select(select, scope, None, &[]),
+            SetExpr::Query(query) => self.query(query, Some(scope)),
+            // A set operation has the columns of its first query
+            SetExpr::SetOperation { left, right, .. } => {
+                let columns = self.set_expr(left, scope);
+                self.set_expr(right, scope);
+                columns
+            }
+            _ => None,
+        }
+    }
+
+    fn select(
+        &mut self,
+        select: &Select,
+        parent: &Scope,
+        order_by: Option<&OrderBy>,
+        limit_by: &[Expr],
+    ) -> Columns {
+        let mut from = FromClause::default();
+        self.add_from_items(&select.from, parent, &mut from, false);
+        if select.from.is_empty() {
+            // A select without `FROM` reads `system.one`
+            from.relations.push(Relation {
+                names: Vec::new(),
+                columns: Some(vec!["dummy".to_string()]),
+            });
+        }
+
+        let mut scope = Scope {
+            relations: from.relations,
+            using: from.using,
+            parent: Some(parent),
+            ..Scope::default()
+        };
+        for item in &select.projection {
+            if let SelectItem::ExprWithAlias { alias, .. } = item {
+                scope.aliases.push(alias.value.clone());
+            }
+        }
+        for array in &from.arrays {
+            if let TableFactor::Table {
+                alias: Some(alias), ..
+            } = array
+            {
+                scope.aliases.push(alias.name.value.clone());
+            }
+        }
+
+        let mut names = Names {
+            resolver: self,
+            scope: &scope,
+            query_depth: 0,
+            params: Vec::new(),
+            not_columns: Vec::new(),
+        };
+        for array in &from.arrays {
+            match array {
+                TableFactor::Table {
+                    name, args: None, ..
+                } => names.check_name(name),
+                TableFactor::Table {
+                    args: Some(args), ..
+                } => names.visit(&args.args),
+                _ => {}
+            }
+        }
+        names.visit(&select.projection);
+        for condition in &from.conditions {
+            names.visit(*condition);
+        }
+        names.visit(&select.prewhere);
+        names.visit(&select.selection);
+        if let GroupByExpr::Expressions(exprs, _) = &select.group_by {
+            names.visit(exprs);
+        }
+        names.visit(&select.having);
+        names.visit(&select.qualify);
+        names.visit(&select.named_window);
+        if let Some(order_by) = order_by {
+            names.visit(order_by);
+        }
+        for expr in limit_by {
+            names.visit(expr);
+        }
+
+        for item in &select.projection {
+            if let SelectItem::QualifiedWildcard(
+                SelectItemQualifiedWildcardKind::ObjectName(name),
+                _,
+            ) = item
+            {
+                self.check_qualifier(&scope, name);
+            }
+        }
+        selected_columns(select, &scope)
+    }
+
+    /// Adds the tables of `items` to `from`. `after_array_join` is whether
+    /// the item before them ended with an `ARRAY JOIN`, which makes them
+    /// more of its arrays.
+    fn add_from_items<'q>(
+        &mut self,
+        items: &'q [TableWithJoins],
+        ctes: &Scope,
+        from: &mut FromClause<'q>,
+        mut after_array_join: bool,
+    ) {
+        for item in items {
+            if after_array_join {
+                from.arrays.push(&item.relation);
+            } else {
+                self.relation(&item.relation, ctes, from);
+            }
+            let mut array_join = is_array_marker(&item.relation);
+            after_array_join = false;
+            for join in &item.joins {
+                if array_join && join.join_operator == JoinOperator::Join(JoinConstraint::None) {
+                    from.arrays.push(&join.relation);
+                    array_join = false;
+                    after_array_join = true;
+                    continue;
+                }
+                after_array_join = false;
+                self.relation(&join.relation, ctes, from);
+                array_join = is_array_marker(&join.relation);
+                if let JoinOperator::AsOf {
+                    match_condition, ..
+                } = &join.join_operator
+                {
+                    from.conditions.push(match_condition);
+                }
+                match join_constraint(&join.join_operator) {
+                    Some(JoinConstraint::On(condition)) => from.conditions.push(condition),
+                    Some(JoinConstraint::Using(columns)) => from.using.extend(
+                        columns
+                            .iter()
+                            .filter_map(|c| c.0.last()?.as_ident())
+                            .map(|ident| ident.value.clone()),
+                    ),
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    /// Adds the tables `factor` reads to `from`, reporting unknown ones
+    fn relation<'q>(&mut self, factor: &'q TableFactor, ctes: &Scope, from: &mut FromClause<'q>) {
+        let relation = match factor {
+            TableFactor::Table {
+                name,
+                alias,
+                args: None,
+                ..
+            } => self.table(name, alias_name(alias.as_ref()), ctes),
+            TableFactor::Derived {
+                subquery, alias, ..
+            } => {
+                let mut columns = self.query(subquery, Some(ctes));
+                if let Some(alias) = alias.as_ref().filter(|a| !a.columns.is_empty()) {
+                    columns = Some(alias.columns.iter().map(|c| c.name.value.clone()).collect());
+                }
+                Relation {
+                    names: alias_name(alias.as_ref()).into_iter().collect(),
+                    columns,
+                }
+            }
+            TableFactor::NestedJoin {
+                table_with_joins, ..
+            } => {
+                self.add_from_items(std::slice::from_ref(table_with_joins), ctes, from, false);
+                return;
+            }
+            // Table functions and the like
+            TableFactor::Table { alias, .. } | TableFactor::TableFunction { alias, .. } => {
+                Relation {
+                    names: alias_name(alias.as_ref()).into_iter().collect(),
+                    columns: None,
+                }
+            }
+            _ => Relation {
+                names: Vec::new(),
+                columns: None,
+            },
+        };
+        from.relations.push(relation);
+    }
+
+    /// The table or CTE `name`, reporting it when it is neither
+    fn table(&mut self, name: &ObjectName, alias: Option<String>, ctes: &Scope) -> Relation {
+        let parts: Option<Vec<&str>> = name
+            .0
+            .iter()
+            .map(|part| part.as_ident().map(|ident| ident.value.as_str()))
+            .collect();
+        let mut names: Vec<String> = alias.into_iter().collect();
+        let columns = match parts.as_deref() {
+            Some([table]) if is_template_placeholder(table) => None,
+            Some([table]) if ctes.cte(table).is_some() => {
+                names.push((*table).to_string());
+                ctes.cte(table).cloned().flatten()
+            }
+            Some(&[table]) => self.registered(None, table, name, &mut names, ctes),
+            Some(&[database, table]) => {
+                if self.ctx.schema.database(database).is_some() {
+                    self.registered(Some(database), table, name, &mut names, ctes)
+                } else {
+                    names.push(table.to_string());
+                    names.push(format!("{database}.{table}"));
+                    None
+                }
+            }
+            _ => None,
+        };
+        Relation { names, columns }
+    }
+
+    /// Columns of the registered table `database.table`, reporting it when
+    /// there is no such table
+    fn registered(
+        &mut self,
+        database: Option<&str>,
+        table: &str,
+        name: &ObjectName,
+        names: &mut Vec<String>,
+        ctes: &Scope,
+    ) -> Columns {
+        let schema = self.ctx.schema;
+        let found = schema
+            .tables()
+            .find(|(d, t)| t.name == table && database.is_none_or(|db| d.name == db));
+        names.push(table.to_string());
+        if let Some((d, t)) = found {
+            names.push(format!("{}.{}", d.name, t.name));
+            return Some(t.columns.iter().map(|c| c.name.clone()).collect());
+        }
+        if self.created.iter().any(|created| created == table) {
+            return None;
+        }
+
+        let candidates: Vec<&str> = match database {
+            Some(db) => schema
+                .database(db)
+                .into_iter()
+                .flat_map(|d| &d.tables)
+                .map(|t| t.name.as_str())
+                .collect(),
+            None => schema
+                .tables()
+                .map(|(_, t)| t.name.as_str())
+                .chain(
+                    ctes.ancestors()
+                        .flat_map(|s| s.ctes.iter().map(|(n, _)| n.as_str())),
+                )
+                .collect(),
+        };
+        let suggestions = suggest(table, candidates);
+        let message = format!("Unknown table `{name}`.{}", did_you_mean(&suggestions));
+        self.push(ErrorCode::UnknownTable, message, name.span(), suggestions);
+        None
+    }
+
+    /// Reports the qualifier of `t.*` when it names no table in scope
+    fn check_qualifier(&mut self, scope: &Scope, name: &ObjectName) {
+        let qualifier = name.to_string();
+        if scope.relation(&qualifier).is_some()
+            || scope
+                .ancestors()
+                .any(|s| s.relations.iter().any(|r| r.columns.is_none()))
+        {
+            return;
+        }
+        let candidates = scope
+            .relations
+            .iter()
+            .flat_map(|r| &r.names)
+            .map(String::as_str);
+        let suggestions = suggest(&qualifier, candidates);
+        let message = format!(
+            "`{qualifier}` is not a table or alias in scope.{}",
+            did_you_mean(&suggestions)
+        );
+        self.push(ErrorCode::UnknownTable, message, name.span(), suggestions);
+    }
+
+    /// Reports the unqualified column `ident` unless it resolves to exactly
+    /// one column
+    fn column(&mut self, scope: &Scope, ident: &Ident) {
+        let name = &ident.value;
+        if name.starts_with('_') {
+            return;
+        }
+        match scope.lookup(name) {
+            Lookup::Found => {}
+            Lookup::Ambiguous(tables) => {
+                let suggestions: Vec<String> = tables
+                    .iter()
+                    .map(|table| format!("{table}.{name}"))
+                    .collect();
+                let tables: Vec<String> = tables.iter().map(|t| format!("`{t}`")).collect();
+                let message = format!(
+                    "Column `{name}` is ambiguous: it is in {}. Qualify it with the table it is read from.",
+                    tables.join(" and ")
+                );
+                self.push(ErrorCode::AmbiguousColumn, message, ident.span, suggestions);
+            }
+            Lookup::Unknown => {
+                let suggestions = suggest(name, scope.column_names());
+                let message = format!("Unknown column `{name}`.{}", did_you_mean(&suggestions));
+                self.push(ErrorCode::UnknownColumn, message, ident.span, suggestions);
+            }
+        }
+    }
+
+    /// Reports `a.b...` unless `a` qualifies a table in scope that has the
+    /// column `b`, `a.b` qualifies one that has the column after it, or `a`
+    /// or `a.b` is itself a column, such as a tuple or a `Nested` column
+    fn qualified_column(&mut self, scope: &Scope, parts: &[Ident]) {
+        let [first, second, rest @ ..] = parts else {
+            return;
+        };
+        if first.value.starts_with('_') {
+            return;
+        }
+        let relation = scope
+            .relation(&first.value)
+            .map(|r| (r, second, rest))
+            .or_else(|| {
+                let [third, rest @ ..] = rest else {
+                    return None;
+                };
+                let qualified = format!("{}.{}", first.value, second.value);
+                scope.relation(&qualified).map(|r| (r, third, rest))
+            });
+
+        if let Some((relation, column, rest)) = relation {
+            let nested = rest
+                .first()
+                .map(|r| format!("{}.{}", column.value, r.value));
+            let known = relation.columns.is_none()
+                || relation.has_column(&column.value)
+                || nested.is_some_and(|nested| relation.has_column(&nested));
+            if !known {
+                let qualifier = &first.value;
+                let candidates = relation.columns.iter().flatten().map(String::as_str);
+                let suggestions = suggest(&column.value, candidates);
+                let message = format!(
+                    "Unknown column `{}` in `{qualifier}`.{}",
+                    column.value,
+                    did_you_mean(&suggestions)
+                );
+                self.push(ErrorCode::UnknownColumn, message, column.span, suggestions);
+            }
+            return;
+        }
+
+        let nested = format!("{}.{}", first.value, second.value);
+        if matches!(scope.lookup(&nested), Lookup::Found) {
+            return;
+        }
+        match scope.lookup(&first.value) {
+            Lookup::Found => {}
+            Lookup::Ambiguous(_) => self.column(scope, first),
+            Lookup::Unknown => {
+                let candidates = scope
+                    .relations
+                    .iter()
+                    .flat_map(|r| &r.names)
+                    .map(String::as_str)
+                    .chain(scope.column_names());
+                let suggestions = suggest(&first.value, candidates);
+                let message = format!(
+                    "`{}` is not a table, alias or column in scope.{}",
+                    first.value,
+                    did_you_mean(&suggestions)
+                );
+                self.push(ErrorCode::UnknownColumn, message, first.span, suggestions);
+            }
+        }
+    }
+
+    fn push(&mut self, code: ErrorCode, message: String, span: Span, suggestions: Vec<String>) {
+        self.errors.push(
+            ValidationError::at_span(code, message, span, self.ctx.lines)
+                .with_suggestions(suggestions),
+        );
+    }
+}
+
+/// Checks the names in expressions of one query, resolving the subqueries
+/// among them inside its scope
+struct Names<'r, 'a, 'b, 's> {
+    resolver: &'r mut Resolver<'a, 'b>,
+    scope: &'s Scope<'s>,
+    query_depth: usize,
+    /// Parameters of the lambdas being visited
+    params: Vec<String>,
+    /// Spans of names that are not columns, such as the dictionary of
+    /// `dictGet(dict, ...)`
+    not_columns: Vec<Span>,
+}
+
+impl Names<'_, '_, '_, '_> {
+    fn visit<T: Visit>(&mut self, node: &T) {
+        let _ = node.visit(self);
+    }
+
+    /// Checks an array of `ARRAY JOIN`, which sqlparser reads as a table name
+    fn check_name(&mut self, name: &ObjectName) {
+        let parts: Option<Vec<Ident>> = name.0.iter().map(|p| p.as_ident().cloned()).collect();
+        match parts.as_deref() {
+            Some([ident]) => self.resolver.column(self.scope, ident),
+            Some(parts) => self.resolver.qualified_column(self.scope, parts),
+            None => {}
+        }
+    }
+
+    fn is_param(&self, ident: &Ident) -> bool {
+        self.params.contains(&ident.value) || self.not_columns.contains(&ident.span)
+    }
+}
+
+impl Visitor for Names<'_, '_, '_, '_> {
+    type Break = ();
+
+    fn pre_visit_query(&mut self, query: &Query) -> ControlFlow<()> {
+        if self.query_depth == 0 {
+            self.resolver.query(query, Some(self.scope));
+        }
+        self.query_depth += 1;
+        ControlFlow::Continue(())
+    }
+
+    fn post_visit_query(&mut self, _query: &Query) -> ControlFlow<()> {
+        self.query_depth -= 1;
+        ControlFlow::Continue(())
+    }
+
+    fn pre_visit_expr(&mut self, expr: &Expr) -> ControlFlow<()> {
+        if self.query_depth > 0 {
+            return ControlFlow::Continue(());
+        }
+        match expr {
+            Expr::Lambda(lambda) => {
+                self.params
+                    .extend(lambda.params.iter().map(|p| p.value.clone()));
+            }
+            Expr::Function(func) => {
+                if let Some(span) = object_argument(func) {
+                    self.not_columns.push(span);
+                }
+            }
+            Expr::Identifier(ident) if !self.is_param(ident) => {
+                self.resolver.column(self.scope, ident);
+            }
+            Expr::CompoundIdentifier(parts)
+                if parts.first().is_some_and(|first| !self.is_param(first)) =>
+            {
+                self.resolver.qualified_column(self.scope, parts);
+            }
+            _ => {}
+        }
+        ControlFlow::Continue(())
+    }
+
+    fn post_visit_expr(&mut self, expr: &Expr) -> ControlFlow<()> {
+        if let (0, Expr::Lambda(lambda)) = (self.query_depth, expr) {
+            let count = self.params.len() - lambda.params.len();
+            self.params.truncate(count);
+        }
+        ControlFlow::Continue(())
+    }
+}
+
+/// Span of the name a function such as `dictGet` or `joinGet` takes as its
+/// first argument, which is a dictionary or table rather than a column
+fn object_argument(func: &sqlparser::ast::Function) -> Option<Span> {
+    let name = func.name.0.last()?.as_ident()?.value.to_ascii_lowercase();
+    if !(name.starts_with("dict") || name.starts_with("joinget")) {
+        return None;
+    }
+    let FunctionArguments::List(list) = &func.args else {
+        return None;
+    };
+    match list.args.first()? {
+        FunctionArg::Unnamed(FunctionArgExpr::Expr(Expr::Identifier(ident))) => Some(ident.span),
+        FunctionArg::Unnamed(FunctionArgExpr::Expr(Expr::CompoundIdentifier(parts))) => {
+            parts.first().map(|ident| ident.span)
+        }
+        _ => None,
+    }
+}
+
+/// Whether `factor` is the table before an `ARRAY JOIN`, which sqlparser
+/// reads as aliased `ARRAY`
+fn is_array_marker(factor: &TableFactor) -> bool {
+    let (TableFactor::Table { alias, .. }
+    | TableFactor::Derived { alias, .. }
+    | TableFactor::TableFunction { alias, .. }
+    | TableFactor::NestedJoin { alias, .. }) = factor
+    else {
+        return false;
+    };
+    alias.as_ref().is_some_and(is_array_alias)
+}
+
+fn is_array_alias(alias: &TableAlias) -> bool {
+    alias.name.quote_style.is_none() && alias.name.value.eq_ignore_ascii_case("ARRAY")
+}
+
+/// The alias of a table, unless it is the `ARRAY` of an `ARRAY JOIN`
+fn alias_name(alias: Option<&TableAlias>) -> Option<String> {
+    alias
+        .filter(|alias| !is_array_alias(alias))
+        .map(|alias| alias.name.value.clone())
+}
+
+fn join_constraint(operator: &JoinOperator) -> Option<&JoinConstraint> {
+    match operator {
+        JoinOperator::Join(constraint)
+        | JoinOperator::Inner(constraint)
+        | JoinOperator::Left(constraint)
+        | JoinOperator::LeftOuter(constraint)
+        | JoinOperator::Right(constraint)
+        | JoinOperator::RightOuter(constraint)
+        | JoinOperator::FullOuter(constraint)
+        | JoinOperator::CrossJoin(constraint)
+        | JoinOperator::Semi(constraint)
+        | JoinOperator::LeftSemi(constraint)
+        | JoinOperator::RightSemi(constraint)
+        | JoinOperator::Anti(constraint)
+        | JoinOperator::LeftAnti(constraint)
+        | JoinOperator::RightAnti(constraint)
+        | JoinOperator::StraightJoin(constraint)
+        | JoinOperator::AsOf { constraint, .. } => Some(constraint),
+        JoinOperator::CrossApply | JoinOperator::OuterApply => None,
+    }
+}
+
+/// The columns `select` returns, named as `ClickHouse` names them
+fn selected_columns(select: &Select, scope: &Scope) -> Columns {
+    let mut columns = Vec::new();
+    for item in &select.projection {
+        match item {
+            SelectItem::UnnamedExpr(Expr::Identifier(ident)) => columns.push(ident.value.clone()),
+            SelectItem::UnnamedExpr(Expr::CompoundIdentifier(parts)) => {
+                columns.extend(parts.last().map(|ident| ident.value.clone()));
+            }
+            SelectItem::UnnamedExpr(expr) => columns.push(expr.to_string()),
+            SelectItem::ExprWithAlias { alias, .. } => columns.push(alias.value.clone()),
+            SelectItem::Wildcard(_) => {
+                for relation in &scope.relations {
+                    columns.extend(relation.columns.clone()?);
+                }
+            }
+            SelectItem::QualifiedWildcard(SelectItemQualifiedWildcardKind::ObjectName(name), _) => {
+                columns.extend(scope.relation(&name.to_string())?.columns.clone()?);
+            }
+            SelectItem::QualifiedWildcard(SelectItemQualifiedWildcardKind::Expr(_), _) => {
+                return None;
+            }
+        }
+    }
+    Some(columns)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::clickhouse_index::test_data::clickhouse_25_8;
+    use crate::{validate, ErrorCode, Schema};
+
+    fn schema() -> Schema {
+        serde_json::from_str(
+            r#"{"databases": [
+                {"name": "default", "tables": [
+                    {"name": "events", "columns": [
+                        {"name": "user_id", "type": "UInt64"},
+                        {"name": "ts", "type": "DateTime"},
+                        {"name": "tags", "type": "Array(String)"},
+                        {"name": "props.key", "type": "Array(String)"}
+                    ]},
+                    {"name": "users", "columns": [
+                        {"name": "user_id", "type": "UInt64"},
+                        {"name": "name", "type": "String"}
+                    ]}
+                ]},
+                {"name": "analytics", "tables": [
+                    {"name": "sessions", "columns": [{"name": "session_id", "type": "UUID"}]}
+                ]}
+            ]}"#,
+        )
+        .expect("valid schema")
+    }
+
+    /// Name errors as the code, the offending text and the suggestions
+    fn name_errors(sql: &str) -> Vec<(ErrorCode, String, Vec<String>)> {
+        validate(sql, Some(clickhouse_25_8()), &schema())
+            .errors
+            .into_iter()
+            .filter(|e| {
+                matches!(
+                    e.code,
+                    ErrorCode::UnknownTable | ErrorCode::UnknownColumn | ErrorCode::AmbiguousColumn
+                )
+            })
+            .map(|e| {
+                let start = e.start_offset.unwrap_or_default();
+                let end = e.end_offset.unwrap_or_default();
+                (e.code, sql[start..end].to_string(), e.suggestions)
+            })
+            .collect()
+    }
+
+    fn error(
+        code: ErrorCode,
+        text: &str,
+        suggestions: &[&str],
+    ) -> (ErrorCode, String, Vec<String>) {
+        (
+            code,
+            text.to_string(),
+            suggestions.iter().map(ToString::to_string).collect(),
+        )
+    }
+
+    #[test]
+    fn test_unknown_tables_and_columns() {
+        assert_eq!(
+            name_errors("SELECT usre_id FROM events"),
+            vec![error(ErrorCode::UnknownColumn, "usre_id", &["user_id"])]
+        );
+        assert_eq!(
+            name_errors("SELECT e.tss FROM events AS e JOIN analytics.sesions s ON 1"),
+            vec![
+                error(ErrorCode::UnknownColumn, "tss", &["ts", "tags"]),
+                error(ErrorCode::UnknownTable, "analytics.sesions", &["sessions"]),
+            ]
+        );
+        assert_eq!(
+            name_errors("SELECT evnts.ts FROM events"),
+            vec![error(ErrorCode::UnknownColumn, "evnts", &["events"])]
+        );
+    }
+
+    #[test]
+    fn test_ambiguous_columns_in_joins() {
+        assert_eq!(
+            name_errors("SELECT user_id, name FROM events e JOIN users u ON e.user_id = u.user_id"),
+            vec![error(
+                ErrorCode::AmbiguousColumn,
+                "user_id",
+                &["e.user_id", "u.user_id"]
+            )]
+        );
+        assert!(name_errors("SELECT user_id FROM events JOIN users USING (user_id)").is_empty());
+    }
+
+    #[test]
+    fn test_ctes_subqueries_and_aliases() {
+        let sqls = [
+            "WITH recent AS (SELECT user_id, ts AS at FROM events) SELECT at FROM recent",
+            "SELECT n FROM (SELECT count() AS n FROM events) WHERE n > 1",
+            "SELECT toDate(ts) AS day, count() FROM events GROUP BY day ORDER BY day",
+            "SELECT name FROM users WHERE user_id IN (SELECT user_id FROM events WHERE ts > now())",
+            "SELECT name FROM users u WHERE EXISTS (SELECT 1 FROM events e WHERE e.user_id = u.user_id)",
+            "SELECT arrayMap(x -> x + 1, [1]), props.key FROM events",
+            "SELECT tag FROM events ARRAY JOIN tags AS tag",
+            "SELECT number FROM numbers(10)",
+            "SELECT name FROM system.tables",
+            "SELECT _part, events.* FROM events",
+            "SELECT dictGet(users_dict, 'name', user_id) FROM events",
+            "WITH RECURSIVE r AS (SELECT 1 AS n UNION ALL SELECT n + 1 FROM r) SELECT n FROM r",
+            "CREATE TABLE staged (id UInt64) ENGINE = Memory; SELECT id FROM staged",
+        ];
+        for sql in sqls {
+            assert!(name_errors(sql).is_empty(), "{sql}: {:?}", name_errors(sql));
+        }
+        assert_eq!(
+            name_errors("WITH recent AS (SELECT user_id FROM events) SELECT ts FROM recent"),
+            vec![error(ErrorCode::UnknownColumn, "ts", &[])]
+        );
+        assert_eq!(
+            name_errors("SELECT tag FROM events ARRAY JOIN tagz AS tag"),
+            vec![error(ErrorCode::UnknownColumn, "tagz", &["tag", "tags"])]
+        );
+    }
+
+    #[test]
+    fn test_names_are_not_resolved_without_a_schema() {
+        let result = validate(
+            "SELECT usre_id FROM evnts",
+            Some(clickhouse_25_8()),
+            &Schema::default(),
+        );
+        assert!(result.valid, "{:?}", result.errors);
+    }
+}
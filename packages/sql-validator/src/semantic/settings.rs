@@ -0,0 +1,341 @@
+//! `SETTINGS` clause validation.
+//!
+//! Query `SETTINGS` (on `SELECT` and `INSERT`) take query-level settings and
+//! `CREATE TABLE ... SETTINGS` takes `MergeTree` settings; the two lists are
+//! kept apart in `ClickHouseData`. Table settings of engines outside the
+//! `MergeTree` family (`Kafka`, `S3`, ...) are engine-specific and not
+//! checked. Values are checked against the setting's declared type where
+//! that type has a known literal form.
+
+use super::{is_template_placeholder, CheckContext};
+use crate::suggest::{did_you_mean, suggest};
+use crate::{ErrorCode, SettingInfo, ValidationError};
+use sqlparser::ast::{
+    Expr, Query, Setting, Spanned, Statement, UnaryOperator, Value, Visit, Visitor,
+};
+use std::ops::ControlFlow;
+
+/// Reports unknown and misplaced settings and values of the wrong type
+pub(super) fn check_settings(
+    statements: &[Statement],
+    ctx: &CheckContext,
+    errors: &mut Vec<ValidationError>,
+) {
+    let mut visitor = Settings { ctx, errors };
+    for statement in statements {
+        let _ = statement.visit(&mut visitor);
+    }
+    // Tables without an engine get the default, `MergeTree`
+    for table in ctx.table_settings {
+        if table
+            .engine
+            .as_deref()
+            .is_none_or(|e| e.ends_with("MergeTree"))
+        {
+            for setting in &table.settings {
+                visitor.check(setting, Scope::Table);
+            }
+        }
+    }
+}
+
+/// Which kind of `SETTINGS` clause a setting appears in
+#[derive(Clone, Copy)]
+enum Scope {
+    Query,
+    Table,
+}
+
+struct Settings<'a, 'b> {
+    ctx: &'a CheckContext<'a>,
+    errors: &'b mut Vec<ValidationError>,
+}
+
+impl Visitor for Settings<'_, '_> {
+    type Break = ();
+
+    fn pre_visit_query(&mut self, query: &Query) -> ControlFlow<()> {
+        for setting in query.settings.iter().flatten() {
+            self.check(setting, Scope::Query);
+        }
+        ControlFlow::Continue(())
+    }
+
+    fn pre_visit_statement(&mut self, statement: &Statement) -> ControlFlow<()> {
+        if let Statement::Insert(insert) = statement {
+            for setting in insert.settings.iter().flatten() {
+                self.check(setting, Scope::Query);
+            }
+        }
+        ControlFlow::Continue(())
+    }
+}
+
+impl Settings<'_, '_> {
+    fn check(&mut self, setting: &Setting, scope: Scope) {
+        let name = setting.key.value.as_str();
+        if is_template_placeholder(name) {
+            return;
+        }
+        let index = self.ctx.index;
+        let (own, other) = match scope {
+            Scope::Query => (index.setting(name), index.merge_tree_setting(name)),
+            Scope::Table => (index.merge_tree_setting(name), index.setting(name)),
+        };
+
+        if let Some(info) = own {
+            self.check_value(info, &setting.value);
+            return;
+        }
+
+        let (code, message, suggestions) = if other.is_some() {
+            let message = match scope {
+                Scope::Query => format!(
+                    "`{name}` is a MergeTree table setting; set it in CREATE TABLE ... SETTINGS, not in a query."
+                ),
+                Scope::Table => format!(
+                    "`{name}` is a query setting, not a MergeTree table setting."
+                ),
+            };
+            (ErrorCode::MisplacedSetting, message, Vec::new())
+        } else {
+            let candidates = match scope {
+                Scope::Query => &index.data.settings,
+                Scope::Table => &index.data.merge_tree_settings,
+            };
+            let suggestions = suggest(name, candidates.iter().map(|s| s.name.as_str()));
+            let message = format!("Unknown setting `{name}`.{}", did_you_mean(&suggestions));
+            (ErrorCode::UnknownSetting, message, suggestions)
+        };
+        self.errors.push(
+            ValidationError::at_span(code, message, setting.key.span, self.ctx.lines)
+                .with_suggestions(suggestions),
+        );
+    }
+
+    fn check_value(&mut self, info: &SettingInfo, value: &Expr) {
+        let Some(literal) = Literal::from_expr(value) else {
+            return;
+        };
+        let Some(expected) = expected_form(&info.setting_type, &literal) else {
+            return;
+        };
+        let message = format!("Setting `{}` expects {expected}, got {value}.", info.name);
+        self.errors.push(ValidationError::at_span(
+            ErrorCode::InvalidSettingValue,
+            message,
+            value.span(),
+            self.ctx.lines,
+        ));
+    }
+}
+
+/// A setting value reduced to the shapes type checking cares about
+enum Literal<'a> {
+    Number {
+        text: &'a str,
+        negative: bool,
+    },
+    String(&'a str),
+    Bool,
+    /// A bare word such as `auto`
+    Word(&'a str),
+}
+
+impl<'a> Literal<'a> {
+    /// `None` for anything that is not a plain literal (expressions,
+    /// placeholders, `NULL`), which is left unchecked
+    fn from_expr(expr: &'a Expr) -> Option<Self> {
+        match expr {
+            Expr::Value(v) => match &v.value {
+                Value::Number(text, _) => Some(Literal::Number {
+                    text,
+                    negative: false,
+                }),
+                Value::SingleQuotedString(s) => Some(Literal::String(s)),
+                Value::Boolean(_) => Some(Literal::Bool),
+                _ => None,
+            },
+            Expr::UnaryOp {
+                op: UnaryOperator::Minus,
+                expr,
+            } => match Literal::from_expr(expr)? {
+                Literal::Number { text, .. } => Some(Literal::Number {
+                    text,
+                    negative: true,
+                }),
+                _ => None,
+            },
+            Expr::Identifier(ident) if !is_template_placeholder(&ident.value) => {
+                Some(Literal::Word(&ident.value))
+            }
+            _ => None,
+        }
+    }
+
+    /// The value as a number, accepting numeric strings as `ClickHouse` does
+    fn number(&self) -> Option<f64> {
+        let (text, negative) = match self {
+            Literal::Number { text, negative } => (*text, *negative),
+            Literal::String(s) => (s.trim(), false),
+            _ => return None,
+        };
+        let n: f64 = text.parse().ok()?;
+        Some(if negative { -n } else { n })
+    }
+
+    fn is_integer(&self) -> bool {
+        self.number().is_some_and(|n| n.fract() == 0.0)
+    }
+
+    fn is_unsigned_integer(&self) -> bool {
+        self.is_integer() && self.number().is_some_and(|n| n >= 0.0)
+    }
+
+    fn is_bool(&self) -> bool {
+        match self {
+            Literal::Bool => true,
+            Literal::Number { text, negative } => !negative && matches!(*text, "0" | "1"),
+            Literal::String(s) | Literal::Word(s) => [
+                "true", "false", "1", "0", "yes", "no", "on", "off", "enable", "disable",
+                "enabled", "disabled",
+            ]
+            .iter()
+            .any(|b| b.eq_ignore_ascii_case(s)),
+        }
+    }
+
+    fn is_auto(&self) -> bool {
+        matches!(self, Literal::String(s) | Literal::Word(s) if s.eq_ignore_ascii_case("auto"))
+    }
+}
+
+/// Describes what a setting of type `setting_type` accepts, or `None` if
+/// `value` is acceptable or the type has no checkable form
+fn expected_form(setting_type: &str, value: &Literal) -> Option<&'static str> {
+    let (ok, expected) = match setting_type {
+        "Bool" => (value.is_bool(), "a boolean (0, 1, true or false)"),
+        "BoolAuto" => (value.is_bool() || value.is_auto(), "a boolean or 'auto'"),
+        "UInt64" => (value.is_unsigned_integer(), "a non-negative integer"),
+        "NonZeroUInt64" => (
+            value.is_unsigned_integer() && value.number().is_some_and(|n| n >= 1.0),
+            "a positive integer",
+        ),
+        "Int64" => (value.is_integer(), "an integer"),
+        "MaxThreads" | "UInt64Auto" => (
+            value.is_unsigned_integer() || value.is_auto(),
+            "a non-negative integer or 'auto'",
+        ),
+        "Float" | "Double" => (value.number().is_some(), "a number"),
+        "FloatAuto" => (
+            value.number().is_some() || value.is_auto(),
+            "a number or 'auto'",
+        ),
+        "Seconds" | "Milliseconds" => (
+            value.number().is_some_and(|n| n >= 0.0),
+            "a non-negative duration",
+        ),
+        // Free-form values
+        "String" | "Char" | "URI" | "Timezone" | "Map" => return None,
+        // Every other type is an enum whose values are named by strings
+        _ => (
+            matches!(value, Literal::String(_) | Literal::Word(_)),
+            "one of its named values as a string",
+        ),
+    };
+    (!ok).then_some(expected)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::clickhouse_index::test_data::clickhouse_25_8;
+    use crate::{validate, ErrorCode, Schema, ValidationError};
+
+    fn setting_errors(sql: &str) -> Vec<ValidationError> {
+        validate(sql, Some(clickhouse_25_8()), &Schema::default())
+            .errors
+            .into_iter()
+            .filter(|e| {
+                matches!(
+                    e.code,
+                    ErrorCode::UnknownSetting
+                        | ErrorCode::MisplacedSetting
+                        | ErrorCode::InvalidSettingValue
+                )
+            })
+            .collect()
+    }
+
+    fn codes(sql: &str) -> Vec<ErrorCode> {
+        setting_errors(sql).into_iter().map(|e| e.code).collect()
+    }
+
+    #[test]
+    fn test_accepts_valid_settings() {
+        let sql = "SELECT * FROM t SETTINGS max_threads = 8, max_threads = 'auto', \
+                   use_query_cache = true, log_queries = 0, max_execution_time = 1.5, \
+                   join_algorithm = 'hash', max_memory_usage = '1000000'";
+        assert!(setting_errors(sql).is_empty(), "{:?}", setting_errors(sql));
+
+        let sql = "CREATE TABLE t (a UInt64) ENGINE = MergeTree ORDER BY a \
+                   SETTINGS index_granularity = 8192, ttl_only_drop_parts = 1";
+        let result = validate(sql, Some(clickhouse_25_8()), &Schema::default());
+        assert!(result.valid, "{:?}", result.errors);
+    }
+
+    #[test]
+    fn test_reports_unknown_setting_with_suggestion() {
+        let errors = setting_errors("SELECT * FROM t SETTINGS max_thread = 4");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].code, ErrorCode::UnknownSetting);
+        assert_eq!(errors[0].start_offset, Some(25));
+        assert_eq!(
+            errors[0].suggestions.first().map(String::as_str),
+            Some("max_threads")
+        );
+    }
+
+    #[test]
+    fn test_reports_values_of_the_wrong_type() {
+        let sql = "SELECT * FROM t SETTINGS max_threads = 'lots'";
+        let errors = setting_errors(sql);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].code, ErrorCode::InvalidSettingValue);
+        assert_eq!(errors[0].start_offset, Some(39));
+        assert!(errors[0].message.contains("'auto'"));
+
+        assert_eq!(
+            codes("SELECT * FROM t SETTINGS use_query_cache = 2, max_block_size = -1, join_algorithm = 1"),
+            vec![ErrorCode::InvalidSettingValue; 3]
+        );
+    }
+
+    #[test]
+    fn test_reports_settings_in_the_wrong_clause() {
+        assert_eq!(
+            codes("SELECT * FROM t SETTINGS index_granularity = 8192"),
+            vec![ErrorCode::MisplacedSetting]
+        );
+        assert_eq!(
+            codes(
+                "CREATE TABLE t (a UInt64) ENGINE = MergeTree ORDER BY a SETTINGS max_threads = 4"
+            ),
+            vec![ErrorCode::MisplacedSetting]
+        );
+        assert_eq!(
+            codes("CREATE TABLE t (a UInt64) ENGINE = MergeTree ORDER BY a SETTINGS index_granularty = 1"),
+            vec![ErrorCode::UnknownSetting]
+        );
+    }
+
+    #[test]
+    fn test_skips_settings_of_other_engines() {
+        let sql = "CREATE TABLE t (a UInt64) ENGINE = Kafka SETTINGS kafka_broker_list = 'x', \
+                   kafka_topic_list = 't', kafka_group_name = 'g', kafka_format = 'JSONEachRow'";
+        assert!(codes(sql).is_empty());
+        assert_eq!(
+            codes("CREATE TABLE t (a UInt64) ENGINE = ReplicatedMergeTree ORDER BY a SETTINGS max_threads = 4"),
+            vec![ErrorCode::MisplacedSetting]
+        );
+    }
+}
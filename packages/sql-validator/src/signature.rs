@@ -0,0 +1,344 @@
+//! Function signatures parsed from the free-form `FunctionInfo::syntax` text.
+//!
+//! The documentation writes signatures like `toStartOfInterval(time,
+//! interval[, timezone])`, one overload per line, with `[...]` around optional
+//! parameters and `...` for repetition. Parametric aggregates are written with
+//! two groups, as in `quantile(level)(expr)`; the last group holds the
+//! arguments. Lines that are not a call of the function are ignored.
+
+/// Parameters of one overload
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct Signature {
+    pub required: Vec<String>,
+    pub optional: Vec<String>,
+    /// Accepts any number of further arguments
+    pub variadic: bool,
+    /// Written as `name(parameters)(arguments)`
+    pub parametric: bool,
+}
+
+impl Signature {
+    pub fn min_args(&self) -> usize {
+        self.required.len()
+    }
+
+    /// `None` when the signature is variadic
+    pub fn max_args(&self) -> Option<usize> {
+        (!self.variadic).then(|| self.required.len() + self.optional.len())
+    }
+
+    pub fn accepts(&self, count: usize) -> bool {
+        count >= self.min_args() && self.max_args().is_none_or(|max| count <= max)
+    }
+}
+
+/// Parses every overload of `name` found in `syntax`
+pub(crate) fn parse_signatures(name: &str, syntax: &str) -> Vec<Signature> {
+    let mut signatures = Vec::new();
+    for line in syntax.lines().map(str::trim) {
+        if line.starts_with("--") {
+            continue;
+        }
+        let Some(groups) = call_groups(name, line) else {
+            continue;
+        };
+        let Some(arguments) = groups.last() else {
+            continue;
+        };
+        let mut signature = parse_params(arguments);
+        signature.parametric = groups.len() > 1;
+        if !signatures.contains(&signature) {
+            signatures.push(signature);
+        }
+    }
+    signatures
+}
+
+/// Syntax of common functions the documentation gives none for, written
+/// like documented syntax. `OrNull` and `OrZero` conversions take the
+/// arguments of the plain conversion. `sum`, `min` and `max` are left out:
+/// their `Map` forms still accept the older `(keys, values)` arguments.
+pub(crate) fn undocumented_syntax(name: &str) -> Option<String> {
+    let base = ["OrNull", "OrZero"]
+        .iter()
+        .find_map(|suffix| name.strip_suffix(suffix))
+        .unwrap_or(name);
+    let overloads: &[&str] = match base {
+        "toDateTime"
+        | "toDateTime32"
+        | "toDate"
+        | "toString"
+        | "parseDateTimeBestEffort"
+        | "parseDateTimeBestEffortUS" => &["(expr[, timezone])"],
+        "toDateTime64" => &["(expr, scale[, timezone])"],
+        "parseDateTime64BestEffort" => &["(expr[, precision[, timezone]])"],
+        "dateDiff" => &["(unit, startdate, enddate[, timezone])"],
+        "toInt8" | "toInt16" | "toInt32" | "toInt64" | "toInt128" | "toInt256" | "toUInt8"
+        | "toUInt16" | "toUInt32" | "toUInt64" | "toUInt128" | "toUInt256" | "toFloat32"
+        | "toFloat64" | "toUUID" | "toTypeName" | "toLowCardinality" | "formatReadableSize"
+        | "mapKeys" | "mapValues" => &["(expr)"],
+        "toDecimal32" | "toDecimal64" | "toDecimal128" | "toDecimal256" => &["(expr, scale)"],
+        "toFixedString" => &["(s, n)"],
+        "tupleElement" => &["(tuple, index_or_name[, default_value])"],
+        "position" => &["(haystack, needle[, start_pos])"],
+        "like" | "match" => &["(haystack, pattern)"],
+        "JSONExtractString" | "JSONExtractInt" => &["(json [, indices_or_keys]...)"],
+        "JSONExtract" => &["(json, return_type, ...)"],
+        "generateUUIDv4" => &["([expr])"],
+        "avg" | "any" | "anyLast" => &["(x)"],
+        "argMax" | "argMin" => &["(arg, val)"],
+        "uniq" | "uniqExact" => &["(x[, ...])"],
+        "groupArray" => &["(x)", "(max_size)(x)"],
+        "quantile" => &["(expr)", "(level)(expr)"],
+        _ => return None,
+    };
+    Some(
+        overloads
+            .iter()
+            .map(|params| format!("{name}{params}"))
+            .collect::<Vec<_>>()
+            .join("\n"),
+    )
+}
+
+/// The contents of each parenthesized group directly following the first
+/// call of `name` in `line`
+fn call_groups<'a>(name: &str, line: &'a str) -> Option<Vec<&'a str>> {
+    let haystack = line.to_ascii_lowercase();
+    let needle = format!("{}(", name.to_ascii_lowercase());
+    let start = haystack.match_indices(&needle).map(|(i, _)| i).find(|&i| {
+        !line[..i]
+            .chars()
+            .next_back()
+            .is_some_and(|c| c.is_alphanumeric() || c == '_')
+    })?;
+
+    let mut groups = Vec::new();
+    let mut rest = &line[start + name.len()..];
+    while rest.starts_with('(') {
+        let mut depth = 0usize;
+        let close = rest.char_indices().find_map(|(i, c)| {
+            match c {
+                '(' => depth += 1,
+                ')' => depth -= 1,
+                _ => {}
+            }
+            (depth == 0).then_some(i)
+        })?;
+        groups.push(&rest[1..close]);
+        rest = &rest[close + 1..];
+    }
+    Some(groups)
+}
+
+/// Parses a parameter list such as `x1[, x2, ..., xN]`
+fn parse_params(text: &str) -> Signature {
+    struct Param {
+        name: String,
+        optional: bool,
+    }
+
+    let mut params: Vec<Param> = Vec::new();
+    let mut variadic = false;
+    let mut current = String::new();
+    let mut current_optional = false;
+    let mut optional_depth = 0usize;
+    let mut paren_depth = 0usize;
+
+    let mut flush = |current: &mut String, optional: bool, variadic: &mut bool| {
+        let param = std::mem::take(current);
+        let param = param.trim();
+        if param.is_empty() {
+            return;
+        }
+        if is_ellipsis(param) {
+            *variadic = true;
+        } else if !*variadic {
+            // Parameters after the repetition (`..., arrN`) are not counted
+            params.push(Param {
+                name: param.to_string(),
+                optional,
+            });
+            if param == "args" {
+                *variadic = true;
+            }
+        }
+    };
+
+    for c in text.chars() {
+        match c {
+            '(' => {
+                paren_depth += 1;
+                current.push(c);
+            }
+            ')' => {
+                paren_depth = paren_depth.saturating_sub(1);
+                current.push(c);
+            }
+            _ if paren_depth > 0 => current.push(c),
+            '[' | ']' | ',' => {
+                flush(&mut current, current_optional, &mut variadic);
+                match c {
+                    '[' => optional_depth += 1,
+                    ']' => optional_depth = optional_depth.saturating_sub(1),
+                    _ => {}
+                }
+            }
+            _ => {
+                if current.trim().is_empty() {
+                    current_optional = optional_depth > 0;
+                }
+                current.push(c);
+            }
+        }
+    }
+    flush(&mut current, current_optional, &mut variadic);
+
+    let mut signature = Signature {
+        variadic,
+        ..Signature::default()
+    };
+    for param in params {
+        // In `arr1, arr2, ..., arrN` only the first is really required
+        if variadic && is_repetition(&param.name) {
+            continue;
+        }
+        if param.optional {
+            signature.optional.push(param.name);
+        } else {
+            signature.required.push(param.name);
+        }
+    }
+    signature
+}
+
+/// Whether `...` appears in `param` outside any nested call
+fn is_ellipsis(param: &str) -> bool {
+    let mut depth = 0usize;
+    let outer: String = param
+        .chars()
+        .filter(|&c| {
+            match c {
+                '(' => depth += 1,
+                ')' => {
+                    depth = depth.saturating_sub(1);
+                    return false;
+                }
+                _ => {}
+            }
+            depth == 0
+        })
+        .collect();
+    outer.contains("...") || outer.contains('…')
+}
+
+/// Names like `x2` or `cond_2`: a second example of a repeated parameter
+fn is_repetition(name: &str) -> bool {
+    let digits = name
+        .chars()
+        .rev()
+        .take_while(char::is_ascii_digit)
+        .collect::<String>();
+    !digits.is_empty() && digits != "1" && digits != "0"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn arity(name: &str, syntax: &str) -> Vec<(usize, Option<usize>)> {
+        parse_signatures(name, syntax)
+            .iter()
+            .map(|s| (s.min_args(), s.max_args()))
+            .collect()
+    }
+
+    #[test]
+    fn test_required_and_optional() {
+        let signatures = parse_signatures(
+            "formatDateTime",
+            "formatDateTime(datetime, format[, timezone])",
+        );
+        assert_eq!(signatures.len(), 1);
+        assert_eq!(signatures[0].required, vec!["datetime", "format"]);
+        assert_eq!(signatures[0].optional, vec!["timezone"]);
+        assert!(!signatures[0].variadic);
+
+        assert_eq!(arity("e", "e()"), vec![(0, Some(0))]);
+        assert_eq!(
+            arity(
+                "toLastDayOfWeek",
+                "toLastDayOfWeek(datetime[, mode[, timezone]])"
+            ),
+            vec![(1, Some(3))]
+        );
+        assert_eq!(
+            arity(
+                "arrayPartialSort",
+                "arrayPartialSort([f,] arr [, arr1, ... ,arrN], limit)"
+            ),
+            vec![(1, None)]
+        );
+    }
+
+    #[test]
+    fn test_variadic() {
+        assert_eq!(arity("concat", "concat([s1, s2, ...])"), vec![(0, None)]);
+        assert_eq!(
+            arity("greatest", "greatest(x1[, x2, ..., xN])"),
+            vec![(1, None)]
+        );
+        assert_eq!(
+            arity(
+                "arrayZipUnaligned",
+                "arrayZipUnaligned(arr1, arr2, ..., arrN)"
+            ),
+            vec![(1, None)]
+        );
+        assert_eq!(
+            arity(
+                "multiIf",
+                "multiIf(cond_1, then_1, cond_2, then_2, ..., else)"
+            ),
+            vec![(2, None)]
+        );
+        assert_eq!(
+            arity(
+                "JSONExtractUInt",
+                "JSONExtractUInt(json [, indices_or_keys]...)"
+            ),
+            vec![(1, None)]
+        );
+    }
+
+    #[test]
+    fn test_overloads_and_noise() {
+        let syntax = "toStartOfInterval(value, INTERVAL x unit[, time_zone])\n\
+                      toStartOfInterval(value, INTERVAL x unit[, origin[, time_zone]])";
+        assert_eq!(
+            arity("toStartOfInterval", syntax),
+            vec![(2, Some(3)), (2, Some(4))]
+        );
+        assert_eq!(arity("MD4", "SELECT MD4(s);"), vec![(1, Some(1))]);
+        assert_eq!(
+            arity("mortonEncode", "-- Simplified mode\nmortonEncode(args)\n\n-- Expanded mode\nmortonEncode(range_mask, args)"),
+            vec![(1, None), (2, None)]
+        );
+        // Nested calls inside an optional group stay one parameter
+        assert_eq!(
+            arity(
+                "arrayAvg",
+                "arrayAvg([func(x[, y1, ..., yN])], source_arr[, cond1_arr, ... , condN_arr])"
+            ),
+            vec![(1, None)]
+        );
+        assert!(arity("sum", "arraySum(arr)").is_empty());
+    }
+
+    #[test]
+    fn test_parametric() {
+        let signatures = parse_signatures("quantile", "quantile(level)(expr)");
+        assert_eq!(signatures[0].required, vec!["expr"]);
+        assert!(signatures[0].parametric);
+    }
+}
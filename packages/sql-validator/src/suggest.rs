@@ -0,0 +1,111 @@
+//! "Did you mean" suggestions ranked by edit distance.
+
+/// Most suggestions attached to a single diagnostic
+pub(crate) const MAX_SUGGESTIONS: usize = 3;
+
+/// Returns up to `MAX_SUGGESTIONS` candidates close to `name`, best first.
+///
+/// Distance is case-insensitive edit distance where swapping two adjacent
+/// characters counts as one edit; a candidate differing only in case always
+/// ranks first. Candidates further than about a third of the name's
+/// length (at least 2 edits) are not considered typos of it.
+pub(crate) fn suggest<'a, I>(name: &str, candidates: I) -> Vec<String>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let needle = name.to_lowercase();
+    let max_distance = (needle.chars().count() / 3).max(2);
+
+    let mut ranked: Vec<(usize, &str)> = candidates
+        .into_iter()
+        .filter(|candidate| candidate.len().abs_diff(needle.len()) <= max_distance)
+        .filter_map(|candidate| {
+            let distance = edit_distance(&needle, &candidate.to_lowercase());
+            (distance <= max_distance).then_some((distance, candidate))
+        })
+        .collect();
+
+    ranked.sort_by(|(da, a), (db, b)| da.cmp(db).then_with(|| a.cmp(b)));
+    ranked.dedup_by(|(_, a), (_, b)| a == b);
+    ranked
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, candidate)| candidate.to_string())
+        .collect()
+}
+
+/// Formats the `Did you mean ...?` tail of a diagnostic message
+pub(crate) fn did_you_mean(suggestions: &[String]) -> String {
+    match suggestions {
+        [] => String::new(),
+        [only] => format!(" Did you mean `{only}`?"),
+        [init @ .., last] => {
+            let init: Vec<String> = init.iter().map(|s| format!("`{s}`")).collect();
+            format!(" Did you mean {} or `{last}`?", init.join(", "))
+        }
+    }
+}
+
+/// Optimal string alignment distance: Levenshtein plus adjacent transpositions
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut before_previous = vec![0; b.len() + 1];
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+
+    for (i, &ca) in a.iter().enumerate() {
+        current[0] = i + 1;
+        for (j, &cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            let mut best = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+            if i > 0 && j > 0 && ca == b[j - 1] && a[i - 1] == cb {
+                best = best.min(before_previous[j - 1] + 1);
+            }
+            current[j + 1] = best;
+        }
+        std::mem::swap(&mut before_previous, &mut previous);
+        std::mem::swap(&mut previous, &mut current);
+    }
+
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("same", "same"), 0);
+        assert_eq!(edit_distance("datetime46", "datetime64"), 1);
+    }
+
+    #[test]
+    fn test_suggest_ranks_closest_first() {
+        let candidates = ["toStartOfHour", "toStartOfDay", "toStartOfMinute", "count"];
+        assert_eq!(
+            suggest("toStartOfHourr", candidates),
+            vec!["toStartOfHour".to_string()]
+        );
+        assert_eq!(
+            suggest("tostartofday", candidates)
+                .first()
+                .map(String::as_str),
+            Some("toStartOfDay")
+        );
+        assert!(suggest("completelyDifferent", candidates).is_empty());
+    }
+
+    #[test]
+    fn test_did_you_mean() {
+        assert_eq!(did_you_mean(&[]), "");
+        assert_eq!(did_you_mean(&["a".into()]), " Did you mean `a`?");
+        assert_eq!(
+            did_you_mean(&["a".into(), "b".into(), "c".into()]),
+            " Did you mean `a`, `b` or `c`?"
+        );
+    }
+}
@@ -0,0 +1,459 @@
+//! Template placeholders treated as holes in the SQL.
+//!
+//! A `${...}` interpolation can stand for a value, a name, a table or a whole
+//! expression, and no single stand-in is grammatical everywhere: `_ph_1`
+//! works in `FROM ${table}` but not in `IN ${list}`, which needs a tuple, or
+//! `Decimal(10, ${scale})`, which needs a number. Each hole is filled with one
+//! stand-in at a time until the SQL around it parses, and the role the hole
+//! then plays is read back from the AST.
+//!
+//! Stand-ins rarely have the length of the `${...}` they replace, so filling
+//! keeps an `OffsetMap` from the filled SQL back to the template, and
+//! diagnostics are moved back through it before they are reported.
+
+use crate::position::LineIndex;
+use crate::recovery::{self, Fragment, RecoveredParse};
+use crate::{ErrorCategory, Hole, HoleRole, ParseMode, ValidationError};
+use sqlparser::ast::{
+    Expr, LimitClause, ObjectName, ObjectNamePart, Query, Spanned, Statement, Visit, Visitor,
+};
+use sqlparser::keywords::Keyword;
+use sqlparser::tokenizer::{Span, Token};
+use std::ops::ControlFlow;
+
+/// Text a hole is filled with, in the order they are tried
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StandIn {
+    /// `_ph_N`, which parses as a name, a table or a column expression
+    Identifier,
+    Number,
+    String,
+    /// `(0)`, for holes that must be a parenthesized list or expression
+    Tuple,
+}
+
+const STAND_INS: [StandIn; 4] = [
+    StandIn::Identifier,
+    StandIn::Number,
+    StandIn::String,
+    StandIn::Tuple,
+];
+
+impl StandIn {
+    fn text(self, hole_number: usize) -> String {
+        match self {
+            StandIn::Identifier => format!("_ph_{hole_number}"),
+            StandIn::Number => "0".to_string(),
+            StandIn::String => "''".to_string(),
+            StandIn::Tuple => "(0)".to_string(),
+        }
+    }
+}
+
+/// `sql` with its holes filled, and the role each hole plays in it; `None`
+/// for holes under which no stand-in parses
+#[derive(Debug)]
+pub(crate) struct FilledTemplate {
+    pub sql: String,
+    pub roles: Vec<Option<HoleRole>>,
+    pub offsets: OffsetMap,
+}
+
+/// Where each hole of a template ended up in the filled SQL
+#[derive(Debug, Default)]
+pub(crate) struct OffsetMap {
+    /// Pairs of a hole in the template and its stand-in in the filled SQL,
+    /// in order
+    holes: Vec<(Hole, Hole)>,
+}
+
+impl OffsetMap {
+    /// The template offset of a filled-SQL offset. Offsets inside a stand-in
+    /// widen to the whole hole: to its start for the `start` of a range and
+    /// to its end otherwise.
+    pub fn to_original(&self, offset: usize, start: bool) -> usize {
+        // The last pair of positions known to line up
+        let mut anchor = (0, 0);
+        for &(original, filled) in &self.holes {
+            if offset <= filled.start {
+                break;
+            }
+            if offset < filled.end {
+                return if start { original.start } else { original.end };
+            }
+            anchor = (original.end, filled.end);
+        }
+        anchor.0 + (offset - anchor.1)
+    }
+
+    /// `error`, found in the filled SQL, positioned in the template
+    pub fn map_error(&self, error: ValidationError, lines: &LineIndex) -> ValidationError {
+        let (Some(start), Some(end)) = (error.start_offset, error.end_offset) else {
+            return error;
+        };
+        ValidationError::at_range(
+            error.code,
+            error.message,
+            self.to_original(start, true),
+            self.to_original(end, false),
+            lines,
+        )
+        .with_suggestions(error.suggestions)
+    }
+
+    fn filled(&self, i: usize) -> Hole {
+        self.holes[i].1
+    }
+}
+
+/// Drops holes that are out of range, split a character or overlap an
+/// earlier hole, and sorts the rest
+pub(crate) fn normalize_holes(sql: &str, holes: &[Hole]) -> Vec<Hole> {
+    let mut holes: Vec<Hole> = holes
+        .iter()
+        .copied()
+        .filter(|h| {
+            h.start < h.end
+                && h.end <= sql.len()
+                && sql.is_char_boundary(h.start)
+                && sql.is_char_boundary(h.end)
+        })
+        .collect();
+    holes.sort_by_key(|h| h.start);
+    let mut end = 0;
+    holes.retain(|h| {
+        let keep = h.start >= end;
+        if keep {
+            end = h.end;
+        }
+        keep
+    });
+    holes
+}
+
+/// Fills every hole of `sql`, trying stand-ins for each hole that a syntax
+/// error points into until one parses. `holes` must be normalized.
+pub(crate) fn fill_holes(sql: &str, holes: &[Hole], mode: ParseMode) -> FilledTemplate {
+    let mut choices = vec![0usize; holes.len()];
+    let mut exhausted = vec![false; holes.len()];
+
+    let (filled, offsets, parsed) = loop {
+        let (filled, offsets) = fill(sql, holes, &choices);
+        let parsed = recovery::parse_fragment(&filled, mode);
+        let lines = LineIndex::new(&filled);
+        let blocked = (0..holes.len()).find(|&i| {
+            let hole = offsets.filled(i);
+            !exhausted[i]
+                && (parsed.errors.iter().any(|e| {
+                    e.category == ErrorCategory::Syntax
+                        && e.start_offset.is_some_and(|o| hole.contains(o))
+                }) || (STAND_INS[choices[i]] == StandIn::Tuple
+                    && follows_name(&parsed, &lines, hole)))
+        });
+        let Some(i) = blocked else {
+            break (filled, offsets, parsed);
+        };
+        if choices[i] + 1 < STAND_INS.len() {
+            choices[i] += 1;
+        } else {
+            // Nothing parses; report errors against the identifier
+            choices[i] = 0;
+            exhausted[i] = true;
+        }
+    };
+
+    let roles = roles(&filled, &offsets, &choices, &exhausted, &parsed);
+    FilledTemplate {
+        sql: filled,
+        roles,
+        offsets,
+    }
+}
+
+/// Whether `hole` directly follows a name or a closing paren, where a
+/// parenthesized stand-in would be read as call arguments rather than as
+/// what the hole stands for
+fn follows_name(parsed: &RecoveredParse, lines: &LineIndex, hole: Hole) -> bool {
+    let previous = parsed
+        .tokens
+        .iter()
+        .take_while(|t| lines.offset(t.span.start) < hole.start)
+        .last();
+    match previous.map(|t| &t.token) {
+        Some(Token::Word(w)) => w.keyword == Keyword::NoKeyword || w.quote_style.is_some(),
+        Some(Token::RParen) => true,
+        _ => false,
+    }
+}
+
+/// `sql` with each hole replaced by its chosen stand-in
+fn fill(sql: &str, holes: &[Hole], choices: &[usize]) -> (String, OffsetMap) {
+    let mut filled = String::with_capacity(sql.len());
+    let mut offsets = OffsetMap::default();
+    let mut copied = 0;
+    for (i, hole) in holes.iter().enumerate() {
+        filled.push_str(&sql[copied..hole.start]);
+        let start = filled.len();
+        filled.push_str(&STAND_INS[choices[i]].text(i + 1));
+        offsets.holes.push((
+            *hole,
+            Hole {
+                start,
+                end: filled.len(),
+            },
+        ));
+        copied = hole.end;
+    }
+    filled.push_str(&sql[copied..]);
+    (filled, offsets)
+}
+
+/// Reads each hole's role from where its stand-in ended up
+fn roles(
+    filled: &str,
+    offsets: &OffsetMap,
+    choices: &[usize],
+    exhausted: &[bool],
+    parsed: &RecoveredParse,
+) -> Vec<Option<HoleRole>> {
+    let lines = LineIndex::new(filled);
+    let holes: Vec<Hole> = offsets.holes.iter().map(|&(_, filled)| filled).collect();
+    let mut visitor = Roles {
+        lines: &lines,
+        holes: &holes,
+        found: vec![None; holes.len()],
+        literal_only: Vec::new(),
+    };
+    for statement in &parsed.statements {
+        let _ = statement.visit(&mut visitor);
+    }
+    if let Some(fragment) = &parsed.fragment {
+        let _ = fragment.visit(&mut visitor);
+    }
+    let found = visitor.found;
+
+    (0..holes.len())
+        .map(|i| {
+            if exhausted[i] {
+                return None;
+            }
+            let in_string = parsed.tokens.iter().any(|t| {
+                matches!(t.token, Token::SingleQuotedString(_)) && {
+                    let (start, end) = lines.range(t.span);
+                    start < holes[i].start && holes[i].end <= end
+                }
+            });
+            Some(match STAND_INS[choices[i]] {
+                _ if in_string => HoleRole::Value,
+                StandIn::Number | StandIn::String => HoleRole::Value,
+                StandIn::Tuple => HoleRole::Expression,
+                StandIn::Identifier => found[i].unwrap_or(HoleRole::Identifier),
+            })
+        })
+        .collect()
+}
+
+/// Finds identifier stand-ins used as tables and as expressions
+struct Roles<'a> {
+    lines: &'a LineIndex<'a>,
+    holes: &'a [Hole],
+    found: Vec<Option<HoleRole>>,
+    /// Expressions where only a literal is accepted: `LIMIT`, `OFFSET`,
+    /// `INTERVAL` amounts and setting values
+    literal_only: Vec<Span>,
+}
+
+impl Roles<'_> {
+    fn mark(&mut self, span: Span, role: HoleRole) {
+        if span == Span::empty() {
+            return;
+        }
+        let start = self.lines.offset(span.start);
+        if let Some(i) = self.holes.iter().position(|h| h.contains(start)) {
+            self.found[i].get_or_insert(role);
+        }
+    }
+}
+
+impl Visitor for Roles<'_> {
+    type Break = ();
+
+    fn pre_visit_relation(&mut self, relation: &ObjectName) -> ControlFlow<()> {
+        for part in &relation.0 {
+            if let ObjectNamePart::Identifier(ident) = part {
+                self.mark(ident.span, HoleRole::Table);
+            }
+        }
+        ControlFlow::Continue(())
+    }
+
+    fn pre_visit_query(&mut self, query: &Query) -> ControlFlow<()> {
+        match &query.limit_clause {
+            Some(LimitClause::LimitOffset { limit, offset, .. }) => {
+                self.literal_only.extend(limit.iter().map(Spanned::span));
+                self.literal_only
+                    .extend(offset.iter().map(|o| o.value.span()));
+            }
+            Some(LimitClause::OffsetCommaLimit { offset, limit }) => {
+                self.literal_only.extend([offset.span(), limit.span()]);
+            }
+            None => {}
+        }
+        self.literal_only
+            .extend(query.settings.iter().flatten().map(|s| s.value.span()));
+        ControlFlow::Continue(())
+    }
+
+    fn pre_visit_statement(&mut self, statement: &Statement) -> ControlFlow<()> {
+        if let Statement::Insert(insert) = statement {
+            self.literal_only
+                .extend(insert.settings.iter().flatten().map(|s| s.value.span()));
+        }
+        ControlFlow::Continue(())
+    }
+
+    fn pre_visit_expr(&mut self, expr: &Expr) -> ControlFlow<()> {
+        match expr {
+            Expr::Interval(interval) => self.literal_only.push(interval.value.span()),
+            Expr::Identifier(ident) => {
+                let role = if self.literal_only.contains(&ident.span) {
+                    HoleRole::Value
+                } else {
+                    HoleRole::Expression
+                };
+                self.mark(ident.span, role);
+            }
+            _ => {}
+        }
+        ControlFlow::Continue(())
+    }
+}
+
+impl Fragment {
+    /// Visits the parsed fragment like `Visit::visit` on a statement
+    pub(crate) fn visit<V: Visitor>(&self, visitor: &mut V) -> ControlFlow<V::Break> {
+        match self {
+            Fragment::Expr(expr) => expr.visit(visitor),
+            Fragment::SelectItems(items) => items.visit(visitor),
+            Fragment::OrderBy(exprs) => exprs.visit(visitor),
+            Fragment::Columns(columns) => columns.visit(visitor),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Holes at every `_ph_N` in `sql`
+    fn holes_of(sql: &str) -> Vec<Hole> {
+        sql.match_indices("_ph_")
+            .map(|(start, _)| {
+                let digits = sql[start + 4..]
+                    .chars()
+                    .take_while(char::is_ascii_digit)
+                    .count();
+                Hole {
+                    start,
+                    end: start + 4 + digits,
+                }
+            })
+            .collect()
+    }
+
+    fn roles_of(sql: &str, mode: ParseMode) -> Vec<Option<HoleRole>> {
+        fill_holes(sql, &holes_of(sql), mode).roles
+    }
+
+    #[test]
+    fn test_roles_of_identifier_stand_ins() {
+        assert_eq!(
+            roles_of(
+                "SELECT _ph_1, t._ph_2 FROM _ph_3 AS t WHERE x = _ph_4 LIMIT _ph_5",
+                ParseMode::Statement
+            ),
+            vec![
+                Some(HoleRole::Expression),
+                Some(HoleRole::Identifier),
+                Some(HoleRole::Table),
+                Some(HoleRole::Expression),
+                Some(HoleRole::Value),
+            ]
+        );
+        assert_eq!(
+            roles_of(
+                "SELECT now() - INTERVAL _ph_1 DAY, 'prefix-_ph_2'",
+                ParseMode::Statement
+            ),
+            vec![Some(HoleRole::Value), Some(HoleRole::Value)]
+        );
+        assert_eq!(
+            roles_of("INSERT INTO _ph_1 (a) VALUES (1)", ParseMode::Statement),
+            vec![Some(HoleRole::Table)]
+        );
+    }
+
+    #[test]
+    fn test_falls_back_to_other_stand_ins() {
+        let sql = "SELECT * FROM t WHERE x IN _ph_1";
+        let filled = fill_holes(sql, &holes_of(sql), ParseMode::Statement);
+        assert_eq!(filled.roles, vec![Some(HoleRole::Expression)]);
+        assert_eq!(filled.sql, "SELECT * FROM t WHERE x IN (0)");
+        assert!(recovery::parse_with_recovery(&filled.sql).errors.is_empty());
+
+        assert_eq!(
+            roles_of(
+                "CREATE TABLE t (a Decimal(10, _ph_1)) ENGINE = Memory",
+                ParseMode::Statement
+            ),
+            vec![Some(HoleRole::Value)]
+        );
+        assert_eq!(
+            roles_of("a = _ph_1 AND b IN _ph_2", ParseMode::Condition),
+            vec![Some(HoleRole::Expression), Some(HoleRole::Expression)]
+        );
+    }
+
+    #[test]
+    fn test_unparseable_holes_have_no_role() {
+        // `(0)` would parse as arguments of a call to `x`
+        let sql = "SELECT * FROM t ORDER BY x _ph_1";
+        let filled = fill_holes(sql, &holes_of(sql), ParseMode::Statement);
+        assert_eq!(filled.roles, vec![None]);
+        assert_eq!(filled.sql, sql);
+    }
+
+    #[test]
+    fn test_offset_map_points_back_into_the_template() {
+        let sql = "SELECT ${someLongExpression} + ${\n  x}";
+        let holes = [Hole { start: 7, end: 28 }, Hole { start: 31, end: 38 }];
+        let (filled, offsets) = fill(sql, &holes, &[0, 1]);
+        assert_eq!(filled, "SELECT _ph_1 + 0");
+
+        // Outside holes, offsets shift by the difference in length
+        assert_eq!(offsets.to_original(3, true), 3);
+        assert_eq!(offsets.to_original(13, true), 29);
+        // Inside a stand-in, ranges widen to the whole hole
+        assert_eq!(offsets.to_original(7, true), 7);
+        assert_eq!(offsets.to_original(9, true), 7);
+        assert_eq!(offsets.to_original(9, false), 28);
+        assert_eq!(offsets.to_original(12, false), 28);
+        assert_eq!(offsets.to_original(16, false), 38);
+    }
+
+    #[test]
+    fn test_normalize_holes() {
+        let sql = "SELECT é, x";
+        let holes = [
+            Hole { start: 11, end: 12 },
+            Hole { start: 7, end: 8 },
+            Hole { start: 7, end: 9 },
+            Hole { start: 8, end: 12 },
+            Hole { start: 20, end: 22 },
+        ];
+        assert_eq!(
+            normalize_holes(sql, &holes),
+            vec![Hole { start: 7, end: 9 }, Hole { start: 11, end: 12 }]
+        );
+    }
+}
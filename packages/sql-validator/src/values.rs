@@ -0,0 +1,135 @@
+//! What a string literal holds, judged from where it is written.
+//!
+//! A few string arguments name something from a known list: the time zone of
+//! a date and time function or `DateTime` type, the value of a `Timezone`
+//! setting, and the format of table functions such as `file`. Time zone
+//! parameters are found in documented signatures; the conversion functions
+//! the data has no syntax for are listed here, as are table functions, which
+//! have no syntax in the data at all.
+
+use crate::clickhouse_index::ClickHouseIndex;
+
+/// A list of names a string literal can be completed from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ValueKind {
+    TimeZone,
+    Format,
+}
+
+/// What argument `argument` (0-based) of a call of `function` names, if
+/// anything. `function` may also be a parameterized type such as `DateTime`.
+pub(crate) fn argument_kind(
+    index: &ClickHouseIndex,
+    function: &str,
+    argument: usize,
+) -> Option<ValueKind> {
+    if format_argument(function) == Some(argument) {
+        return Some(ValueKind::Format);
+    }
+
+    let time_zone = match index.canonical_data_type(function) {
+        Some("DateTime") => argument == 0,
+        Some("DateTime64") => argument == 1,
+        _ => {
+            let signatures = index
+                .function(function)
+                .map(|f| index.signatures(f))
+                .unwrap_or_default();
+            if signatures.is_empty() {
+                undocumented_time_zone_argument(function) == Some(argument)
+            } else {
+                signatures.iter().any(|s| {
+                    s.required
+                        .iter()
+                        .chain(&s.optional)
+                        .nth(argument)
+                        .is_some_and(|param| is_time_zone_parameter(param))
+                })
+            }
+        }
+    };
+    time_zone.then_some(ValueKind::TimeZone)
+}
+
+/// What the value of setting `name` names, if anything
+pub(crate) fn setting_kind(index: &ClickHouseIndex, name: &str) -> Option<ValueKind> {
+    index
+        .setting(name)
+        .filter(|s| s.setting_type == "Timezone")
+        .map(|_| ValueKind::TimeZone)
+}
+
+fn is_time_zone_parameter(name: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    name.contains("timezone") || name.contains("time_zone")
+}
+
+/// Position of the format argument of table functions that take one at a
+/// fixed position
+fn format_argument(function: &str) -> Option<usize> {
+    match function {
+        "format" | "formatRow" | "formatRowNoNewline" => Some(0),
+        "file" | "url" | "hdfs" | "fileCluster" | "urlCluster" | "hdfsCluster" => Some(1),
+        _ => None,
+    }
+}
+
+/// Time zone position of conversion functions documented without a syntax
+fn undocumented_time_zone_argument(function: &str) -> Option<usize> {
+    let base = ["OrNull", "OrZero", "OrDefault"]
+        .iter()
+        .find_map(|suffix| function.strip_suffix(suffix))
+        .unwrap_or(function);
+    match base {
+        "toDateTime"
+        | "toDateTime32"
+        | "parseDateTimeBestEffort"
+        | "parseDateTime32BestEffort"
+        | "parseDateTimeBestEffortUS"
+        | "fromUnixTimestamp64Second"
+        | "fromUnixTimestamp64Milli"
+        | "fromUnixTimestamp64Micro"
+        | "fromUnixTimestamp64Nano" => Some(1),
+        "toDateTime64"
+        | "parseDateTime"
+        | "parseDateTime64"
+        | "parseDateTimeInJodaSyntax"
+        | "parseDateTime64InJodaSyntax"
+        | "parseDateTime64BestEffort"
+        | "parseDateTime64BestEffortUS" => Some(2),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clickhouse_index::test_data::clickhouse_25_8;
+
+    #[test]
+    fn test_time_zone_arguments() {
+        let index = clickhouse_25_8();
+        let kind = |function, argument| argument_kind(index, function, argument);
+        assert_eq!(kind("toDateTime", 1), Some(ValueKind::TimeZone));
+        assert_eq!(kind("toDateTime", 0), None);
+        assert_eq!(kind("toDateTime64OrNull", 2), Some(ValueKind::TimeZone));
+        // From the documented `formatDateTime(datetime, format[, timezone])`
+        assert_eq!(kind("formatDateTime", 2), Some(ValueKind::TimeZone));
+        assert_eq!(kind("formatDateTime", 1), None);
+        assert_eq!(kind("now", 0), Some(ValueKind::TimeZone));
+        assert_eq!(kind("DateTime64", 1), Some(ValueKind::TimeZone));
+        assert_eq!(kind("length", 0), None);
+    }
+
+    #[test]
+    fn test_format_and_setting_values() {
+        let index = clickhouse_25_8();
+        assert_eq!(argument_kind(index, "file", 1), Some(ValueKind::Format));
+        assert_eq!(argument_kind(index, "file", 0), None);
+        assert_eq!(
+            setting_kind(index, "session_timezone"),
+            Some(ValueKind::TimeZone)
+        );
+        assert_eq!(setting_kind(index, "max_threads"), None);
+    }
+}
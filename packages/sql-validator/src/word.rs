@@ -0,0 +1,185 @@
+//! The word at the cursor, which completions are matched against and replace.
+//!
+//! Completions are matched against the part of the word before the cursor
+//! but replace all of it, so that choosing `toStartOfHour` with the cursor in
+//! `toStart|OfDay` leaves no `OfDay` behind, and choosing `sumIfOrNull` after
+//! `sumIfOr` replaces the whole name rather than the combinator being typed.
+//! A word is a run of identifier characters and ends at a `.`: in `db.events`
+//! only `events` is replaced. A quoted identifier is replaced together with
+//! its quotes, and in a string literal everything between the quotes is.
+
+use crate::context::{self, SqlContext};
+use crate::{position, TextRange};
+
+/// The word at the cursor
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct TypedWord<'a> {
+    /// What has been typed of it, without any opening quote
+    pub text: &'a str,
+    /// Byte range a completion replaces
+    pub range: TextRange,
+}
+
+/// The word at `cursor_offset`, which must be a char boundary, in `context`
+pub(crate) fn typed_word<'a>(
+    sql: &'a str,
+    cursor_offset: usize,
+    context: &SqlContext,
+) -> TypedWord<'a> {
+    let (before, after) = sql.split_at(cursor_offset);
+    let in_string = matches!(
+        context,
+        SqlContext::StringArgument { .. }
+            | SqlContext::SettingValue { .. }
+            | SqlContext::StringLiteral
+    );
+
+    // Where the typed text starts, where the replaced range starts, and how
+    // far past the cursor it runs
+    let quoted = if in_string {
+        before
+            .rfind('\'')
+            .map(|quote| (quote + 1, quote + 1, closing(after, '\'', false)))
+    } else {
+        context::open_identifier(before)
+            .map(|(start, quote)| (start + 1, start, closing(after, quote, true)))
+    };
+    let (text_start, start, rest) = quoted.unwrap_or_else(|| {
+        let start = cursor_offset - word_before(sql, cursor_offset).len();
+        (start, start, word_after(after).len())
+    });
+    TypedWord {
+        text: &sql[text_start..cursor_offset],
+        range: TextRange {
+            start,
+            end: cursor_offset + rest,
+        },
+    }
+}
+
+/// The identifier characters directly before the cursor
+pub(crate) fn word_before(sql: &str, cursor_offset: usize) -> &str {
+    let before = &sql[..position::floor_char_boundary(sql, cursor_offset)];
+    let start = before
+        .char_indices()
+        .rev()
+        .take_while(|&(_, c)| is_identifier_char(c))
+        .last()
+        .map_or(before.len(), |(i, _)| i);
+    &before[start..]
+}
+
+/// The name a word starting at `word_start` is qualified with: `e` in `e.id`
+/// and `db` in `` `db`.events ``, or `None` when no `.` precedes the word
+pub(crate) fn qualifier(sql: &str, word_start: usize) -> Option<&str> {
+    let before = sql[..word_start].strip_suffix('.')?;
+    let name = match before.chars().last()? {
+        quote @ ('`' | '"') => {
+            let inside = &before[..before.len() - 1];
+            &inside[inside.rfind(quote)? + 1..]
+        }
+        _ => word_before(before, before.len()),
+    };
+    (!name.is_empty()).then_some(name)
+}
+
+/// The identifier characters at the start of `text`
+fn word_after(text: &str) -> &str {
+    let end = text
+        .char_indices()
+        .find(|&(_, c)| !is_identifier_char(c))
+        .map_or(text.len(), |(i, _)| i);
+    &text[..end]
+}
+
+/// Length of the rest of a quoted token in `after`, up to its closing `quote`
+/// and past it when `inclusive`. Without a closing quote on the line the
+/// token is taken to end at the cursor, as the quote found further on would
+/// open something else.
+fn closing(after: &str, quote: char, inclusive: bool) -> usize {
+    let line = after.split('\n').next().unwrap_or_default();
+    match line.find(quote) {
+        Some(end) if inclusive => end + quote.len_utf8(),
+        Some(end) => end,
+        None => 0,
+    }
+}
+
+fn is_identifier_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context::detect_context;
+
+    /// The word at the `|` in `marked`, as its text and the replaced text
+    fn word_at(marked: &str) -> (String, String) {
+        let cursor = marked.find('|').expect("cursor marked");
+        let sql = marked.replacen('|', "", 1);
+        let word = typed_word(&sql, cursor, &detect_context(&sql, cursor));
+        (
+            word.text.to_string(),
+            sql[word.range.start..word.range.end].to_string(),
+        )
+    }
+
+    fn pair(text: &str, replaced: &str) -> (String, String) {
+        (text.to_string(), replaced.to_string())
+    }
+
+    #[test]
+    fn test_replaces_the_whole_word() {
+        assert_eq!(
+            word_at("SELECT toStart|OfDay(ts)"),
+            pair("toStart", "toStartOfDay")
+        );
+        assert_eq!(word_at("SELECT sumIfOr|"), pair("sumIfOr", "sumIfOr"));
+        assert_eq!(word_at("SELECT | FROM t"), pair("", ""));
+        assert_eq!(word_at("SELECT é_|x, 1"), pair("é_", "é_x"));
+    }
+
+    #[test]
+    fn test_dotted_names_replace_their_last_part() {
+        assert_eq!(word_at("SELECT * FROM db.ev|ents"), pair("ev", "events"));
+        assert_eq!(word_at("SELECT t.|"), pair("", ""));
+    }
+
+    #[test]
+    fn test_quoted_identifiers_are_replaced_with_their_quotes() {
+        assert_eq!(
+            word_at("SELECT `my co|l` FROM t"),
+            pair("my co", "`my col`")
+        );
+        assert_eq!(word_at("SELECT \"co|"), pair("co", "\"co"));
+        assert_eq!(word_at("SELECT * FROM `db`.`ev|"), pair("ev", "`ev"));
+    }
+
+    #[test]
+    fn test_strings_replace_their_contents() {
+        assert_eq!(
+            word_at("SELECT toDateTime(ts, 'Europe/Ber|lin')"),
+            pair("Europe/Ber", "Europe/Berlin")
+        );
+        assert_eq!(
+            word_at("SELECT toDateTime(ts, 'Eu|\n) FROM t WHERE a = 'x'"),
+            pair("Eu", "Eu")
+        );
+    }
+
+    #[test]
+    fn test_qualifiers() {
+        assert_eq!(qualifier("SELECT e.", 9), Some("e"));
+        assert_eq!(qualifier("SELECT `my db`.ev", 15), Some("my db"));
+        assert_eq!(qualifier("SELECT ev", 7), None);
+        assert_eq!(qualifier("SELECT 1 + .", 12), None);
+    }
+
+    #[test]
+    fn test_word_before_cursor() {
+        assert_eq!(word_before("SELECT avgMerge", 15), "avgMerge");
+        assert_eq!(word_before("SELECT (", 8), "");
+        assert_eq!(word_before("SELECT é_x", 11), "é_x");
+    }
+}
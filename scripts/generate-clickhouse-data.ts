@@ -25,6 +25,7 @@ interface ExtractionResult {
   formats: FormatInfo[];
   tableFunctions: TableFunctionInfo[];
   aggregateCombinators: string[];
+  timeZones: string[];
   settings: SettingInfo[];
   mergeTreeSettings: SettingInfo[];
 }
@@ -392,6 +393,14 @@ async function extractData(version: string): Promise<ExtractionResult> {
       aggregateCombinators,
     );
 
+    // Extract time zones
+    console.log('  - system.time_zones');
+    const rawTimeZones = runQuery<Array<{ time_zone: string }>>(
+      containerName,
+      'SELECT time_zone FROM system.time_zones ORDER BY time_zone',
+    );
+    const timeZones = rawTimeZones.map((z) => z.time_zone);
+
     // Extract settings
     console.log('  - system.settings');
     const rawSettings = runQuery<
@@ -440,6 +449,7 @@ async function extractData(version: string): Promise<ExtractionResult> {
       formats,
       tableFunctions,
       aggregateCombinators,
+      timeZones,
       settings,
       mergeTreeSettings,
     };
@@ -506,6 +516,7 @@ async function main() {
     console.log(`Formats: ${data.formats.length}`);
     console.log(`Table Functions: ${data.tableFunctions.length}`);
     console.log(`Aggregate Combinators: ${data.aggregateCombinators.length}`);
+    console.log(`Time Zones: ${data.timeZones.length}`);
     console.log(`Settings: ${data.settings.length}`);
     console.log(`MergeTree Settings: ${data.mergeTreeSettings.length}`);
     console.log(`\nOutput written to: ${outputFile}`);